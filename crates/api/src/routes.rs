@@ -1,13 +1,15 @@
 use crate::state::AppState;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
-    routing::{get, post},
+    routing::{delete, get, post},
     Json, Router,
 };
+use propbot_core::DataProvider;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use uuid::Uuid;
 
 pub fn api_routes() -> Router<Arc<AppState>> {
     Router::new()
@@ -16,8 +18,12 @@ pub fn api_routes() -> Router<Arc<AppState>> {
         // Strategies
         .route("/strategies", get(list_strategies))
         // Backtests
+        .route("/backtest", get(list_backtests))
         .route("/backtest", post(run_backtest))
+        .route("/backtest/compare", get(compare_backtests))
         .route("/backtest/{id}", get(get_backtest_result))
+        .route("/backtest/{id}", delete(delete_backtest_result))
+        .route("/backtest/{id}/analytics", get(backtest_analytics))
         // Bots
         .route("/bots", get(list_bots))
         .route("/bots", post(create_bot))
@@ -116,17 +122,231 @@ async fn run_backtest(
     )
 }
 
+#[derive(Deserialize)]
+struct BacktestListQuery {
+    strategy: Option<String>,
+    instrument: Option<String>,
+    /// Runs whose test period overlaps `start..=end` (YYYY-MM-DD).
+    start: Option<chrono::NaiveDate>,
+    end: Option<chrono::NaiveDate>,
+    /// Comma-separated metric thresholds, e.g. `sharpe_ratio>=1,net_profit>0`.
+    filter: Option<String>,
+    limit: Option<i64>,
+}
+
+async fn list_backtests(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BacktestListQuery>,
+) -> impl IntoResponse {
+    let metrics: Result<Vec<propbot_data::db::MetricFilter>, String> = query
+        .filter
+        .iter()
+        .flat_map(|f| f.split(','))
+        .filter(|f| !f.trim().is_empty())
+        .map(str::parse)
+        .collect();
+    let metrics = match metrics {
+        Ok(metrics) => metrics,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": e})),
+            );
+        }
+    };
+    let filter = propbot_data::db::BacktestFilter {
+        strategy_id: query.strategy,
+        instrument: query.instrument,
+        start: query
+            .start
+            .map(|d| d.and_time(chrono::NaiveTime::MIN).and_utc()),
+        end: query.end.map(|d| {
+            d.and_hms_milli_opt(23, 59, 59, 999)
+                .expect("valid time")
+                .and_utc()
+        }),
+        metrics,
+        limit: query.limit,
+    };
+
+    match propbot_data::db::list_backtest_results(&state.db, &filter).await {
+        Ok(results) => (StatusCode::OK, Json(serde_json::to_value(results).unwrap())),
+        Err(e) => database_error(e),
+    }
+}
+
 async fn get_backtest_result(
-    State(_state): State<Arc<AppState>>,
-    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    match propbot_data::db::load_backtest_result(&state.db, id).await {
+        Ok(Some(result)) => (StatusCode::OK, Json(serde_json::to_value(result).unwrap())),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": "Backtest result not found",
+                "id": id,
+            })),
+        ),
+        Err(e) => database_error(e),
+    }
+}
+
+async fn delete_backtest_result(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
 ) -> impl IntoResponse {
-    // TODO: Look up from database
+    match propbot_data::db::delete_backtest_result(&state.db, id).await {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(serde_json::json!({"status": "deleted", "id": id})),
+        ),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": "Backtest result not found",
+                "id": id,
+            })),
+        ),
+        Err(e) => database_error(e),
+    }
+}
+
+#[derive(Deserialize)]
+struct AnalyticsQuery {
+    /// Timeframe to take ATR from for volatility regimes; defaults to the
+    /// timeframe the backtest ran on.
+    timeframe: Option<String>,
+    #[serde(default = "default_atr_period")]
+    atr_period: usize,
+}
+
+fn default_atr_period() -> usize {
+    14
+}
+
+async fn backtest_analytics(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<AnalyticsQuery>,
+) -> impl IntoResponse {
+    let timeframe: Option<propbot_core::Timeframe> = match query.timeframe.map(|t| t.parse()) {
+        Some(Ok(timeframe)) => Some(timeframe),
+        Some(Err(e)) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": e})),
+            );
+        }
+        None => None,
+    };
+    let result = match propbot_data::db::load_backtest_result(&state.db, id).await {
+        Ok(Some(result)) => result,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({
+                    "error": "Backtest result not found",
+                    "id": id,
+                })),
+            );
+        }
+        Err(e) => return database_error(e),
+    };
+
+    // Volatility regimes need bars, built from whatever is stored; without
+    // a timeframe or any stored data they are left empty
+    let atr = match timeframe.or(result.timeframe) {
+        Some(timeframe) => {
+            let provider = propbot_data::PostgresDataProvider::new(state.db.clone());
+            let bars = provider.stream_bars(
+                &result.instrument,
+                timeframe,
+                result.start_date,
+                result.end_date,
+            );
+            match propbot_engine::AtrSeries::from_stream(query.atr_period.max(1), timeframe, bars)
+                .await
+            {
+                Ok(atr) => Some(atr),
+                Err(e) => return data_error(e),
+            }
+        }
+        None => None,
+    };
+
+    let analytics = propbot_engine::analyze_trades(&result.trades, atr.as_ref());
     (
-        StatusCode::NOT_FOUND,
-        Json(serde_json::json!({
-            "error": "Backtest result not found",
-            "id": id,
-        })),
+        StatusCode::OK,
+        Json(serde_json::to_value(analytics).unwrap()),
+    )
+}
+
+#[derive(Deserialize)]
+struct CompareQuery {
+    /// Comma-separated backtest result IDs; the first is the baseline.
+    ids: String,
+    /// Samples per overlaid equity curve.
+    #[serde(default = "default_compare_points")]
+    points: usize,
+}
+
+fn default_compare_points() -> usize {
+    200
+}
+
+async fn compare_backtests(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CompareQuery>,
+) -> impl IntoResponse {
+    let ids: Result<Vec<Uuid>, _> = query.ids.split(',').map(|id| id.trim().parse()).collect();
+    let ids = match ids {
+        Ok(ids) if ids.len() >= 2 => ids,
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": "Give two or more backtest result IDs"})),
+            );
+        }
+    };
+
+    let mut results = Vec::with_capacity(ids.len());
+    for id in ids {
+        match propbot_data::db::load_backtest_result(&state.db, id).await {
+            Ok(Some(result)) => results.push(result),
+            Ok(None) => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(serde_json::json!({
+                        "error": "Backtest result not found",
+                        "id": id,
+                    })),
+                );
+            }
+            Err(e) => return database_error(e),
+        }
+    }
+
+    let comparison = propbot_engine::compare_results(&results, query.points.clamp(2, 10_000));
+    (
+        StatusCode::OK,
+        Json(serde_json::to_value(comparison).unwrap()),
+    )
+}
+
+fn database_error(e: sqlx::Error) -> (StatusCode, Json<serde_json::Value>) {
+    tracing::error!("Database error: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({"error": "Database error"})),
+    )
+}
+
+fn data_error(e: propbot_core::DataError) -> (StatusCode, Json<serde_json::Value>) {
+    tracing::error!("Data error: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({"error": "Data error"})),
     )
 }
 
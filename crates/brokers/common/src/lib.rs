@@ -1,3 +1,4 @@
 pub mod simulated;
+pub mod wire;
 
 pub use propbot_core::{Broker, BrokerError};
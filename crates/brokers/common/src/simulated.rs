@@ -1,8 +1,8 @@
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Duration, NaiveTime, Utc};
 use propbot_core::*;
 use rust_decimal::Decimal;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
@@ -19,6 +19,11 @@ pub struct SimulatedBrokerConfig {
     pub tick_size: Decimal,
     /// Tick value (for PnL calculation).
     pub tick_value: Decimal,
+    /// Session close (UTC) at which `Day` orders expire when the broker has
+    /// no calendar. It is fixed in UTC, so it doesn't follow daylight saving
+    /// or early closes; use [`SimulatedBroker::with_calendar`] for exchange
+    /// hours.
+    pub session_close_utc: NaiveTime,
 }
 
 impl Default for SimulatedBrokerConfig {
@@ -29,24 +34,52 @@ impl Default for SimulatedBrokerConfig {
             slippage_ticks: Decimal::ONE,
             tick_size: Decimal::new(25, 2),  // 0.25 (e.g., ES futures)
             tick_value: Decimal::new(1250, 2), // $12.50 per tick
+            session_close_utc: NaiveTime::from_hms_opt(21, 0, 0).unwrap(), // 4pm CDT, 3pm CST
         }
     }
 }
 
+/// Key for a strategy's virtual position: (strategy id, instrument).
+type PositionKey = (Option<String>, String);
+
 /// A simulated broker for backtesting.
 ///
 /// Processes orders against historical data, simulating fills with
 /// configurable slippage and commissions.
+///
+/// Each strategy holds its own virtual position per instrument, so strategies
+/// trading the same symbol don't net against each other; trades and realized
+/// PnL are booked against those. The net account position per instrument is
+/// derived from the virtual positions.
 pub struct SimulatedBroker {
     config: SimulatedBrokerConfig,
     account: AccountState,
+    /// Net account position per instrument.
     positions: HashMap<String, Position>,
+    /// Virtual positions per (strategy, instrument).
+    strategy_positions: HashMap<PositionKey, Position>,
+    /// Lowest and highest price seen while each virtual position is open.
+    excursions: HashMap<PositionKey, (Decimal, Decimal)>,
     active_orders: Vec<Order>,
+    /// Stop-limit orders whose stop has been hit and now rest as limits.
+    triggered_stops: HashSet<Uuid>,
+    /// When working Day/GTD orders expire.
+    expiries: HashMap<Uuid, DateTime<Utc>>,
+    /// Order events not yet collected by the engine.
+    events: Vec<OrderEvent>,
+    /// Owning strategy of every order submitted with a strategy ID.
+    order_owners: HashMap<Uuid, String>,
     filled_orders: Vec<Order>,
     trades: Vec<Trade>,
+    /// Every order state change, in order.
+    order_log: Vec<OrderUpdate>,
+    /// How much of the order log `take_order_updates` has handed out.
+    order_log_taken: usize,
     connected: bool,
     /// Current bar being processed (set by the engine).
     current_bar: Option<Bar>,
+    /// Exchange calendar whose session closes `Day` orders expire at.
+    calendar: Option<TradingCalendar>,
 }
 
 impl SimulatedBroker {
@@ -56,24 +89,58 @@ impl SimulatedBroker {
             config,
             account,
             positions: HashMap::new(),
+            strategy_positions: HashMap::new(),
+            excursions: HashMap::new(),
             active_orders: Vec::new(),
+            triggered_stops: HashSet::new(),
+            expiries: HashMap::new(),
+            events: Vec::new(),
+            order_owners: HashMap::new(),
             filled_orders: Vec::new(),
             trades: Vec::new(),
+            order_log: Vec::new(),
+            order_log_taken: 0,
             connected: false,
             current_bar: None,
+            calendar: None,
         }
     }
 
+    /// Expire `Day` orders at the calendar's session closes, early closes
+    /// included, instead of the configured `session_close_utc`.
+    pub fn with_calendar(mut self, calendar: TradingCalendar) -> Self {
+        self.calendar = Some(calendar);
+        self
+    }
+
     /// Set the current bar (called by the engine on each step).
     pub fn set_current_bar(&mut self, bar: Bar) {
         self.current_bar = Some(bar.clone());
         // Update unrealized PnL for all positions
-        for pos in self.positions.values_mut() {
+        for pos in self.strategy_positions.values_mut() {
             pos.update_pnl(bar.close, self.config.tick_size, self.config.tick_value);
         }
+        self.refresh_net_positions();
         self.update_account_equity();
+        // Expire Day/GTD orders before they get a chance to fill
+        self.expire_orders(bar.timestamp);
         // Process working orders against this bar
         self.process_pending_orders(&bar);
+        // Positions still open saw the whole bar; ones opened during it only
+        // count prices from their fill on
+        for (key, pos) in &self.strategy_positions {
+            if pos.opened_at < bar.timestamp {
+                if let Some((low, high)) = self.excursions.get_mut(key) {
+                    *low = (*low).min(bar.low);
+                    *high = (*high).max(bar.high);
+                }
+            }
+        }
+    }
+
+    /// Take the order events generated since the last call.
+    pub fn drain_events(&mut self) -> Vec<OrderEvent> {
+        std::mem::take(&mut self.events)
     }
 
     /// Get the trade log.
@@ -81,47 +148,252 @@ impl SimulatedBroker {
         &self.trades
     }
 
+    /// Get the order log.
+    pub fn order_log(&self) -> &[OrderUpdate] {
+        &self.order_log
+    }
+
+    /// Log an order that was rejected before reaching the broker (e.g. by
+    /// the risk manager).
+    pub fn log_rejection(&mut self, order: &Order, reason: &str) {
+        let mut order = order.clone();
+        order.status = OrderStatus::Rejected;
+        order.updated_at = self.now();
+        self.log_order(OrderUpdateKind::Rejected, &order, None, Some(reason));
+    }
+
     /// Get the current account state (non-async).
     pub fn account(&self) -> &AccountState {
         &self.account
     }
 
-    /// Simulate filling a market order at the current bar.
-    fn simulate_fill(&mut self, order: &mut Order) -> Option<Fill> {
+    /// Get the working (unfilled) orders (non-async).
+    pub fn working_orders(&self) -> &[Order] {
+        &self.active_orders
+    }
+
+    /// Get the strategy that submitted an order, if it was tagged with one.
+    pub fn order_owner(&self, order_id: Uuid) -> Option<&str> {
+        self.order_owners.get(&order_id).map(String::as_str)
+    }
+
+    /// Get the net account position for an instrument, if any (non-async).
+    pub fn position(&self, instrument: &str) -> Option<&Position> {
+        self.positions.get(instrument)
+    }
+
+    /// Get a strategy's virtual position on an instrument, if any (non-async).
+    pub fn strategy_position(&self, strategy_id: &str, instrument: &str) -> Option<&Position> {
+        self.strategy_positions
+            .get(&(Some(strategy_id.to_string()), instrument.to_string()))
+    }
+
+    /// Get all of a strategy's open virtual positions (non-async).
+    pub fn strategy_positions(&self, strategy_id: &str) -> Vec<&Position> {
+        self.strategy_positions
+            .values()
+            .filter(|p| p.strategy_id.as_deref() == Some(strategy_id))
+            .collect()
+    }
+
+    /// The simulated clock: the current bar's time, or wall-clock before any data.
+    fn now(&self) -> DateTime<Utc> {
+        self.current_bar
+            .as_ref()
+            .map(|b| b.timestamp)
+            .unwrap_or_else(Utc::now)
+    }
+
+    /// The first session close strictly after `time`.
+    fn session_close_after(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        if let Some(calendar) = &self.calendar {
+            return calendar.next_close(time);
+        }
+        let close = time
+            .date_naive()
+            .and_time(self.config.session_close_utc)
+            .and_utc();
+        if time < close {
+            close
+        } else {
+            close + Duration::days(1)
+        }
+    }
+
+    /// Append a change in an order's state to the order log.
+    fn log_order(
+        &mut self,
+        kind: OrderUpdateKind,
+        order: &Order,
+        fill: Option<&Fill>,
+        reason: Option<&str>,
+    ) {
+        self.order_log.push(OrderUpdate {
+            kind,
+            order: order.clone(),
+            fill: fill.cloned(),
+            reason: reason.map(str::to_string),
+            timestamp: order.updated_at,
+        });
+    }
+
+    /// Add an order to the working set, tracking when it expires.
+    fn rest_order(&mut self, order: &Order) {
+        self.track_expiry(order);
+        self.active_orders.push(order.clone());
+    }
+
+    /// Track when a working order expires under its time in force.
+    fn track_expiry(&mut self, order: &Order) {
+        let expiry = match order.time_in_force {
+            TimeInForce::Day => Some(self.session_close_after(order.updated_at)),
+            TimeInForce::Gtd(at) => Some(at),
+            TimeInForce::Gtc | TimeInForce::Ioc | TimeInForce::Fok => None,
+        };
+        match expiry {
+            Some(at) => self.expiries.insert(order.id, at),
+            None => self.expiries.remove(&order.id),
+        };
+    }
+
+    /// Remove a working order and record its cancellation.
+    fn cancel_working(&mut self, order_id: Uuid, reason: &str) -> Option<Order> {
+        let idx = self.active_orders.iter().position(|o| o.id == order_id)?;
+        let mut order = self.active_orders.remove(idx);
+        self.triggered_stops.remove(&order_id);
+        self.expiries.remove(&order_id);
+        order.status = OrderStatus::Cancelled;
+        order.updated_at = self.now();
+        self.log_order(OrderUpdateKind::Cancelled, &order, None, Some(reason));
+        self.events.push(OrderEvent::Cancelled {
+            order_id,
+            reason: reason.to_string(),
+        });
+        self.filled_orders.push(order.clone());
+        Some(order)
+    }
+
+    /// Cancel working orders whose time in force has run out.
+    fn expire_orders(&mut self, now: DateTime<Utc>) {
+        let expired: Vec<Uuid> = self
+            .active_orders
+            .iter()
+            .filter(|o| self.expiries.get(&o.id).is_some_and(|at| *at <= now))
+            .map(|o| o.id)
+            .collect();
+        for order_id in expired {
+            self.cancel_working(order_id, "Time in force expired");
+        }
+    }
+
+    /// Execute an IOC/FOK order against the current bar, cancelling whatever can't fill.
+    fn execute_immediate(&mut self, order: &mut Order) {
+        let (marketable, limit_price, volume) = match self.current_bar.as_ref() {
+            Some(bar) => {
+                let (marketable, limit_price) = match (order.order_type, order.price) {
+                    (OrderType::Market, _) => (true, None),
+                    (OrderType::Limit, Some(price)) => match order.side {
+                        Side::Buy => (bar.close <= price, Some(bar.close)),
+                        Side::Sell => (bar.close >= price, Some(bar.close)),
+                    },
+                    _ => (false, None),
+                };
+                (marketable, limit_price, bar.volume)
+            }
+            None => (false, None, Decimal::ZERO),
+        };
+
+        // The bar's volume caps what can trade immediately (zero volume = unknown, no cap)
+        let available = if volume > Decimal::ZERO {
+            volume.min(order.quantity)
+        } else {
+            order.quantity
+        };
+        let fill_qty = match order.time_in_force {
+            TimeInForce::Fok if available < order.quantity => Decimal::ZERO,
+            _ => available,
+        };
+
+        if marketable && fill_qty > Decimal::ZERO {
+            self.simulate_fill(order, limit_price, fill_qty);
+        }
+
+        if order.filled_quantity < order.quantity {
+            let reason = if !marketable {
+                "Not immediately executable"
+            } else if order.time_in_force == TimeInForce::Fok {
+                "Insufficient liquidity to fill entire quantity"
+            } else {
+                "Unfilled remainder of IOC order"
+            };
+            order.status = OrderStatus::Cancelled;
+            self.log_order(OrderUpdateKind::Cancelled, order, None, Some(reason));
+            self.events.push(OrderEvent::Cancelled {
+                order_id: order.id,
+                reason: reason.to_string(),
+            });
+        }
+    }
+
+    /// Simulate filling `quantity` of an order at the current bar.
+    ///
+    /// Fills at `limit_price` when given, otherwise as a market order at the
+    /// bar close with slippage.
+    fn simulate_fill(
+        &mut self,
+        order: &mut Order,
+        limit_price: Option<Decimal>,
+        quantity: Decimal,
+    ) -> Option<Fill> {
         let bar = self.current_bar.as_ref()?;
 
         // Determine fill price with slippage
         let slippage = self.config.slippage_ticks * self.config.tick_size;
-        let fill_price = match order.side {
-            Side::Buy => bar.close + slippage,
-            Side::Sell => bar.close - slippage,
+        let fill_price = match (limit_price, order.side) {
+            (Some(price), _) => price,
+            (None, Side::Buy) => bar.close + slippage,
+            (None, Side::Sell) => bar.close - slippage,
         };
 
-        let commission = self.config.commission_per_contract * order.quantity;
+        let commission = self.config.commission_per_contract * quantity;
 
         let fill = Fill {
             order_id: order.id,
             instrument: order.instrument.clone(),
             side: order.side,
-            quantity: order.quantity,
+            quantity,
             price: fill_price,
             commission,
             timestamp: bar.timestamp,
         };
 
-        order.filled_quantity = order.quantity;
-        order.status = OrderStatus::Filled;
+        order.filled_quantity += quantity;
+        order.status = if order.filled_quantity >= order.quantity {
+            OrderStatus::Filled
+        } else {
+            OrderStatus::PartiallyFilled
+        };
         order.updated_at = bar.timestamp;
 
         // Update positions
-        self.apply_fill(&fill);
+        self.apply_fill(&fill, order.strategy_id.clone());
+        let (kind, event) = match order.status {
+            OrderStatus::Filled => (OrderUpdateKind::Filled, OrderEvent::Filled(fill.clone())),
+            _ => (
+                OrderUpdateKind::PartiallyFilled,
+                OrderEvent::PartiallyFilled(fill.clone()),
+            ),
+        };
+        self.log_order(kind, order, Some(&fill), None);
+        self.events.push(event);
 
         Some(fill)
     }
 
-    /// Apply a fill to positions and account.
-    fn apply_fill(&mut self, fill: &Fill) {
-        let existing = self.positions.get(&fill.instrument);
+    /// Apply a fill to the originating strategy's position and the account.
+    fn apply_fill(&mut self, fill: &Fill, strategy_id: Option<String>) {
+        let key: PositionKey = (strategy_id.clone(), fill.instrument.clone());
+        let existing = self.strategy_positions.get(&key);
 
         match existing {
             Some(pos) if pos.side != fill.side => {
@@ -131,6 +403,16 @@ impl SimulatedBroker {
 
                 // Close the position (or part of it)
                 let pnl = self.compute_pnl(pos, fill.price, close_qty);
+                let (low, high) = self
+                    .excursions
+                    .get(&key)
+                    .map_or((fill.price, fill.price), |(low, high)| {
+                        ((*low).min(fill.price), (*high).max(fill.price))
+                    });
+                let (worst, best) = match pos.side {
+                    Side::Buy => (low, high),
+                    Side::Sell => (high, low),
+                };
                 let trade = Trade {
                     id: Uuid::new_v4(),
                     instrument: fill.instrument.clone(),
@@ -143,6 +425,8 @@ impl SimulatedBroker {
                     entry_time: pos.opened_at,
                     exit_time: fill.timestamp,
                     strategy_id: pos.strategy_id.clone(),
+                    mae: (-self.compute_pnl(pos, worst, close_qty)).max(Decimal::ZERO),
+                    mfe: self.compute_pnl(pos, best, close_qty).max(Decimal::ZERO),
                 };
                 self.trades.push(trade);
 
@@ -151,17 +435,21 @@ impl SimulatedBroker {
                 self.account.realized_pnl += pnl;
                 self.account.daily_pnl += pnl - fill.commission;
 
-                let pos = self.positions.get_mut(&fill.instrument).unwrap();
+                let pos = self.strategy_positions.get_mut(&key).unwrap();
                 if close_qty >= pos.quantity {
-                    self.positions.remove(&fill.instrument);
+                    self.strategy_positions.remove(&key);
+                    self.excursions.remove(&key);
                 } else {
                     pos.quantity -= close_qty;
+                    pos.realized_pnl += pnl;
                 }
 
                 // If there's remaining quantity, open a new position in the opposite direction
                 if remaining_qty > Decimal::ZERO {
-                    self.positions.insert(
-                        fill.instrument.clone(),
+                    self.excursions
+                        .insert(key.clone(), (fill.price, fill.price));
+                    self.strategy_positions.insert(
+                        key,
                         Position {
                             instrument: fill.instrument.clone(),
                             side: fill.side,
@@ -170,24 +458,30 @@ impl SimulatedBroker {
                             unrealized_pnl: Decimal::ZERO,
                             realized_pnl: Decimal::ZERO,
                             opened_at: fill.timestamp,
-                            strategy_id: None,
+                            strategy_id,
                         },
                     );
                 }
             }
             Some(pos) if pos.side == fill.side => {
                 // Adding to existing position
-                let pos = self.positions.get_mut(&fill.instrument).unwrap();
+                let pos = self.strategy_positions.get_mut(&key).unwrap();
                 let total_cost = pos.avg_entry_price * pos.quantity + fill.price * fill.quantity;
                 pos.quantity += fill.quantity;
                 pos.avg_entry_price = total_cost / pos.quantity;
+                if let Some((low, high)) = self.excursions.get_mut(&key) {
+                    *low = (*low).min(fill.price);
+                    *high = (*high).max(fill.price);
+                }
                 self.account.balance -= fill.commission;
                 self.account.daily_pnl -= fill.commission;
             }
             _ => {
                 // New position
-                self.positions.insert(
-                    fill.instrument.clone(),
+                self.excursions
+                    .insert(key.clone(), (fill.price, fill.price));
+                self.strategy_positions.insert(
+                    key,
                     Position {
                         instrument: fill.instrument.clone(),
                         side: fill.side,
@@ -196,7 +490,7 @@ impl SimulatedBroker {
                         unrealized_pnl: Decimal::ZERO,
                         realized_pnl: Decimal::ZERO,
                         opened_at: fill.timestamp,
-                        strategy_id: None,
+                        strategy_id,
                     },
                 );
                 self.account.balance -= fill.commission;
@@ -204,9 +498,63 @@ impl SimulatedBroker {
             }
         }
 
+        self.refresh_net_positions();
         self.update_account_equity();
     }
 
+    /// Rebuild the net account positions from the strategies' virtual positions.
+    ///
+    /// The net position's unrealized PnL includes offsetting legs, so it still
+    /// counts profit locked in by strategies holding opposite sides.
+    fn refresh_net_positions(&mut self) {
+        let mut legs: HashMap<&str, Vec<&Position>> = HashMap::new();
+        for pos in self.strategy_positions.values() {
+            legs.entry(pos.instrument.as_str()).or_default().push(pos);
+        }
+
+        let mut net = HashMap::with_capacity(legs.len());
+        for (instrument, legs) in legs {
+            let signed: Decimal = legs
+                .iter()
+                .map(|p| match p.side {
+                    Side::Buy => p.quantity,
+                    Side::Sell => -p.quantity,
+                })
+                .sum();
+            if signed.is_zero() {
+                continue;
+            }
+            let side = if signed > Decimal::ZERO {
+                Side::Buy
+            } else {
+                Side::Sell
+            };
+            let same_side: Vec<&&Position> = legs.iter().filter(|p| p.side == side).collect();
+            let same_side_qty: Decimal = same_side.iter().map(|p| p.quantity).sum();
+            let cost: Decimal = same_side
+                .iter()
+                .map(|p| p.avg_entry_price * p.quantity)
+                .sum();
+            net.insert(
+                instrument.to_string(),
+                Position {
+                    instrument: instrument.to_string(),
+                    side,
+                    quantity: signed.abs(),
+                    avg_entry_price: cost / same_side_qty,
+                    unrealized_pnl: legs.iter().map(|p| p.unrealized_pnl).sum(),
+                    realized_pnl: legs.iter().map(|p| p.realized_pnl).sum(),
+                    opened_at: same_side.iter().map(|p| p.opened_at).min().unwrap(),
+                    strategy_id: match legs.as_slice() {
+                        [only] => only.strategy_id.clone(),
+                        _ => None,
+                    },
+                },
+            );
+        }
+        self.positions = net;
+    }
+
     fn compute_pnl(&self, pos: &Position, exit_price: Decimal, quantity: Decimal) -> Decimal {
         let price_diff = match pos.side {
             Side::Buy => exit_price - pos.avg_entry_price,
@@ -217,7 +565,11 @@ impl SimulatedBroker {
     }
 
     fn update_account_equity(&mut self) {
-        let unrealized: Decimal = self.positions.values().map(|p| p.unrealized_pnl).sum();
+        let unrealized: Decimal = self
+            .strategy_positions
+            .values()
+            .map(|p| p.unrealized_pnl)
+            .sum();
         self.account.unrealized_pnl = unrealized;
         self.account.equity = self.account.balance + unrealized;
         self.account.open_positions = self.positions.len();
@@ -230,55 +582,221 @@ impl SimulatedBroker {
 
     /// Process pending limit/stop orders against a bar.
     fn process_pending_orders(&mut self, bar: &Bar) {
-        let mut to_fill = Vec::new();
+        // (index, limit price to fill at — `None` fills like a market order)
+        let mut to_fill: Vec<(usize, Option<Decimal>)> = Vec::new();
+        let tick_size = self.config.tick_size;
 
-        for (i, order) in self.active_orders.iter().enumerate() {
+        for (i, order) in self.active_orders.iter_mut().enumerate() {
             match order.order_type {
                 OrderType::Limit => {
                     if let Some(price) = order.price {
-                        let triggered = match order.side {
-                            Side::Buy => bar.low <= price,
-                            Side::Sell => bar.high >= price,
-                        };
-                        if triggered {
-                            to_fill.push(i);
+                        if limit_touched(order.side, price, bar) {
+                            to_fill.push((i, Some(limit_fill_price(order.side, price, bar))));
                         }
                     }
                 }
                 OrderType::Stop => {
                     if let Some(stop_price) = order.stop_price {
-                        let triggered = match order.side {
-                            Side::Buy => bar.high >= stop_price,
-                            Side::Sell => bar.low <= stop_price,
-                        };
-                        if triggered {
-                            to_fill.push(i);
+                        if stop_touched(order.side, stop_price, bar) {
+                            to_fill.push((i, None));
                         }
                     }
                 }
-                _ => {}
+                OrderType::StopLimit => {
+                    if let (Some(stop_price), Some(limit)) = (order.stop_price, order.price) {
+                        // Resting as a limit from the open if it triggered on an
+                        // earlier bar or the bar opens through the stop
+                        let resting_at_open = self.triggered_stops.contains(&order.id)
+                            || stop_touched(order.side, stop_price, &opening(bar));
+                        if stop_touched(order.side, stop_price, bar) {
+                            self.triggered_stops.insert(order.id);
+                        }
+                        if self.triggered_stops.contains(&order.id)
+                            && limit_touched(order.side, limit, bar)
+                        {
+                            let price = if resting_at_open {
+                                limit_fill_price(order.side, limit, bar)
+                            } else {
+                                limit
+                            };
+                            to_fill.push((i, Some(price)));
+                        }
+                    }
+                }
+                OrderType::TrailingStop => {
+                    if let (Some(stop_price), Some(trail)) = (order.stop_price, order.trail) {
+                        if stop_touched(order.side, stop_price, bar) {
+                            to_fill.push((i, None));
+                        } else {
+                            // Ratchet the stop toward the market, never away from it
+                            let new_stop = match order.side {
+                                Side::Sell => {
+                                    stop_price.max(bar.high - trail.distance(bar.high, tick_size))
+                                }
+                                Side::Buy => {
+                                    stop_price.min(bar.low + trail.distance(bar.low, tick_size))
+                                }
+                            };
+                            if new_stop != stop_price {
+                                order.stop_price = Some(new_stop);
+                                order.updated_at = bar.timestamp;
+                            }
+                        }
+                    }
+                }
+                OrderType::MarketIfTouched => {
+                    if let Some(touch_price) = order.stop_price {
+                        if limit_touched(order.side, touch_price, bar) {
+                            to_fill.push((i, None));
+                        }
+                    }
+                }
+                OrderType::Market => {}
             }
         }
 
         // Fill triggered orders (reverse iterate to preserve indices)
-        for i in to_fill.into_iter().rev() {
+        for (i, limit_price) in to_fill.into_iter().rev() {
             let mut order = self.active_orders.remove(i);
-            self.simulate_fill(&mut order);
+            self.triggered_stops.remove(&order.id);
+            self.expiries.remove(&order.id);
+            let remaining = order.quantity - order.filled_quantity;
+            self.simulate_fill(&mut order, limit_price, remaining);
             self.filled_orders.push(order);
         }
     }
 
+    /// Anchor a new trailing stop to the current market price.
+    fn init_trailing_stop(&self, order: &mut Order) -> Result<(), BrokerError> {
+        if order.stop_price.is_some() {
+            return Ok(());
+        }
+        let trail = order.trail.ok_or_else(|| {
+            BrokerError::OrderRejected("Trailing stop requires a trail offset".to_string())
+        })?;
+        let bar = self.current_bar.as_ref().ok_or_else(|| {
+            BrokerError::OrderRejected("No market price to anchor trailing stop".to_string())
+        })?;
+        let distance = trail.distance(bar.close, self.config.tick_size);
+        order.stop_price = Some(match order.side {
+            Side::Sell => bar.close - distance,
+            Side::Buy => bar.close + distance,
+        });
+        Ok(())
+    }
+
+    /// Roll every strategy's position in `instrument` into the next contract:
+    /// close it at `exit_price` and reopen the same size at `entry_price`.
+    ///
+    /// Both legs pay commission but no slippage, as rolls usually trade as a
+    /// calendar spread. Working orders move to the new contract with their
+    /// prices shifted by the roll's price difference, so they keep their
+    /// distance from the market.
+    pub fn roll_positions(&mut self, instrument: &str, exit_price: Decimal, entry_price: Decimal) {
+        let shift = entry_price - exit_price;
+        if !shift.is_zero() {
+            let now = self.now();
+            let mut moved = Vec::new();
+            for order in self
+                .active_orders
+                .iter_mut()
+                .filter(|o| o.instrument == instrument)
+            {
+                order.price = order.price.map(|p| p + shift);
+                order.stop_price = order.stop_price.map(|p| p + shift);
+                order.updated_at = now;
+                moved.push(order.clone());
+            }
+            for order in moved {
+                self.log_order(
+                    OrderUpdateKind::Modified,
+                    &order,
+                    None,
+                    Some("Rolled to next contract"),
+                );
+            }
+        }
+
+        let mut legs: Vec<(Option<String>, Side, Decimal)> = self
+            .strategy_positions
+            .iter()
+            .filter(|((_, symbol), _)| symbol == instrument)
+            .map(|((strategy_id, _), pos)| (strategy_id.clone(), pos.side, pos.quantity))
+            .collect();
+        legs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (strategy_id, side, quantity) in legs {
+            for (side, price) in [(side.opposite(), exit_price), (side, entry_price)] {
+                let mut order = Order::limit(instrument, side, quantity, price);
+                order.strategy_id = strategy_id.clone();
+                if let Some(id) = &strategy_id {
+                    self.order_owners.insert(order.id, id.clone());
+                }
+                self.simulate_fill(&mut order, Some(price), quantity);
+                self.filled_orders.push(order);
+            }
+        }
+    }
+
+    /// Start a new trading day: daily PnL counts from zero again.
+    pub fn start_new_day(&mut self) {
+        self.account.daily_pnl = Decimal::ZERO;
+    }
+
     /// Reset broker state (for re-running backtests).
     pub fn reset(&mut self) {
         self.account = AccountState::new(self.config.initial_balance);
         self.positions.clear();
+        self.strategy_positions.clear();
+        self.excursions.clear();
         self.active_orders.clear();
+        self.triggered_stops.clear();
+        self.expiries.clear();
+        self.events.clear();
+        self.order_owners.clear();
         self.filled_orders.clear();
         self.trades.clear();
+        self.order_log.clear();
+        self.order_log_taken = 0;
         self.current_bar = None;
     }
 }
 
+/// Whether a bar trades at or through a limit-style price (buy below / sell above).
+fn limit_touched(side: Side, price: Decimal, bar: &Bar) -> bool {
+    match side {
+        Side::Buy => bar.low <= price,
+        Side::Sell => bar.high >= price,
+    }
+}
+
+/// Where a resting limit order fills on a bar that reaches it: at the limit,
+/// or at the open when the bar gaps through it.
+fn limit_fill_price(side: Side, price: Decimal, bar: &Bar) -> Decimal {
+    match side {
+        Side::Buy => price.min(bar.open),
+        Side::Sell => price.max(bar.open),
+    }
+}
+
+/// Just the opening print of a bar, as a bar.
+fn opening(bar: &Bar) -> Bar {
+    Bar {
+        high: bar.open,
+        low: bar.open,
+        close: bar.open,
+        ..bar.clone()
+    }
+}
+
+/// Whether a bar trades at or through a stop-style price (buy above / sell below).
+fn stop_touched(side: Side, price: Decimal, bar: &Bar) -> bool {
+    match side {
+        Side::Buy => bar.high >= price,
+        Side::Sell => bar.low <= price,
+    }
+}
+
 #[async_trait]
 impl Broker for SimulatedBroker {
     async fn connect(&mut self) -> Result<(), BrokerError> {
@@ -297,17 +815,47 @@ impl Broker for SimulatedBroker {
 
     async fn submit_order(&mut self, mut order: Order) -> Result<Order, BrokerError> {
         order.status = OrderStatus::Submitted;
-        order.updated_at = Utc::now();
+        // Orders reach the simulated market at the current bar, whenever they were built
+        order.created_at = self.now();
+        order.updated_at = order.created_at;
+        if let Some(strategy_id) = &order.strategy_id {
+            self.order_owners.insert(order.id, strategy_id.clone());
+        }
+        self.log_order(OrderUpdateKind::Submitted, &order, None, None);
+
+        if matches!(order.time_in_force, TimeInForce::Ioc | TimeInForce::Fok) {
+            // Never rests: fill now or cancel
+            self.execute_immediate(&mut order);
+            self.filled_orders.push(order.clone());
+            return Ok(order);
+        }
 
         match order.order_type {
             OrderType::Market => {
                 // Immediate fill
-                self.simulate_fill(&mut order);
+                let quantity = order.quantity;
+                self.simulate_fill(&mut order, None, quantity);
                 self.filled_orders.push(order.clone());
             }
-            OrderType::Limit | OrderType::Stop | OrderType::StopLimit => {
+            OrderType::TrailingStop => {
+                if let Err(e) = self.init_trailing_stop(&mut order) {
+                    order.status = OrderStatus::Rejected;
+                    let reason = e.to_string();
+                    self.log_order(OrderUpdateKind::Rejected, &order, None, Some(&reason));
+                    self.events.push(OrderEvent::Rejected {
+                        order_id: order.id,
+                        reason: e.to_string(),
+                    });
+                    return Err(e);
+                }
+                self.rest_order(&order);
+            }
+            OrderType::Limit
+            | OrderType::Stop
+            | OrderType::StopLimit
+            | OrderType::MarketIfTouched => {
                 // Add to working orders
-                self.active_orders.push(order.clone());
+                self.rest_order(&order);
             }
         }
 
@@ -315,26 +863,32 @@ impl Broker for SimulatedBroker {
     }
 
     async fn cancel_order(&mut self, order_id: Uuid) -> Result<(), BrokerError> {
-        if let Some(pos) = self.active_orders.iter().position(|o| o.id == order_id) {
-            let mut order = self.active_orders.remove(pos);
-            order.status = OrderStatus::Cancelled;
-            self.filled_orders.push(order);
-            Ok(())
-        } else {
-            Err(BrokerError::OrderNotFound(order_id))
-        }
+        self.cancel_working(order_id, "Cancelled by request")
+            .map(|_| ())
+            .ok_or(BrokerError::OrderNotFound(order_id))
     }
 
     async fn modify_order(&mut self, order: Order) -> Result<Order, BrokerError> {
-        if let Some(existing) = self.active_orders.iter_mut().find(|o| o.id == order.id) {
-            existing.price = order.price;
-            existing.stop_price = order.stop_price;
-            existing.quantity = order.quantity;
-            existing.updated_at = Utc::now();
-            Ok(existing.clone())
-        } else {
-            Err(BrokerError::OrderNotFound(order.id))
+        let now = self.now();
+        let Some(existing) = self.active_orders.iter_mut().find(|o| o.id == order.id) else {
+            return Err(BrokerError::OrderNotFound(order.id));
+        };
+        if order.quantity < existing.filled_quantity {
+            return Err(BrokerError::OrderRejected(format!(
+                "quantity {} is below the {} already filled",
+                order.quantity, existing.filled_quantity
+            )));
         }
+        existing.price = order.price;
+        existing.stop_price = order.stop_price;
+        existing.trail = order.trail;
+        existing.quantity = order.quantity;
+        existing.time_in_force = order.time_in_force;
+        existing.updated_at = now;
+        let modified = existing.clone();
+        self.track_expiry(&modified);
+        self.log_order(OrderUpdateKind::Modified, &modified, None, None);
+        Ok(modified)
     }
 
     async fn account_state(&self) -> Result<AccountState, BrokerError> {
@@ -350,16 +904,27 @@ impl Broker for SimulatedBroker {
     }
 
     async fn flatten_all(&mut self) -> Result<(), BrokerError> {
-        let instruments: Vec<String> = self.positions.keys().cloned().collect();
-        for instrument in instruments {
-            if let Some(pos) = self.positions.get(&instrument) {
-                let order = Order::market(&instrument, pos.side.opposite(), pos.quantity);
+        // Close each strategy's leg so the exits are attributed back to it
+        let mut keys: Vec<PositionKey> = self.strategy_positions.keys().cloned().collect();
+        keys.sort();
+        for key in keys {
+            if let Some(pos) = self.strategy_positions.get(&key) {
+                let mut order = Order::market(&key.1, pos.side.opposite(), pos.quantity);
+                order.strategy_id = key.0;
                 self.submit_order(order).await?;
             }
         }
         Ok(())
     }
 
+    /// The order log grows for the whole run, so this hands out copies of
+    /// the entries added since the last call and keeps them in the log.
+    fn take_order_updates(&mut self) -> Vec<OrderUpdate> {
+        let updates = self.order_log[self.order_log_taken..].to_vec();
+        self.order_log_taken = self.order_log.len();
+        updates
+    }
+
     async fn subscribe_market_data(
         &mut self,
         _instrument: &str,
@@ -370,3 +935,390 @@ impl Broker for SimulatedBroker {
         Ok(rx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+    use rust_decimal_macros::dec;
+
+    fn bar(minute: i64, high: Decimal, low: Decimal, close: Decimal) -> Bar {
+        Bar {
+            instrument: "ES".to_string(),
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 2, 14, 30, 0).unwrap()
+                + Duration::minutes(minute),
+            open: close,
+            high,
+            low,
+            close,
+            volume: dec!(100),
+        }
+    }
+
+    async fn broker() -> SimulatedBroker {
+        let mut broker = SimulatedBroker::new(SimulatedBrokerConfig {
+            slippage_ticks: Decimal::ZERO,
+            ..Default::default()
+        });
+        broker.connect().await.unwrap();
+        broker.set_current_bar(bar(0, dec!(4751), dec!(4749), dec!(4750)));
+        broker
+    }
+
+    #[tokio::test]
+    async fn test_stop_limit_waits_for_limit_after_trigger() {
+        let mut broker = broker().await;
+        let order = Order::stop_limit("ES", Side::Buy, dec!(1), dec!(4755), dec!(4754));
+        broker.submit_order(order).await.unwrap();
+
+        // Stop triggers but the bar never trades back down to the limit
+        broker.set_current_bar(bar(1, dec!(4758), dec!(4755), dec!(4757)));
+        assert!(broker.position("ES").is_none());
+        assert_eq!(broker.active_orders.len(), 1);
+
+        // Pull back to the limit on a later bar fills at the limit price
+        broker.set_current_bar(bar(2, dec!(4756), dec!(4753), dec!(4755)));
+        let pos = broker.position("ES").expect("filled");
+        assert_eq!(pos.avg_entry_price, dec!(4754));
+    }
+
+    #[tokio::test]
+    async fn test_triggered_stop_limit_fills_at_better_open() {
+        let mut broker = broker().await;
+        let order = Order::stop_limit("ES", Side::Buy, dec!(1), dec!(4755), dec!(4754));
+        broker.submit_order(order).await.unwrap();
+
+        // Triggers without reaching the limit
+        broker.set_current_bar(bar(1, dec!(4758), dec!(4755), dec!(4757)));
+        assert!(broker.position("ES").is_none());
+
+        // Next bar gaps down through the limit: the resting limit fills at the open
+        let mut gap = bar(2, dec!(4753), dec!(4749), dec!(4750));
+        gap.open = dec!(4752);
+        broker.set_current_bar(gap);
+        assert_eq!(broker.position("ES").unwrap().avg_entry_price, dec!(4752));
+    }
+
+    #[tokio::test]
+    async fn test_limit_fills_at_limit_or_better_open() {
+        let mut broker = broker().await;
+        // Slippage must not apply to resting limits
+        broker.config.slippage_ticks = dec!(2);
+        broker
+            .submit_order(Order::limit("ES", Side::Buy, dec!(1), dec!(4748)))
+            .await
+            .unwrap();
+        broker
+            .submit_order(Order::limit("ES", Side::Sell, dec!(1), dec!(4760)))
+            .await
+            .unwrap();
+
+        // Trades down through the buy limit and closes above it
+        broker.set_current_bar(bar(1, dec!(4751), dec!(4747), dec!(4750)));
+        assert_eq!(broker.trade_log().len(), 0);
+        assert_eq!(broker.position("ES").unwrap().avg_entry_price, dec!(4748));
+
+        // Gaps up through the sell limit: fills at the better open
+        let mut gap = bar(2, dec!(4766), dec!(4761), dec!(4762));
+        gap.open = dec!(4765);
+        broker.set_current_bar(gap);
+        assert_eq!(broker.trade_log()[0].exit_price, dec!(4765));
+    }
+
+    #[tokio::test]
+    async fn test_trailing_stop_ratchets_and_fills() {
+        let mut broker = broker().await;
+        let order = Order::trailing_stop("ES", Side::Sell, dec!(1), TrailingOffset::Ticks(dec!(8)));
+        let submitted = broker.submit_order(order).await.unwrap();
+        assert_eq!(submitted.stop_price, Some(dec!(4748)));
+
+        // New high drags the stop up; a pullback must not move it back down
+        broker.set_current_bar(bar(1, dec!(4760), dec!(4755), dec!(4758)));
+        broker.set_current_bar(bar(2, dec!(4759), dec!(4759), dec!(4759)));
+        assert_eq!(broker.active_orders[0].stop_price, Some(dec!(4758)));
+
+        // Trading through the trailed stop fills the sell
+        broker.set_current_bar(bar(3, dec!(4759), dec!(4757), dec!(4757)));
+        assert!(broker.active_orders.is_empty());
+        assert_eq!(broker.position("ES").map(|p| p.side), Some(Side::Sell));
+    }
+
+    #[tokio::test]
+    async fn test_strategies_on_same_instrument_do_not_net() {
+        let mut broker = broker().await;
+        let mut long = Order::market("ES", Side::Buy, dec!(1));
+        long.strategy_id = Some("a".to_string());
+        let mut short = Order::market("ES", Side::Sell, dec!(1));
+        short.strategy_id = Some("b".to_string());
+        broker.submit_order(long).await.unwrap();
+        broker.submit_order(short).await.unwrap();
+
+        // Net flat, but each strategy keeps its own leg and no trade was booked
+        assert!(broker.position("ES").is_none());
+        assert_eq!(
+            broker.strategy_position("a", "ES").map(|p| p.side),
+            Some(Side::Buy)
+        );
+        assert_eq!(
+            broker.strategy_position("b", "ES").map(|p| p.side),
+            Some(Side::Sell)
+        );
+        assert!(broker.trade_log().is_empty());
+
+        broker.set_current_bar(bar(1, dec!(4752), dec!(4750), dec!(4751)));
+        broker.flatten_all().await.unwrap();
+        let strategies: Vec<_> = broker
+            .trade_log()
+            .iter()
+            .map(|t| t.strategy_id.clone())
+            .collect();
+        assert_eq!(
+            strategies,
+            vec![Some("a".to_string()), Some("b".to_string())]
+        );
+        assert!(broker.strategy_positions("a").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_day_order_expires_at_session_close() {
+        let mut broker = broker().await;
+        let order =
+            Order::limit("ES", Side::Buy, dec!(1), dec!(4700)).with_time_in_force(TimeInForce::Day);
+        let order = broker.submit_order(order).await.unwrap();
+
+        // 20:59 UTC is still inside the session
+        broker.set_current_bar(bar(389, dec!(4751), dec!(4749), dec!(4750)));
+        assert_eq!(broker.active_orders.len(), 1);
+        assert!(broker.drain_events().is_empty());
+
+        // 21:00 UTC closes the session and cancels the order
+        broker.set_current_bar(bar(390, dec!(4751), dec!(4749), dec!(4750)));
+        assert!(broker.active_orders.is_empty());
+        match broker.drain_events().as_slice() {
+            [OrderEvent::Cancelled { order_id, reason }] => {
+                assert_eq!(*order_id, order.id);
+                assert!(reason.contains("expired"));
+            }
+            other => panic!("Expected a single cancellation, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_modify_moves_gtd_expiry_and_rejects_quantity_below_filled() {
+        let mut broker = broker().await;
+        let expiry = |minute| bar(minute, dec!(0), dec!(0), dec!(0)).timestamp;
+        let order = Order::limit("ES", Side::Buy, dec!(3), dec!(4700))
+            .with_time_in_force(TimeInForce::Gtd(expiry(10)));
+        let order = broker.submit_order(order).await.unwrap();
+
+        let later = order
+            .clone()
+            .with_time_in_force(TimeInForce::Gtd(expiry(20)));
+        let extended = broker.modify_order(later).await.unwrap();
+        assert_eq!(extended.time_in_force, TimeInForce::Gtd(expiry(20)));
+        broker.set_current_bar(bar(10, dec!(4751), dec!(4749), dec!(4750)));
+        assert_eq!(broker.active_orders.len(), 1);
+        broker.set_current_bar(bar(20, dec!(4751), dec!(4749), dec!(4750)));
+        assert!(broker.active_orders.is_empty());
+
+        let order = broker
+            .submit_order(Order::limit("ES", Side::Buy, dec!(3), dec!(4700)))
+            .await
+            .unwrap();
+        broker.active_orders[0].filled_quantity = dec!(2);
+        let shrunk = Order {
+            quantity: dec!(1),
+            ..order
+        };
+        assert!(matches!(
+            broker.modify_order(shrunk).await,
+            Err(BrokerError::OrderRejected(_))
+        ));
+        assert_eq!(broker.active_orders[0].quantity, dec!(3));
+    }
+
+    #[tokio::test]
+    async fn test_day_order_expires_at_calendar_close() {
+        let mut broker = SimulatedBroker::new(SimulatedBrokerConfig::default())
+            .with_calendar(TradingCalendar::cme_equity());
+        broker.connect().await.unwrap();
+        broker.set_current_bar(bar(0, dec!(4751), dec!(4749), dec!(4750)));
+        let order =
+            Order::limit("ES", Side::Buy, dec!(1), dec!(4700)).with_time_in_force(TimeInForce::Day);
+        broker.submit_order(order).await.unwrap();
+
+        // In January the CME closes at 16:00 CST, 22:00 UTC
+        broker.set_current_bar(bar(420, dec!(4751), dec!(4749), dec!(4750)));
+        assert_eq!(broker.active_orders.len(), 1);
+        broker.set_current_bar(bar(450, dec!(4751), dec!(4749), dec!(4750)));
+        assert!(broker.active_orders.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trade_records_excursions_while_open() {
+        let mut broker = broker().await;
+        broker
+            .submit_order(Order::market("ES", Side::Buy, dec!(1)))
+            .await
+            .unwrap();
+        // Long from 4750: down to 4746 and up to 4756 while open
+        broker.set_current_bar(bar(1, dec!(4756), dec!(4746), dec!(4752)));
+        broker.set_current_bar(bar(2, dec!(4753), dec!(4751), dec!(4752)));
+        broker
+            .submit_order(Order::market("ES", Side::Sell, dec!(1)))
+            .await
+            .unwrap();
+
+        let trade = &broker.trade_log()[0];
+        assert_eq!(trade.pnl, dec!(100));
+        assert_eq!(trade.mae, dec!(200));
+        assert_eq!(trade.mfe, dec!(300));
+    }
+
+    #[tokio::test]
+    async fn test_order_log_records_every_state_change() {
+        let mut broker = broker().await;
+        let order = broker
+            .submit_order(Order::limit("ES", Side::Buy, dec!(1), dec!(4700)))
+            .await
+            .unwrap();
+        broker
+            .modify_order(Order {
+                price: Some(dec!(4749)),
+                ..order.clone()
+            })
+            .await
+            .unwrap();
+        broker.set_current_bar(bar(1, dec!(4751), dec!(4748), dec!(4750)));
+        broker
+            .submit_order(Order::stop("ES", Side::Sell, dec!(1), dec!(4740)))
+            .await
+            .unwrap();
+        let stop = broker.working_orders()[0].id;
+        broker.cancel_order(stop).await.unwrap();
+
+        let log: Vec<_> = broker
+            .order_log()
+            .iter()
+            .map(|u| (u.kind, u.order.status, u.order.filled_quantity))
+            .collect();
+        assert_eq!(
+            log,
+            vec![
+                (OrderUpdateKind::Submitted, OrderStatus::Submitted, dec!(0)),
+                (OrderUpdateKind::Modified, OrderStatus::Submitted, dec!(0)),
+                (OrderUpdateKind::Filled, OrderStatus::Filled, dec!(1)),
+                (OrderUpdateKind::Submitted, OrderStatus::Submitted, dec!(0)),
+                (OrderUpdateKind::Cancelled, OrderStatus::Cancelled, dec!(0)),
+            ]
+        );
+        let fill = broker.order_log()[2].fill.as_ref().unwrap();
+        assert_eq!((fill.order_id, fill.price), (order.id, dec!(4749)));
+        let filled_at = bar(1, dec!(0), dec!(0), dec!(0)).timestamp;
+        assert_eq!(broker.order_log()[2].timestamp, filled_at);
+        assert_eq!(
+            broker.order_log()[4].reason.as_deref(),
+            Some("Cancelled by request")
+        );
+        // Handed out once, and still kept for the backtest result
+        assert_eq!(broker.take_order_updates().len(), 5);
+        assert!(broker.take_order_updates().is_empty());
+        assert_eq!(broker.order_log().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_ioc_fills_available_volume_and_cancels_rest() {
+        let mut broker = broker().await;
+        let order = Order::market("ES", Side::Buy, dec!(150)).with_time_in_force(TimeInForce::Ioc);
+        let order = broker.submit_order(order).await.unwrap();
+
+        assert_eq!(order.status, OrderStatus::Cancelled);
+        assert_eq!(order.filled_quantity, dec!(100));
+        assert_eq!(broker.position("ES").map(|p| p.quantity), Some(dec!(100)));
+        assert!(matches!(
+            broker.drain_events().as_slice(),
+            [OrderEvent::PartiallyFilled(_), OrderEvent::Cancelled { .. }]
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fok_is_killed_without_full_liquidity() {
+        let mut broker = broker().await;
+        let order = Order::market("ES", Side::Buy, dec!(150)).with_time_in_force(TimeInForce::Fok);
+        let order = broker.submit_order(order).await.unwrap();
+        assert_eq!(order.status, OrderStatus::Cancelled);
+        assert!(order.filled_quantity.is_zero());
+        assert!(broker.position("ES").is_none());
+
+        // Non-marketable limits are never rested
+        let order =
+            Order::limit("ES", Side::Buy, dec!(1), dec!(4700)).with_time_in_force(TimeInForce::Fok);
+        let order = broker.submit_order(order).await.unwrap();
+        assert_eq!(order.status, OrderStatus::Cancelled);
+        assert!(broker.active_orders.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_market_if_touched_triggers_on_touch() {
+        let mut broker = broker().await;
+        let order = Order::market_if_touched("ES", Side::Buy, dec!(1), dec!(4745));
+        broker.submit_order(order).await.unwrap();
+
+        broker.set_current_bar(bar(1, dec!(4752), dec!(4746), dec!(4748)));
+        assert!(broker.position("ES").is_none());
+
+        broker.set_current_bar(bar(2, dec!(4748), dec!(4745), dec!(4747)));
+        assert_eq!(broker.position("ES").map(|p| p.side), Some(Side::Buy));
+    }
+
+    #[tokio::test]
+    async fn test_roll_reopens_position_in_next_contract() {
+        let mut broker = broker().await;
+        let mut order = Order::market("ES", Side::Buy, dec!(2));
+        order.strategy_id = Some("a".to_string());
+        broker.submit_order(order).await.unwrap();
+        broker.drain_events();
+
+        broker.roll_positions("ES", dec!(4750), dec!(4800));
+
+        let pos = broker
+            .strategy_position("a", "ES")
+            .expect("rolled position");
+        assert_eq!(pos.side, Side::Buy);
+        assert_eq!(pos.quantity, dec!(2));
+        assert_eq!(pos.avg_entry_price, dec!(4800));
+        // The old leg is booked flat apart from commission
+        assert_eq!(broker.trade_log().len(), 1);
+        assert_eq!(broker.trade_log()[0].exit_price, dec!(4750));
+        assert_eq!(broker.drain_events().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_roll_shifts_working_orders_to_next_contract() {
+        let mut broker = broker().await;
+        let stop = Order::stop_limit("ES", Side::Sell, dec!(1), dec!(4740), dec!(4738));
+        broker.submit_order(stop).await.unwrap();
+        broker
+            .submit_order(Order::limit("NQ", Side::Buy, dec!(1), dec!(16000)))
+            .await
+            .unwrap();
+
+        broker.roll_positions("ES", dec!(4750), dec!(4800));
+
+        let prices: Vec<_> = broker
+            .working_orders()
+            .iter()
+            .map(|o| (o.instrument.as_str(), o.stop_price, o.price))
+            .collect();
+        assert_eq!(
+            prices,
+            vec![
+                ("ES", Some(dec!(4790)), Some(dec!(4788))),
+                ("NQ", None, Some(dec!(16000))),
+            ]
+        );
+        let last = broker.order_log().last().unwrap();
+        assert_eq!(last.kind, OrderUpdateKind::Modified);
+        assert_eq!(last.reason.as_deref(), Some("Rolled to next contract"));
+    }
+}
@@ -0,0 +1,170 @@
+//! Order fields as sent and received over the NinjaTrader and MetaTrader
+//! socket bridges.
+
+use chrono::{DateTime, Utc};
+use propbot_core::{
+    Fill, Order, OrderStatus, OrderType, OrderUpdate, OrderUpdateKind, Side, TimeInForce,
+    TrailingOffset,
+};
+use rust_decimal::Decimal;
+
+/// The wire name of an order side.
+pub fn side_name(side: Side) -> &'static str {
+    match side {
+        Side::Buy => "buy",
+        Side::Sell => "sell",
+    }
+}
+
+/// The wire name of an order type.
+pub fn order_type_name(order_type: OrderType) -> &'static str {
+    match order_type {
+        OrderType::Market => "market",
+        OrderType::Limit => "limit",
+        OrderType::Stop => "stop",
+        OrderType::StopLimit => "stop_limit",
+        OrderType::TrailingStop => "trailing_stop",
+        OrderType::MarketIfTouched => "market_if_touched",
+    }
+}
+
+/// Split a trailing offset into the `(trail_ticks, trail_percent)` wire fields.
+pub fn trail_fields(trail: Option<TrailingOffset>) -> (Option<Decimal>, Option<Decimal>) {
+    match trail {
+        Some(TrailingOffset::Ticks(ticks)) => (Some(ticks), None),
+        Some(TrailingOffset::Percent(pct)) => (None, Some(pct)),
+        None => (None, None),
+    }
+}
+
+/// Split a time in force into the `(time_in_force, expire_at)` wire fields.
+pub fn time_in_force_fields(tif: TimeInForce) -> (String, Option<DateTime<Utc>>) {
+    match tif {
+        TimeInForce::Day => ("day".to_string(), None),
+        TimeInForce::Gtc => ("gtc".to_string(), None),
+        TimeInForce::Ioc => ("ioc".to_string(), None),
+        TimeInForce::Fok => ("fok".to_string(), None),
+        TimeInForce::Gtd(at) => ("gtd".to_string(), Some(at)),
+    }
+}
+
+/// The order status named by a bridge `order_update` message.
+pub fn order_status(status: &str) -> OrderStatus {
+    match status {
+        "filled" => OrderStatus::Filled,
+        "partially_filled" => OrderStatus::PartiallyFilled,
+        "cancelled" => OrderStatus::Cancelled,
+        "rejected" => OrderStatus::Rejected,
+        "submitted" => OrderStatus::Submitted,
+        _ => OrderStatus::Pending,
+    }
+}
+
+/// Bring `order` up to date with a bridge `order_update` message and
+/// describe the change for the order log.
+///
+/// A fill is reported when the filled quantity grows and the message carries
+/// a price; the bridges don't report commission, so it is zero. Statuses
+/// other than fills, cancels and rejects only acknowledge the order, whose
+/// submission is already logged, and yield no entry.
+pub fn apply_order_update(
+    order: &mut Order,
+    broker_order_id: &str,
+    status: &str,
+    filled_quantity: Decimal,
+    fill_price: Option<Decimal>,
+    message: Option<&str>,
+) -> Option<OrderUpdate> {
+    let previously_filled = order.filled_quantity;
+    order.broker_order_id = Some(broker_order_id.to_string());
+    order.filled_quantity = filled_quantity;
+    order.updated_at = Utc::now();
+    order.status = order_status(status);
+    if fill_price.is_some() {
+        order.price = fill_price;
+    }
+
+    let kind = match order.status {
+        OrderStatus::Filled => OrderUpdateKind::Filled,
+        OrderStatus::PartiallyFilled => OrderUpdateKind::PartiallyFilled,
+        OrderStatus::Cancelled => OrderUpdateKind::Cancelled,
+        OrderStatus::Rejected => OrderUpdateKind::Rejected,
+        _ => return None,
+    };
+    let fill = fill_price
+        .filter(|_| filled_quantity > previously_filled)
+        .map(|price| Fill {
+            order_id: order.id,
+            instrument: order.instrument.clone(),
+            side: order.side,
+            quantity: filled_quantity - previously_filled,
+            price,
+            commission: Decimal::ZERO,
+            timestamp: order.updated_at,
+        });
+    Some(OrderUpdate {
+        kind,
+        order: order.clone(),
+        fill,
+        reason: message.map(str::to_string),
+        timestamp: order.updated_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_order_update_reports_each_partial_fill() {
+        let mut order = Order::limit("ES", Side::Buy, dec!(3), dec!(4700));
+
+        let ack = apply_order_update(&mut order, "NT-1", "submitted", dec!(0), None, None);
+        let first = apply_order_update(
+            &mut order,
+            "NT-1",
+            "partially_filled",
+            dec!(1),
+            Some(dec!(4700)),
+            None,
+        )
+        .unwrap();
+        let last = apply_order_update(
+            &mut order,
+            "NT-1",
+            "filled",
+            dec!(3),
+            Some(dec!(4699.75)),
+            None,
+        )
+        .unwrap();
+
+        assert!(ack.is_none());
+        assert_eq!(first.kind, OrderUpdateKind::PartiallyFilled);
+        assert_eq!(first.fill.map(|f| f.quantity), Some(dec!(1)));
+        let fill = last.fill.unwrap();
+        assert_eq!((fill.quantity, fill.price), (dec!(2), dec!(4699.75)));
+        assert_eq!(last.order.status, OrderStatus::Filled);
+        assert_eq!(order.broker_order_id.as_deref(), Some("NT-1"));
+    }
+
+    #[test]
+    fn test_order_update_keeps_reject_reason() {
+        let mut order = Order::market("ES", Side::Sell, dec!(1));
+
+        let update = apply_order_update(
+            &mut order,
+            "NT-2",
+            "rejected",
+            dec!(0),
+            None,
+            Some("Margin"),
+        )
+        .unwrap();
+
+        assert_eq!(update.kind, OrderUpdateKind::Rejected);
+        assert!(update.fill.is_none());
+        assert_eq!(update.reason.as_deref(), Some("Margin"));
+    }
+}
@@ -3,6 +3,7 @@
 //! Direct REST + WebSocket integration with exchanges.
 //! Initial target: Binance Futures.
 
+#[derive(Default)]
 pub struct CryptoBroker;
 
 impl CryptoBroker {
@@ -2,6 +2,7 @@
 //!
 //! Fallback for platforms without APIs — uses screen capture and input simulation.
 
+#[derive(Default)]
 pub struct GuiBroker;
 
 impl GuiBroker {
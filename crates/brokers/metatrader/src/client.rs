@@ -10,6 +10,8 @@ use tokio::sync::mpsc;
 use tracing::info;
 use uuid::Uuid;
 
+use propbot_brokers_common::wire;
+
 use crate::protocol::*;
 
 /// Configuration for connecting to MetaTrader 5.
@@ -49,6 +51,8 @@ pub struct MetaTraderBroker {
     active_orders: HashMap<Uuid, Order>,
     /// Maps our order IDs to broker-assigned IDs.
     order_id_map: HashMap<Uuid, String>,
+    /// Order state changes not yet taken by `take_order_updates`.
+    order_updates: Vec<OrderUpdate>,
 }
 
 impl MetaTraderBroker {
@@ -61,9 +65,21 @@ impl MetaTraderBroker {
             positions: HashMap::new(),
             active_orders: HashMap::new(),
             order_id_map: HashMap::new(),
+            order_updates: Vec::new(),
         }
     }
 
+    /// Queue an order-log entry for a change made from this side.
+    fn log_order(&mut self, kind: OrderUpdateKind, order: &Order) {
+        self.order_updates.push(OrderUpdate {
+            kind,
+            order: order.clone(),
+            fill: None,
+            reason: None,
+            timestamp: order.updated_at,
+        });
+    }
+
     /// Send a message to MetaTrader.
     async fn send(&mut self, msg: &OutboundMessage) -> Result<(), BrokerError> {
         let stream = self
@@ -168,24 +184,19 @@ impl MetaTraderBroker {
                 status,
                 filled_quantity,
                 fill_price,
-                message: _,
+                message,
             } => {
                 if let Ok(uuid) = Uuid::parse_str(client_order_id) {
                     if let Some(order) = self.active_orders.get_mut(&uuid) {
-                        order.broker_order_id = Some(broker_order_id.clone());
-                        order.filled_quantity = *filled_quantity;
-                        order.updated_at = Utc::now();
-                        order.status = match status.as_str() {
-                            "filled" => OrderStatus::Filled,
-                            "partially_filled" => OrderStatus::PartiallyFilled,
-                            "cancelled" => OrderStatus::Cancelled,
-                            "rejected" => OrderStatus::Rejected,
-                            "submitted" => OrderStatus::Submitted,
-                            _ => OrderStatus::Pending,
-                        };
-                        if let Some(price) = fill_price {
-                            order.price = Some(*price);
-                        }
+                        let update = wire::apply_order_update(
+                            order,
+                            broker_order_id,
+                            status,
+                            *filled_quantity,
+                            *fill_price,
+                            message.as_deref(),
+                        );
+                        self.order_updates.extend(update);
                         self.order_id_map
                             .insert(uuid, broker_order_id.clone());
                     }
@@ -242,28 +253,13 @@ impl Broker for MetaTraderBroker {
     }
 
     async fn submit_order(&mut self, mut order: Order) -> Result<Order, BrokerError> {
-        let msg = OutboundMessage::OrderSubmit {
-            id: order.id.to_string(),
-            instrument: order.instrument.clone(),
-            side: match order.side {
-                Side::Buy => "buy".to_string(),
-                Side::Sell => "sell".to_string(),
-            },
-            order_type: match order.order_type {
-                OrderType::Market => "market".to_string(),
-                OrderType::Limit => "limit".to_string(),
-                OrderType::Stop => "stop".to_string(),
-                OrderType::StopLimit => "stop_limit".to_string(),
-            },
-            quantity: order.quantity,
-            price: order.price,
-            stop_price: order.stop_price,
-        };
+        let msg = OutboundMessage::order_submit(&order);
 
         self.send(&msg).await?;
         order.status = OrderStatus::Submitted;
         order.updated_at = Utc::now();
         self.active_orders.insert(order.id, order.clone());
+        self.log_order(OrderUpdateKind::Submitted, &order);
 
         Ok(order)
     }
@@ -282,20 +278,18 @@ impl Broker for MetaTraderBroker {
         Ok(())
     }
 
-    async fn modify_order(&mut self, order: Order) -> Result<Order, BrokerError> {
+    async fn modify_order(&mut self, mut order: Order) -> Result<Order, BrokerError> {
         let broker_id = self
             .order_id_map
             .get(&order.id)
             .cloned()
             .ok_or(BrokerError::OrderNotFound(order.id))?;
 
-        let msg = OutboundMessage::OrderModify {
-            broker_order_id: broker_id,
-            quantity: Some(order.quantity),
-            price: order.price,
-            stop_price: order.stop_price,
-        };
+        let msg = OutboundMessage::order_modify(broker_id, &order);
         self.send(&msg).await?;
+        order.updated_at = Utc::now();
+        self.active_orders.insert(order.id, order.clone());
+        self.log_order(OrderUpdateKind::Modified, &order);
         Ok(order)
     }
 
@@ -320,6 +314,10 @@ impl Broker for MetaTraderBroker {
         self.send(&OutboundMessage::FlattenAll).await
     }
 
+    fn take_order_updates(&mut self) -> Vec<OrderUpdate> {
+        std::mem::take(&mut self.order_updates)
+    }
+
     async fn subscribe_market_data(
         &mut self,
         instrument: &str,
@@ -10,6 +10,8 @@ use tokio::sync::mpsc;
 use tracing::info;
 use uuid::Uuid;
 
+use propbot_brokers_common::wire;
+
 use crate::protocol::*;
 
 /// Configuration for connecting to NinjaTrader 8.
@@ -49,6 +51,8 @@ pub struct NinjaTraderBroker {
     active_orders: HashMap<Uuid, Order>,
     /// Maps our order IDs to broker-assigned IDs.
     order_id_map: HashMap<Uuid, String>,
+    /// Order state changes not yet taken by `take_order_updates`.
+    order_updates: Vec<OrderUpdate>,
 }
 
 impl NinjaTraderBroker {
@@ -61,9 +65,21 @@ impl NinjaTraderBroker {
             positions: HashMap::new(),
             active_orders: HashMap::new(),
             order_id_map: HashMap::new(),
+            order_updates: Vec::new(),
         }
     }
 
+    /// Queue an order-log entry for a change made from this side.
+    fn log_order(&mut self, kind: OrderUpdateKind, order: &Order) {
+        self.order_updates.push(OrderUpdate {
+            kind,
+            order: order.clone(),
+            fill: None,
+            reason: None,
+            timestamp: order.updated_at,
+        });
+    }
+
     /// Send a message to NinjaTrader.
     async fn send(&mut self, msg: &OutboundMessage) -> Result<(), BrokerError> {
         let stream = self
@@ -111,6 +127,14 @@ impl NinjaTraderBroker {
         Ok(msg)
     }
 
+    /// Wait for the next message from NinjaTrader and apply any account,
+    /// position or order update it carries.
+    pub async fn poll(&mut self) -> Result<InboundMessage, BrokerError> {
+        let msg = self.recv().await?;
+        self.process_message(&msg);
+        Ok(msg)
+    }
+
     /// Process an inbound message, updating internal state.
     fn process_message(&mut self, msg: &InboundMessage) {
         match msg {
@@ -167,24 +191,19 @@ impl NinjaTraderBroker {
                 status,
                 filled_quantity,
                 fill_price,
-                message: _,
+                message,
             } => {
                 if let Ok(uuid) = Uuid::parse_str(client_order_id) {
                     if let Some(order) = self.active_orders.get_mut(&uuid) {
-                        order.broker_order_id = Some(broker_order_id.clone());
-                        order.filled_quantity = *filled_quantity;
-                        order.updated_at = Utc::now();
-                        order.status = match status.as_str() {
-                            "filled" => OrderStatus::Filled,
-                            "partially_filled" => OrderStatus::PartiallyFilled,
-                            "cancelled" => OrderStatus::Cancelled,
-                            "rejected" => OrderStatus::Rejected,
-                            "submitted" => OrderStatus::Submitted,
-                            _ => OrderStatus::Pending,
-                        };
-                        if fill_price.is_some() {
-                            order.price = *fill_price;
-                        }
+                        let update = wire::apply_order_update(
+                            order,
+                            broker_order_id,
+                            status,
+                            *filled_quantity,
+                            *fill_price,
+                            message.as_deref(),
+                        );
+                        self.order_updates.extend(update);
                         self.order_id_map
                             .insert(uuid, broker_order_id.clone());
                     }
@@ -241,28 +260,13 @@ impl Broker for NinjaTraderBroker {
     }
 
     async fn submit_order(&mut self, mut order: Order) -> Result<Order, BrokerError> {
-        let msg = OutboundMessage::OrderSubmit {
-            id: order.id.to_string(),
-            instrument: order.instrument.clone(),
-            side: match order.side {
-                Side::Buy => "buy".to_string(),
-                Side::Sell => "sell".to_string(),
-            },
-            order_type: match order.order_type {
-                OrderType::Market => "market".to_string(),
-                OrderType::Limit => "limit".to_string(),
-                OrderType::Stop => "stop".to_string(),
-                OrderType::StopLimit => "stop_limit".to_string(),
-            },
-            quantity: order.quantity,
-            price: order.price,
-            stop_price: order.stop_price,
-        };
+        let msg = OutboundMessage::order_submit(&order);
 
         self.send(&msg).await?;
         order.status = OrderStatus::Submitted;
         order.updated_at = Utc::now();
         self.active_orders.insert(order.id, order.clone());
+        self.log_order(OrderUpdateKind::Submitted, &order);
 
         Ok(order)
     }
@@ -281,20 +285,18 @@ impl Broker for NinjaTraderBroker {
         Ok(())
     }
 
-    async fn modify_order(&mut self, order: Order) -> Result<Order, BrokerError> {
+    async fn modify_order(&mut self, mut order: Order) -> Result<Order, BrokerError> {
         let broker_id = self
             .order_id_map
             .get(&order.id)
             .cloned()
             .ok_or(BrokerError::OrderNotFound(order.id))?;
 
-        let msg = OutboundMessage::OrderModify {
-            broker_order_id: broker_id,
-            quantity: Some(order.quantity),
-            price: order.price,
-            stop_price: order.stop_price,
-        };
+        let msg = OutboundMessage::order_modify(broker_id, &order);
         self.send(&msg).await?;
+        order.updated_at = Utc::now();
+        self.active_orders.insert(order.id, order.clone());
+        self.log_order(OrderUpdateKind::Modified, &order);
         Ok(order)
     }
 
@@ -319,6 +321,10 @@ impl Broker for NinjaTraderBroker {
         self.send(&OutboundMessage::FlattenAll).await
     }
 
+    fn take_order_updates(&mut self) -> Vec<OrderUpdate> {
+        std::mem::take(&mut self.order_updates)
+    }
+
     async fn subscribe_market_data(
         &mut self,
         instrument: &str,
@@ -1,4 +1,8 @@
 use chrono::{DateTime, Utc};
+use propbot_brokers_common::wire::{
+    order_type_name, side_name, time_in_force_fields, trail_fields,
+};
+use propbot_core::Order;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
@@ -16,6 +20,18 @@ pub enum OutboundMessage {
         quantity: Decimal,
         price: Option<Decimal>,
         stop_price: Option<Decimal>,
+        /// Trailing-stop distance in ticks (trailing stops only).
+        #[serde(default)]
+        trail_ticks: Option<Decimal>,
+        /// Trailing-stop distance as a percentage (trailing stops only).
+        #[serde(default)]
+        trail_percent: Option<Decimal>,
+        /// One of `day`, `gtc`, `ioc`, `fok`, `gtd`.
+        #[serde(default = "default_time_in_force")]
+        time_in_force: String,
+        /// Expiry time for `gtd` orders.
+        #[serde(default)]
+        expire_at: Option<DateTime<Utc>>,
     },
     /// Cancel an existing order.
     #[serde(rename = "order_cancel")]
@@ -27,6 +43,10 @@ pub enum OutboundMessage {
         quantity: Option<Decimal>,
         price: Option<Decimal>,
         stop_price: Option<Decimal>,
+        #[serde(default)]
+        trail_ticks: Option<Decimal>,
+        #[serde(default)]
+        trail_percent: Option<Decimal>,
     },
     /// Request current account state.
     #[serde(rename = "account_request")]
@@ -115,6 +135,44 @@ pub enum InboundMessage {
     Connected { version: String },
 }
 
+impl OutboundMessage {
+    /// The submit message for a new order.
+    pub fn order_submit(order: &Order) -> Self {
+        let (trail_ticks, trail_percent) = trail_fields(order.trail);
+        let (time_in_force, expire_at) = time_in_force_fields(order.time_in_force);
+        OutboundMessage::OrderSubmit {
+            id: order.id.to_string(),
+            instrument: order.instrument.clone(),
+            side: side_name(order.side).to_string(),
+            order_type: order_type_name(order.order_type).to_string(),
+            quantity: order.quantity,
+            price: order.price,
+            stop_price: order.stop_price,
+            trail_ticks,
+            trail_percent,
+            time_in_force,
+            expire_at,
+        }
+    }
+
+    /// The modify message moving a working order to `order`'s terms.
+    pub fn order_modify(broker_order_id: String, order: &Order) -> Self {
+        let (trail_ticks, trail_percent) = trail_fields(order.trail);
+        OutboundMessage::OrderModify {
+            broker_order_id,
+            quantity: Some(order.quantity),
+            price: order.price,
+            stop_price: order.stop_price,
+            trail_ticks,
+            trail_percent,
+        }
+    }
+}
+
+fn default_time_in_force() -> String {
+    "gtc".to_string()
+}
+
 /// Frame a message with a 4-byte length prefix (big-endian).
 pub fn frame_message(msg: &[u8]) -> Vec<u8> {
     let len = msg.len() as u32;
@@ -123,3 +181,75 @@ pub fn frame_message(msg: &[u8]) -> Vec<u8> {
     framed.extend_from_slice(msg);
     framed
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use propbot_core::{Side, TimeInForce, TrailingOffset};
+    use rust_decimal_macros::dec;
+    use serde_json::{json, Value};
+
+    fn submit(order: &Order) -> Value {
+        serde_json::to_value(OutboundMessage::order_submit(order)).unwrap()
+    }
+
+    #[test]
+    fn test_order_submit_wire_fields() {
+        let stop_limit = submit(&Order::stop_limit(
+            "ES",
+            Side::Buy,
+            dec!(1),
+            dec!(4755),
+            dec!(4754),
+        ));
+        assert_eq!(stop_limit["type"], "order_submit");
+        assert_eq!(stop_limit["order_type"], "stop_limit");
+        assert_eq!(stop_limit["side"], "buy");
+        assert_eq!(stop_limit["stop_price"], json!(dec!(4755)));
+        assert_eq!(stop_limit["price"], json!(dec!(4754)));
+
+        let trailing = submit(&Order::trailing_stop(
+            "ES",
+            Side::Sell,
+            dec!(1),
+            TrailingOffset::Ticks(dec!(8)),
+        ));
+        assert_eq!(trailing["order_type"], "trailing_stop");
+        assert_eq!(trailing["trail_ticks"], json!(dec!(8)));
+        assert_eq!(trailing["trail_percent"], Value::Null);
+
+        let mit = submit(&Order::market_if_touched(
+            "ES",
+            Side::Buy,
+            dec!(2),
+            dec!(4740),
+        ));
+        assert_eq!(mit["order_type"], "market_if_touched");
+        assert_eq!(mit["stop_price"], json!(dec!(4740)));
+
+        let expiry = Utc.with_ymd_and_hms(2024, 1, 5, 21, 0, 0).unwrap();
+        let gtd = submit(
+            &Order::limit("ES", Side::Sell, dec!(1), dec!(4760))
+                .with_time_in_force(TimeInForce::Gtd(expiry)),
+        );
+        assert_eq!(gtd["time_in_force"], "gtd");
+        assert_eq!(gtd["expire_at"], json!(expiry));
+    }
+
+    #[test]
+    fn test_order_modify_sends_trail_percent() {
+        let order = Order::trailing_stop(
+            "ES",
+            Side::Sell,
+            dec!(1),
+            TrailingOffset::Percent(dec!(0.5)),
+        );
+        let modify =
+            serde_json::to_value(OutboundMessage::order_modify("42".to_string(), &order)).unwrap();
+        assert_eq!(modify["type"], "order_modify");
+        assert_eq!(modify["broker_order_id"], "42");
+        assert_eq!(modify["trail_ticks"], Value::Null);
+        assert_eq!(modify["trail_percent"], json!(dec!(0.5)));
+    }
+}
@@ -0,0 +1,385 @@
+//! Exchange trading calendars: session hours, holidays and early closes.
+//!
+//! A session is identified by its trading date, the date on which it closes.
+//! Sessions that open the evening before (CME Globex opens Sunday evening for
+//! Monday) belong to the following day, so the trading date of a timestamp is
+//! the date of the next session close.
+//!
+//! Session times are wall-clock times in the exchange's time zone, so they
+//! follow its daylight saving changes.
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::traits::DataError;
+
+/// When an exchange is open. Times are local to `timezone`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TradingCalendar {
+    pub name: String,
+    /// IANA time zone of the exchange (e.g. "America/Chicago"); UTC if omitted.
+    #[serde(default = "utc")]
+    pub timezone: Tz,
+    /// Session open. If it is not before `close`, the session opens on the
+    /// previous calendar day.
+    pub open: NaiveTime,
+    /// Regular session close; `00:00` means midnight at the end of the day.
+    pub close: NaiveTime,
+    /// Weekdays on which sessions close.
+    #[serde(default = "weekdays")]
+    pub trading_days: Vec<Weekday>,
+    /// Dates with no session.
+    #[serde(default)]
+    pub holidays: BTreeSet<NaiveDate>,
+    /// Dates whose session closes early, with the early close time.
+    #[serde(default)]
+    pub early_closes: BTreeMap<NaiveDate, NaiveTime>,
+}
+
+fn utc() -> Tz {
+    Tz::UTC
+}
+
+fn weekdays() -> Vec<Weekday> {
+    vec![
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+    ]
+}
+
+impl TradingCalendar {
+    /// Open around the clock every day, with sessions on UTC dates (crypto).
+    pub fn always_open() -> Self {
+        Self {
+            name: "24x7".to_string(),
+            timezone: Tz::UTC,
+            open: NaiveTime::MIN,
+            close: NaiveTime::MIN,
+            trading_days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+                Weekday::Sat,
+                Weekday::Sun,
+            ],
+            holidays: BTreeSet::new(),
+            early_closes: BTreeMap::new(),
+        }
+    }
+
+    /// CME Globex equity index futures (ES, NQ, YM, RTY) for 2024-2026.
+    ///
+    /// Sessions run 17:00-16:00 Chicago time, with early closes at 12:00 or
+    /// 12:15.
+    pub fn cme_equity() -> Self {
+        let date = |s: &str| s.parse::<NaiveDate>().expect("valid date");
+        let time = |s: &str| s.parse::<NaiveTime>().expect("valid time");
+        let holidays = [
+            "2024-01-01",
+            "2024-03-29",
+            "2024-12-25",
+            "2025-01-01",
+            "2025-04-18",
+            "2025-12-25",
+            "2026-01-01",
+            "2026-04-03",
+            "2026-12-25",
+        ];
+        let early_closes = [
+            // MLK Day, Presidents Day, Memorial Day, Juneteenth,
+            // Independence Day, Labor Day, Thanksgiving
+            ("2024-01-15", "12:00"),
+            ("2024-02-19", "12:00"),
+            ("2024-05-27", "12:00"),
+            ("2024-06-19", "12:00"),
+            ("2024-07-04", "12:00"),
+            ("2024-09-02", "12:00"),
+            ("2024-11-28", "12:00"),
+            ("2025-01-20", "12:00"),
+            ("2025-02-17", "12:00"),
+            ("2025-05-26", "12:00"),
+            ("2025-06-19", "12:00"),
+            ("2025-07-04", "12:00"),
+            ("2025-09-01", "12:00"),
+            ("2025-11-27", "12:00"),
+            ("2026-01-19", "12:00"),
+            ("2026-02-16", "12:00"),
+            ("2026-05-25", "12:00"),
+            ("2026-06-19", "12:00"),
+            ("2026-07-03", "12:00"),
+            ("2026-09-07", "12:00"),
+            ("2026-11-26", "12:00"),
+            // Black Friday and Christmas Eve
+            ("2024-11-29", "12:15"),
+            ("2024-12-24", "12:15"),
+            ("2025-11-28", "12:15"),
+            ("2025-12-24", "12:15"),
+            ("2026-11-27", "12:15"),
+            ("2026-12-24", "12:15"),
+        ];
+        Self {
+            name: "CME equity".to_string(),
+            timezone: chrono_tz::America::Chicago,
+            open: time("17:00"),
+            close: time("16:00"),
+            trading_days: weekdays(),
+            holidays: holidays.into_iter().map(date).collect(),
+            early_closes: early_closes
+                .into_iter()
+                .map(|(d, t)| (date(d), time(t)))
+                .collect(),
+        }
+    }
+
+    /// A built-in calendar by name ("24x7" or "cme_equity").
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "24x7" | "always_open" => Some(Self::always_open()),
+            "cme_equity" | "cme" => Some(Self::cme_equity()),
+            _ => None,
+        }
+    }
+
+    /// Load a calendar from a TOML file.
+    pub fn from_toml_file(path: &Path) -> Result<Self, DataError> {
+        let text = std::fs::read_to_string(path)?;
+        Self::from_toml_str(&text)
+    }
+
+    pub fn from_toml_str(text: &str) -> Result<Self, DataError> {
+        let calendar: Self = toml::from_str(text)
+            .map_err(|e| DataError::ParseError(format!("Invalid trading calendar: {}", e)))?;
+        if calendar.trading_days.is_empty() {
+            return Err(DataError::ParseError(
+                "Trading calendar has no trading days".to_string(),
+            ));
+        }
+        Ok(calendar)
+    }
+
+    /// Whether a session closes on `date`.
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        self.trading_days.contains(&date.weekday()) && !self.holidays.contains(&date)
+    }
+
+    /// The first trading day after `date`.
+    pub fn next_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        self.trading_day_from(date + Duration::days(1))
+    }
+
+    /// Number of trading days from `start` to `end`, inclusive.
+    pub fn trading_days_between(&self, start: NaiveDate, end: NaiveDate) -> usize {
+        start
+            .iter_days()
+            .take_while(|d| *d <= end)
+            .filter(|d| self.is_trading_day(*d))
+            .count()
+    }
+
+    /// The open and close of the session on `date`, if there is one.
+    pub fn session_bounds(&self, date: NaiveDate) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        if !self.is_trading_day(date) {
+            return None;
+        }
+        let open_day = if self.overnight() {
+            date - Duration::days(1)
+        } else {
+            date
+        };
+        Some((self.to_utc(open_day, self.open), self.close_on(date)))
+    }
+
+    /// The trading date `timestamp` counts towards: times at or after a
+    /// session's close (or on a holiday) belong to the next session.
+    pub fn session_date(&self, timestamp: DateTime<Utc>) -> NaiveDate {
+        let date = timestamp.with_timezone(&self.timezone).date_naive();
+        if timestamp >= self.close_on(date) {
+            self.next_trading_day(date)
+        } else {
+            self.trading_day_from(date)
+        }
+    }
+
+    /// Whether the market is open at `timestamp`.
+    pub fn is_open(&self, timestamp: DateTime<Utc>) -> bool {
+        self.session_bounds(self.session_date(timestamp))
+            .is_some_and(|(open, close)| open <= timestamp && timestamp < close)
+    }
+
+    /// The first session close after `timestamp`.
+    pub fn next_close(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        self.close_on(self.session_date(timestamp))
+    }
+
+    /// Whether sessions open on the previous calendar day.
+    fn overnight(&self) -> bool {
+        self.close != NaiveTime::MIN && self.open >= self.close
+    }
+
+    /// When the session on `date` closes (or would close on a non-trading day).
+    fn close_on(&self, date: NaiveDate) -> DateTime<Utc> {
+        match self.early_closes.get(&date) {
+            Some(close) => self.to_utc(date, *close),
+            None if self.close == NaiveTime::MIN => {
+                self.to_utc(date + Duration::days(1), NaiveTime::MIN)
+            }
+            None => self.to_utc(date, self.close),
+        }
+    }
+
+    /// The instant of a local wall-clock time on `date`. A time skipped by a
+    /// daylight saving change is taken an hour later.
+    fn to_utc(&self, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+        let local = date.and_time(time);
+        self.timezone
+            .from_local_datetime(&local)
+            .earliest()
+            .or_else(|| {
+                self.timezone
+                    .from_local_datetime(&(local + Duration::hours(1)))
+                    .earliest()
+            })
+            .expect("DST gaps are at most an hour")
+            .with_timezone(&Utc)
+    }
+
+    /// `date` if it is a trading day, otherwise the next one.
+    fn trading_day_from(&self, date: NaiveDate) -> NaiveDate {
+        date.iter_days()
+            .take(366)
+            .find(|d| self.is_trading_day(*d))
+            .unwrap_or(date)
+    }
+}
+
+impl Default for TradingCalendar {
+    fn default() -> Self {
+        Self::always_open()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn day(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cme_sessions_span_the_evening_before() {
+        let cme = TradingCalendar::cme_equity();
+
+        // Sunday evening trades for Monday (17:00 CST is 23:00 UTC)
+        assert!(!cme.is_open(at("2024-03-03T22:30:00Z")));
+        assert_eq!(
+            cme.session_date(at("2024-03-03T23:30:00Z")),
+            day("2024-03-04")
+        );
+        assert!(cme.is_open(at("2024-03-03T23:30:00Z")));
+        // Daily maintenance break and the weekend
+        assert!(cme.is_open(at("2024-03-04T21:30:00Z")));
+        assert!(!cme.is_open(at("2024-03-04T22:30:00Z")));
+        assert!(!cme.is_open(at("2024-03-09T12:00:00Z")));
+        assert_eq!(
+            cme.session_date(at("2024-03-08T22:00:00Z")),
+            day("2024-03-11")
+        );
+    }
+
+    #[test]
+    fn test_cme_sessions_follow_daylight_saving() {
+        let cme = TradingCalendar::cme_equity();
+
+        // 16:00-17:00 CDT is 21:00-22:00 UTC
+        assert!(cme.is_open(at("2024-07-08T20:30:00Z")));
+        assert!(!cme.is_open(at("2024-07-08T21:30:00Z")));
+        assert!(cme.is_open(at("2024-07-08T22:30:00Z")));
+        assert_eq!(
+            cme.session_bounds(day("2024-07-09")),
+            Some((at("2024-07-08T22:00:00Z"), at("2024-07-09T21:00:00Z")))
+        );
+        // The session that spans the switch to daylight time on 2024-03-10
+        assert_eq!(
+            cme.session_bounds(day("2024-03-11")),
+            Some((at("2024-03-10T22:00:00Z"), at("2024-03-11T21:00:00Z")))
+        );
+        assert_eq!(
+            cme.next_close(at("2024-01-12T20:00:00Z")),
+            at("2024-01-12T22:00:00Z")
+        );
+    }
+
+    #[test]
+    fn test_cme_holidays_and_early_closes() {
+        let cme = TradingCalendar::cme_equity();
+
+        // Good Friday: closed, so Thursday evening trades for Monday
+        assert!(!cme.is_trading_day(day("2024-03-29")));
+        assert!(!cme.is_open(at("2024-03-29T15:00:00Z")));
+        assert_eq!(
+            cme.session_date(at("2024-03-28T22:30:00Z")),
+            day("2024-04-01")
+        );
+
+        // Black Friday closes at 18:15
+        assert!(cme.is_open(at("2024-11-29T18:00:00Z")));
+        assert!(!cme.is_open(at("2024-11-29T18:30:00Z")));
+        assert_eq!(
+            cme.session_date(at("2024-11-29T18:30:00Z")),
+            day("2024-12-02")
+        );
+
+        // December 2024 has 22 weekdays, less Christmas
+        assert_eq!(
+            cme.trading_days_between(day("2024-12-01"), day("2024-12-31")),
+            21
+        );
+    }
+
+    #[test]
+    fn test_calendar_from_toml() {
+        let calendar = TradingCalendar::from_toml_str(
+            r#"
+            name = "Day session"
+            timezone = "America/New_York"
+            open = "09:30"
+            close = "16:00"
+            holidays = ["2024-07-04"]
+
+            [early_closes]
+            2024-07-03 = "13:00"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(calendar.trading_days.len(), 5);
+        assert_eq!(
+            calendar.session_bounds(day("2024-07-03")),
+            Some((at("2024-07-03T13:30:00Z"), at("2024-07-03T17:00:00Z")))
+        );
+        assert_eq!(calendar.session_bounds(day("2024-07-04")), None);
+        assert!(!calendar.is_open(at("2024-07-05T13:00:00Z")));
+        assert!(calendar.is_open(at("2024-07-05T13:30:00Z")));
+
+        let always = TradingCalendar::always_open();
+        assert_eq!(
+            always.session_date(at("2024-07-06T23:59:00Z")),
+            day("2024-07-06")
+        );
+        assert!(always.is_open(at("2024-07-06T23:59:00Z")));
+    }
+}
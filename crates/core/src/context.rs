@@ -0,0 +1,136 @@
+use crate::events::RiskViolation;
+use crate::models::*;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// An order action requested by a strategy through its [`StrategyContext`].
+#[derive(Debug, Clone)]
+pub enum OrderRequest {
+    /// Submit a new order (subject to risk checks).
+    Place(Order),
+    /// Cancel a working order by ID.
+    Cancel(Uuid),
+    /// Replace a working order's price, stop price, trail and quantity.
+    Modify(Order),
+}
+
+/// Risk manager state visible to strategies.
+#[derive(Debug, Clone, Default)]
+pub struct RiskStatus {
+    /// Trading is halted; new orders will be rejected.
+    pub halted: bool,
+    /// Current warnings and breaches.
+    pub violations: Vec<RiskViolation>,
+}
+
+/// A strategy's view of the account during a callback.
+///
+/// The engine fills in a snapshot of the strategy's own positions and working
+/// orders, the account and risk state, and instrument specs before each
+/// callback. Orders placed, cancelled or modified through the context are
+/// queued and executed by the engine once the callback returns.
+#[derive(Debug, Clone)]
+pub struct StrategyContext {
+    strategy_id: String,
+    /// Time of the event being processed.
+    pub timestamp: DateTime<Utc>,
+    pub account: AccountState,
+    /// This strategy's open positions.
+    pub positions: Vec<Position>,
+    /// This strategy's working orders.
+    pub working_orders: Vec<Order>,
+    pub risk: RiskStatus,
+    /// Instrument specs keyed by symbol.
+    pub instruments: HashMap<String, Instrument>,
+    requests: Vec<OrderRequest>,
+}
+
+impl StrategyContext {
+    pub fn new(strategy_id: &str, timestamp: DateTime<Utc>, account: AccountState) -> Self {
+        Self {
+            strategy_id: strategy_id.to_string(),
+            timestamp,
+            account,
+            positions: Vec::new(),
+            working_orders: Vec::new(),
+            risk: RiskStatus::default(),
+            instruments: HashMap::new(),
+            requests: Vec::new(),
+        }
+    }
+
+    /// The strategy this context belongs to.
+    pub fn strategy_id(&self) -> &str {
+        &self.strategy_id
+    }
+
+    /// This strategy's open position on an instrument, if any.
+    pub fn position(&self, instrument: &str) -> Option<&Position> {
+        self.positions.iter().find(|p| p.instrument == instrument)
+    }
+
+    /// Side of this strategy's position on an instrument (`None` when flat).
+    pub fn position_side(&self, instrument: &str) -> Option<Side> {
+        self.position(instrument).map(|p| p.side)
+    }
+
+    /// This strategy's working orders on an instrument.
+    pub fn orders_for<'a>(&'a self, instrument: &'a str) -> impl Iterator<Item = &'a Order> {
+        self.working_orders
+            .iter()
+            .filter(move |o| o.instrument == instrument)
+    }
+
+    /// Instrument specs by symbol.
+    pub fn instrument(&self, symbol: &str) -> Option<&Instrument> {
+        self.instruments.get(symbol)
+    }
+
+    /// Queue a new order, tagged with this strategy. Returns the order ID.
+    pub fn place_order(&mut self, mut order: Order) -> Uuid {
+        order.strategy_id = Some(self.strategy_id.clone());
+        let id = order.id;
+        self.requests.push(OrderRequest::Place(order));
+        id
+    }
+
+    /// Queue cancellation of a working order.
+    pub fn cancel_order(&mut self, order_id: Uuid) {
+        self.requests.push(OrderRequest::Cancel(order_id));
+    }
+
+    /// Queue a modification of a working order.
+    pub fn modify_order(&mut self, order: Order) {
+        self.requests.push(OrderRequest::Modify(order));
+    }
+
+    /// Take the queued order requests (called by the engine).
+    pub fn take_requests(&mut self) -> Vec<OrderRequest> {
+        std::mem::take(&mut self.requests)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_place_order_tags_strategy_and_queues() {
+        let mut ctx = StrategyContext::new("s1", Utc::now(), AccountState::new(dec!(50000)));
+        let id = ctx.place_order(Order::limit("ES", Side::Buy, dec!(1), dec!(4700)));
+        ctx.cancel_order(id);
+
+        let requests = ctx.take_requests();
+        match requests.as_slice() {
+            [OrderRequest::Place(order), OrderRequest::Cancel(cancel_id)] => {
+                assert_eq!(order.id, id);
+                assert_eq!(order.strategy_id.as_deref(), Some("s1"));
+                assert_eq!(*cancel_id, id);
+            }
+            other => panic!("Unexpected requests: {:?}", other),
+        }
+        assert!(ctx.take_requests().is_empty());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::models::*;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
@@ -45,6 +46,13 @@ pub enum RiskEvent {
     },
 }
 
+/// A risk event raised during a run, with when it happened.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RiskEventRecord {
+    pub timestamp: DateTime<Utc>,
+    pub event: RiskEvent,
+}
+
 /// Details about a risk rule violation.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RiskViolation {
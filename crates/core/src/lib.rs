@@ -1,7 +1,11 @@
+pub mod calendar;
+pub mod context;
 pub mod events;
 pub mod models;
 pub mod traits;
 
+pub use calendar::TradingCalendar;
+pub use context::*;
 pub use events::*;
 pub use models::*;
 pub use traits::*;
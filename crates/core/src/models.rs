@@ -1,8 +1,10 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, Utc};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::events::RiskEventRecord;
+
 // ---------------------------------------------------------------------------
 // Asset & Instrument
 // ---------------------------------------------------------------------------
@@ -60,6 +62,26 @@ pub struct Tick {
     pub volume: Decimal,
 }
 
+/// A roll from one futures contract to the next within a continuous series.
+///
+/// Prices are in the continuous series' price space, so for back-adjusted
+/// series `exit_price` and `entry_price` coincide.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractRoll {
+    /// Continuous symbol (e.g. "ES").
+    pub instrument: String,
+    /// Timestamp of the first bar taken from the new contract.
+    pub timestamp: DateTime<Utc>,
+    pub from_contract: String,
+    pub to_contract: String,
+    /// Price at which positions in the old contract are closed.
+    pub exit_price: Decimal,
+    /// Price at which they are reopened in the new contract.
+    pub entry_price: Decimal,
+    /// Raw price difference between the contracts at the roll (new - old).
+    pub price_gap: Decimal,
+}
+
 /// Timeframe for bars.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -73,6 +95,111 @@ pub enum Timeframe {
     Monthly,
 }
 
+impl Timeframe {
+    /// Fixed length of one bar, if the timeframe has one (ticks and months don't).
+    pub fn duration(&self) -> Option<Duration> {
+        match self {
+            Timeframe::Tick | Timeframe::Monthly => None,
+            Timeframe::Second(n) => Some(Duration::seconds(i64::from(*n))),
+            Timeframe::Minute(n) => Some(Duration::minutes(i64::from(*n))),
+            Timeframe::Hour(n) => Some(Duration::hours(i64::from(*n))),
+            Timeframe::Daily => Some(Duration::days(1)),
+            Timeframe::Weekly => Some(Duration::weeks(1)),
+        }
+    }
+
+    /// Start of the bar period containing `timestamp`.
+    ///
+    /// Intraday periods are aligned to UTC midnight, weeks start on Monday
+    /// and months on the 1st (all UTC).
+    pub fn period_start(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let midnight = timestamp.date_naive().and_time(NaiveTime::MIN).and_utc();
+        let floor = |step: i64| {
+            let since_midnight = (timestamp - midnight).num_seconds();
+            midnight + Duration::seconds(since_midnight - since_midnight.rem_euclid(step.max(1)))
+        };
+        match self {
+            Timeframe::Tick => timestamp,
+            Timeframe::Second(n) => floor(i64::from(*n)),
+            Timeframe::Minute(n) => floor(i64::from(*n) * 60),
+            Timeframe::Hour(n) => floor(i64::from(*n) * 3600),
+            Timeframe::Daily => midnight,
+            Timeframe::Weekly => {
+                midnight - Duration::days(i64::from(timestamp.weekday().num_days_from_monday()))
+            }
+            Timeframe::Monthly => midnight - Duration::days(i64::from(timestamp.day0())),
+        }
+    }
+
+    /// End (exclusive) of the bar period containing `timestamp`.
+    pub fn period_end(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let start = self.period_start(timestamp);
+        match self {
+            Timeframe::Monthly => start
+                .checked_add_months(Months::new(1))
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            _ => start + self.duration().unwrap_or_default(),
+        }
+    }
+
+    /// Whether bars of this timeframe can be built from a `base` feed: it must
+    /// be strictly coarser and a whole multiple of the base.
+    pub fn builds_from(&self, base: Timeframe) -> bool {
+        match (self.duration(), base.duration()) {
+            (Some(higher), Some(base)) => {
+                let base = base.num_seconds();
+                higher.num_seconds() > base && higher.num_seconds() % base == 0
+            }
+            // Months are built from anything up to daily bars
+            (None, Some(base)) => *self == Timeframe::Monthly && base.num_days() <= 1,
+            (_, None) => base == Timeframe::Tick && *self != Timeframe::Tick,
+        }
+    }
+}
+
+impl std::fmt::Display for Timeframe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Timeframe::Tick => write!(f, "tick"),
+            Timeframe::Second(n) => write!(f, "{}s", n),
+            Timeframe::Minute(n) => write!(f, "{}m", n),
+            Timeframe::Hour(n) => write!(f, "{}h", n),
+            Timeframe::Daily => write!(f, "1d"),
+            Timeframe::Weekly => write!(f, "1w"),
+            Timeframe::Monthly => write!(f, "1mo"),
+        }
+    }
+}
+
+impl std::str::FromStr for Timeframe {
+    type Err = String;
+
+    /// Parse "tick", "30s", "5m", "1h", "1d"/"daily", "1w"/"weekly" or "1mo"/"monthly".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        match s.as_str() {
+            "tick" => return Ok(Timeframe::Tick),
+            "1d" | "daily" => return Ok(Timeframe::Daily),
+            "1w" | "weekly" => return Ok(Timeframe::Weekly),
+            "1mo" | "monthly" => return Ok(Timeframe::Monthly),
+            _ => {}
+        }
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (count, unit) = s.split_at(split);
+        let count: u32 = count
+            .parse()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| format!("Invalid timeframe: {}", s))?;
+        match unit {
+            "s" | "sec" => Ok(Timeframe::Second(count)),
+            "m" | "min" => Ok(Timeframe::Minute(count)),
+            "h" => Ok(Timeframe::Hour(count)),
+            _ => Err(format!("Invalid timeframe: {}", s)),
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Orders
 // ---------------------------------------------------------------------------
@@ -101,7 +228,49 @@ pub enum OrderType {
     Market,
     Limit,
     Stop,
+    /// Becomes a limit order at `price` once `stop_price` is traded through.
     StopLimit,
+    /// Stop whose `stop_price` follows the market by a fixed offset (see `Order::trail`).
+    TrailingStop,
+    /// Becomes a market order once `stop_price` is touched (buy below / sell above market).
+    MarketIfTouched,
+}
+
+/// How long an order stays working before the broker cancels it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeInForce {
+    /// Cancelled at the close of the session it was submitted in.
+    Day,
+    /// Good 'til cancelled.
+    #[default]
+    Gtc,
+    /// Immediate or cancel: fill what is available now, cancel the rest.
+    Ioc,
+    /// Fill or kill: fill the whole quantity now or cancel it entirely.
+    Fok,
+    /// Good 'til date: cancelled once the given time is reached.
+    Gtd(DateTime<Utc>),
+}
+
+/// Distance a trailing stop keeps from the best price seen since submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailingOffset {
+    /// A fixed number of ticks.
+    Ticks(Decimal),
+    /// A percentage of the reference price (e.g. 0.5 = 0.5%).
+    Percent(Decimal),
+}
+
+impl TrailingOffset {
+    /// Convert the offset to a price distance from `reference`.
+    pub fn distance(&self, reference: Decimal, tick_size: Decimal) -> Decimal {
+        match self {
+            TrailingOffset::Ticks(ticks) => *ticks * tick_size,
+            TrailingOffset::Percent(pct) => reference * *pct / Decimal::ONE_HUNDRED,
+        }
+    }
 }
 
 /// The lifecycle state of an order.
@@ -126,7 +295,13 @@ pub struct Order {
     pub quantity: Decimal,
     pub filled_quantity: Decimal,
     pub price: Option<Decimal>,
+    /// Trigger price for stop, stop-limit, trailing-stop and market-if-touched orders.
     pub stop_price: Option<Decimal>,
+    /// Trailing distance for trailing-stop orders.
+    #[serde(default)]
+    pub trail: Option<TrailingOffset>,
+    #[serde(default)]
+    pub time_in_force: TimeInForce,
     pub status: OrderStatus,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -149,6 +324,8 @@ impl Order {
             filled_quantity: Decimal::ZERO,
             price: None,
             stop_price: None,
+            trail: None,
+            time_in_force: TimeInForce::Gtc,
             status: OrderStatus::Pending,
             created_at: now,
             updated_at: now,
@@ -169,6 +346,8 @@ impl Order {
             filled_quantity: Decimal::ZERO,
             price: Some(price),
             stop_price: None,
+            trail: None,
+            time_in_force: TimeInForce::Gtc,
             status: OrderStatus::Pending,
             created_at: now,
             updated_at: now,
@@ -189,6 +368,8 @@ impl Order {
             filled_quantity: Decimal::ZERO,
             price: None,
             stop_price: Some(stop_price),
+            trail: None,
+            time_in_force: TimeInForce::Gtc,
             status: OrderStatus::Pending,
             created_at: now,
             updated_at: now,
@@ -197,6 +378,56 @@ impl Order {
         }
     }
 
+    /// Create a new stop-limit order.
+    pub fn stop_limit(
+        instrument: &str,
+        side: Side,
+        quantity: Decimal,
+        stop_price: Decimal,
+        limit_price: Decimal,
+    ) -> Self {
+        Self {
+            order_type: OrderType::StopLimit,
+            price: Some(limit_price),
+            ..Self::stop(instrument, side, quantity, stop_price)
+        }
+    }
+
+    /// Create a new trailing stop order.
+    ///
+    /// The stop price is set by the broker from the market price at submission.
+    pub fn trailing_stop(
+        instrument: &str,
+        side: Side,
+        quantity: Decimal,
+        trail: TrailingOffset,
+    ) -> Self {
+        Self {
+            order_type: OrderType::TrailingStop,
+            trail: Some(trail),
+            ..Self::market(instrument, side, quantity)
+        }
+    }
+
+    /// Create a new market-if-touched order.
+    pub fn market_if_touched(
+        instrument: &str,
+        side: Side,
+        quantity: Decimal,
+        touch_price: Decimal,
+    ) -> Self {
+        Self {
+            order_type: OrderType::MarketIfTouched,
+            ..Self::stop(instrument, side, quantity, touch_price)
+        }
+    }
+
+    /// Set the order's time in force.
+    pub fn with_time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.time_in_force = time_in_force;
+        self
+    }
+
     pub fn is_active(&self) -> bool {
         matches!(
             self.status,
@@ -221,6 +452,35 @@ pub struct Fill {
     pub timestamp: DateTime<Utc>,
 }
 
+// ---------------------------------------------------------------------------
+// Order log
+// ---------------------------------------------------------------------------
+
+/// What changed in an order-log entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderUpdateKind {
+    Submitted,
+    Modified,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+/// One change in an order's state, as kept in the order log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderUpdate {
+    pub kind: OrderUpdateKind,
+    /// The order as it stood after the change.
+    pub order: Order,
+    /// The fill behind a fill update.
+    pub fill: Option<Fill>,
+    /// Why the order was cancelled or rejected.
+    pub reason: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
 // ---------------------------------------------------------------------------
 // Position
 // ---------------------------------------------------------------------------
@@ -268,6 +528,12 @@ pub struct Trade {
     pub entry_time: DateTime<Utc>,
     pub exit_time: DateTime<Utc>,
     pub strategy_id: Option<String>,
+    /// Maximum adverse excursion: the worst open loss while the trade was on.
+    #[serde(default)]
+    pub mae: Decimal,
+    /// Maximum favorable excursion: the best open profit while the trade was on.
+    #[serde(default)]
+    pub mfe: Decimal,
 }
 
 impl Trade {
@@ -363,6 +629,9 @@ pub struct BacktestResult {
     pub id: Uuid,
     pub strategy_id: String,
     pub instrument: String,
+    /// Timeframe of the bars the backtest ran on, if known.
+    #[serde(default)]
+    pub timeframe: Option<Timeframe>,
     pub start_date: DateTime<Utc>,
     pub end_date: DateTime<Utc>,
     pub initial_balance: Decimal,
@@ -379,14 +648,137 @@ pub struct BacktestResult {
     pub profit_factor: Decimal,
     pub sharpe_ratio: Decimal,
     pub sortino_ratio: Decimal,
+    /// Annualized standard deviation of daily returns, in percent.
+    #[serde(default)]
+    pub volatility: Decimal,
     pub avg_trade_pnl: Decimal,
     pub avg_winner: Decimal,
     pub avg_loser: Decimal,
     pub total_commission: Decimal,
+    /// Compound annual return over the last 36 months over their max drawdown percent.
+    #[serde(default)]
+    pub calmar_ratio: Decimal,
+    /// Compound annual return over the whole test over its max drawdown percent.
+    #[serde(default)]
+    pub mar_ratio: Decimal,
+    /// Average R-multiple per trade, where 1R is the average losing trade.
+    /// `None` without losing trades: R is undefined then, and zero would
+    /// read as break-even.
+    #[serde(default)]
+    pub expectancy: Option<Decimal>,
+    #[serde(default)]
+    pub largest_winner: Decimal,
+    /// Net loss of the worst trade, as a positive amount.
+    #[serde(default)]
+    pub largest_loser: Decimal,
+    #[serde(default)]
+    pub max_consecutive_wins: usize,
+    #[serde(default)]
+    pub max_consecutive_losses: usize,
+    /// Longest time from an equity peak to the next new high (or the end of the test).
+    #[serde(default)]
+    pub max_drawdown_duration_hours: Decimal,
+    /// Time from the deepest drawdown's trough back to its peak (or the end of the test).
+    #[serde(default)]
+    pub max_drawdown_recovery_hours: Decimal,
+    #[serde(default)]
+    pub avg_trade_duration_hours: Decimal,
+    /// Share of the test period with at least one position open.
+    #[serde(default)]
+    pub time_in_market_percent: Decimal,
+    #[serde(default)]
+    pub avg_mae: Decimal,
+    #[serde(default)]
+    pub avg_mfe: Decimal,
+    /// Return per calendar month (UTC).
+    #[serde(default)]
+    pub monthly_returns: Vec<PeriodReturn>,
+    /// Return per trading day.
+    #[serde(default)]
+    pub daily_returns: Vec<PeriodReturn>,
     /// Per-bar equity snapshots.
     pub equity_curve: Vec<EquityPoint>,
     /// All trades executed.
     pub trades: Vec<Trade>,
+    /// Every order state change during the run, in order.
+    #[serde(default)]
+    pub order_log: Vec<OrderUpdate>,
+    /// Risk rule violations, blocked orders and auto-flattens, in order.
+    #[serde(default)]
+    pub risk_events: Vec<RiskEventRecord>,
+}
+
+/// A summary metric's name and how to read it from a result.
+pub type Metric = (&'static str, fn(&BacktestResult) -> Option<Decimal>);
+
+impl BacktestResult {
+    /// The summary metrics by name, usable with [`BacktestResult::metric`].
+    /// The names match the `backtest_results` column names.
+    pub const METRICS: &'static [Metric] = &[
+        ("final_balance", |r| Some(r.final_balance)),
+        ("net_profit", |r| Some(r.net_profit)),
+        ("gross_profit", |r| Some(r.gross_profit)),
+        ("gross_loss", |r| Some(r.gross_loss)),
+        ("total_trades", |r| Some(Decimal::from(r.total_trades))),
+        ("winning_trades", |r| Some(Decimal::from(r.winning_trades))),
+        ("losing_trades", |r| Some(Decimal::from(r.losing_trades))),
+        ("win_rate", |r| Some(r.win_rate)),
+        ("profit_factor", |r| Some(r.profit_factor)),
+        ("max_drawdown", |r| Some(r.max_drawdown)),
+        ("max_drawdown_percent", |r| Some(r.max_drawdown_percent)),
+        ("sharpe_ratio", |r| Some(r.sharpe_ratio)),
+        ("sortino_ratio", |r| Some(r.sortino_ratio)),
+        ("volatility", |r| Some(r.volatility)),
+        ("avg_trade_pnl", |r| Some(r.avg_trade_pnl)),
+        ("avg_winner", |r| Some(r.avg_winner)),
+        ("avg_loser", |r| Some(r.avg_loser)),
+        ("total_commission", |r| Some(r.total_commission)),
+        ("calmar_ratio", |r| Some(r.calmar_ratio)),
+        ("mar_ratio", |r| Some(r.mar_ratio)),
+        ("expectancy", |r| r.expectancy),
+        ("largest_winner", |r| Some(r.largest_winner)),
+        ("largest_loser", |r| Some(r.largest_loser)),
+        ("max_consecutive_wins", |r| {
+            Some(Decimal::from(r.max_consecutive_wins))
+        }),
+        ("max_consecutive_losses", |r| {
+            Some(Decimal::from(r.max_consecutive_losses))
+        }),
+        ("max_drawdown_duration_hours", |r| {
+            Some(r.max_drawdown_duration_hours)
+        }),
+        ("max_drawdown_recovery_hours", |r| {
+            Some(r.max_drawdown_recovery_hours)
+        }),
+        ("avg_trade_duration_hours", |r| {
+            Some(r.avg_trade_duration_hours)
+        }),
+        ("time_in_market_percent", |r| Some(r.time_in_market_percent)),
+        ("avg_mae", |r| Some(r.avg_mae)),
+        ("avg_mfe", |r| Some(r.avg_mfe)),
+    ];
+
+    /// Names of the summary metrics, in [`BacktestResult::METRICS`] order.
+    pub fn metric_names() -> impl Iterator<Item = &'static str> {
+        Self::METRICS.iter().map(|(name, _)| *name)
+    }
+
+    /// A summary metric by name; `None` for an unknown name or a metric
+    /// undefined for this run, like `expectancy` without losing trades.
+    pub fn metric(&self, name: &str) -> Option<Decimal> {
+        let (_, value) = Self::METRICS.iter().find(|(n, _)| *n == name)?;
+        value(self)
+    }
+}
+
+/// Profit and return over one calendar period of a backtest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeriodReturn {
+    /// First day of the period.
+    pub start: NaiveDate,
+    pub pnl: Decimal,
+    /// Return on the equity at the start of the period.
+    pub return_percent: Decimal,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,7 +1,9 @@
+use crate::context::StrategyContext;
 use crate::events::*;
 use crate::models::*;
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::stream::{self, BoxStream, StreamExt};
 use uuid::Uuid;
 
 // ---------------------------------------------------------------------------
@@ -9,6 +11,10 @@ use uuid::Uuid;
 // ---------------------------------------------------------------------------
 
 /// A trading strategy that processes market data and produces signals.
+///
+/// Callbacks receive a [`StrategyContext`] with the strategy's positions,
+/// working orders, account and risk state. Strategies can either return
+/// signals or place/cancel/modify orders directly through the context.
 #[async_trait]
 pub trait Strategy: Send + Sync {
     /// Unique identifier for this strategy.
@@ -21,18 +27,69 @@ pub trait Strategy: Send + Sync {
     async fn on_start(&mut self) {}
 
     /// Called on every new bar.
-    async fn on_bar(&mut self, bar: &Bar) -> Vec<Signal>;
+    async fn on_bar(&mut self, bar: &Bar, ctx: &mut StrategyContext) -> Vec<Signal>;
+
+    /// Higher timeframes to build from the base feed (e.g. `Hour(1)` as a trend
+    /// filter on a 1-minute feed). Base bars always arrive through `on_bar`.
+    fn timeframes(&self) -> Vec<Timeframe> {
+        Vec::new()
+    }
+
+    /// Called when a bar of a subscribed higher timeframe closes, before the
+    /// `on_bar` call for the base bar that completed it.
+    async fn on_timeframe_bar(
+        &mut self,
+        _timeframe: Timeframe,
+        _bar: &Bar,
+        _ctx: &mut StrategyContext,
+    ) -> Vec<Signal> {
+        Vec::new()
+    }
 
     /// Called on every new tick (optional, default no-op).
-    async fn on_tick(&mut self, _tick: &Tick) -> Vec<Signal> {
+    async fn on_tick(&mut self, _tick: &Tick, _ctx: &mut StrategyContext) -> Vec<Signal> {
         Vec::new()
     }
 
     /// Called when an order is filled.
-    async fn on_fill(&mut self, _fill: &Fill) {}
+    async fn on_fill(&mut self, _fill: &Fill, _ctx: &mut StrategyContext) {}
+
+    /// Called when this strategy's position on an instrument changes.
+    ///
+    /// Positions are tracked per strategy; a zero quantity means flat.
+    async fn on_position_update(&mut self, _position: &Position, _ctx: &mut StrategyContext) {}
 
-    /// Called when a position changes.
-    async fn on_position_update(&mut self, _position: &Position) {}
+    /// Called when the broker rejects one of this strategy's orders.
+    async fn on_order_rejected(
+        &mut self,
+        _order_id: Uuid,
+        _reason: &str,
+        _ctx: &mut StrategyContext,
+    ) {
+    }
+
+    /// Called when one of this strategy's working orders is cancelled or expires.
+    async fn on_order_cancelled(
+        &mut self,
+        _order_id: Uuid,
+        _reason: &str,
+        _ctx: &mut StrategyContext,
+    ) {
+    }
+
+    /// Called for risk events affecting this strategy: blocked orders, new
+    /// warnings or breaches, and auto-flattens.
+    async fn on_risk_event(&mut self, _event: &RiskEvent, _ctx: &mut StrategyContext) {}
+
+    /// Called once when the risk manager halts trading. Positions have already
+    /// been flattened and new orders will be rejected.
+    async fn on_risk_halt(&mut self, _reason: &str, _ctx: &mut StrategyContext) {}
+
+    /// Called at the start of each trading session, before its first bar.
+    async fn on_session_start(&mut self, _session: NaiveDate, _ctx: &mut StrategyContext) {}
+
+    /// Called at the end of each trading session, after its last bar.
+    async fn on_session_end(&mut self, _session: NaiveDate, _ctx: &mut StrategyContext) {}
 
     /// Called once on shutdown.
     async fn on_stop(&mut self) {}
@@ -93,6 +150,9 @@ pub trait Broker: Send + Sync {
     /// Flatten (close) all open positions.
     async fn flatten_all(&mut self) -> Result<(), BrokerError>;
 
+    /// Take the order state changes since the last call, oldest first.
+    fn take_order_updates(&mut self) -> Vec<OrderUpdate>;
+
     /// Subscribe to a market data event stream.
     /// Returns a receiver that yields `Event` variants.
     async fn subscribe_market_data(
@@ -121,7 +181,17 @@ pub enum DataError {
     ApiError(String),
 }
 
+/// A stream of bars in time order, as produced by [`DataProvider::stream_bars`].
+pub type BarStream<'a> = BoxStream<'a, Result<Bar, DataError>>;
+
+/// A stream of ticks in time order, as produced by [`DataProvider::stream_ticks`].
+pub type TickStream<'a> = BoxStream<'a, Result<Tick, DataError>>;
+
 /// Provides historical market data for backtesting.
+///
+/// The `stream_*` methods deliver data incrementally so long histories don't
+/// need to fit in memory. Their default implementations load everything with
+/// the `load_*` methods first; providers that can read incrementally override them.
 #[async_trait]
 pub trait DataProvider: Send + Sync {
     /// Load historical bars for an instrument within a date range.
@@ -143,6 +213,75 @@ pub trait DataProvider: Send + Sync {
 
     /// List available instruments.
     async fn available_instruments(&self) -> Result<Vec<String>, DataError>;
+
+    /// Stream historical bars for an instrument within a date range.
+    fn stream_bars<'a>(
+        &'a self,
+        instrument: &'a str,
+        timeframe: Timeframe,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BarStream<'a> {
+        stream::once(self.load_bars(instrument, timeframe, start, end))
+            .flat_map(flatten_loaded)
+            .boxed()
+    }
+
+    /// Stream historical ticks for an instrument within a date range.
+    fn stream_ticks<'a>(
+        &'a self,
+        instrument: &'a str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> TickStream<'a> {
+        stream::once(self.load_ticks(instrument, start, end))
+            .flat_map(flatten_loaded)
+            .boxed()
+    }
+}
+
+/// Turn a loaded `Vec` (or the error loading it) into a stream of items.
+fn flatten_loaded<T>(
+    loaded: Result<Vec<T>, DataError>,
+) -> stream::Iter<std::vec::IntoIter<Result<T, DataError>>> {
+    let items = match loaded {
+        Ok(items) => items.into_iter().map(Ok).collect(),
+        Err(e) => vec![Err(e)],
+    };
+    stream::iter(items)
+}
+
+/// Downloads historical data from a remote vendor.
+///
+/// Requests are paged: each call returns at most one page of data, in time
+/// order, starting at `start` (inclusive). An empty page means there is no
+/// more data up to `end`.
+#[async_trait]
+pub trait HistoricalDataApi: Send + Sync {
+    /// Vendor name, for logs and errors.
+    fn name(&self) -> &str;
+
+    /// Fetch one page of bars.
+    async fn fetch_bars(
+        &self,
+        instrument: &str,
+        timeframe: Timeframe,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Bar>, DataError>;
+
+    /// Fetch one page of ticks.
+    async fn fetch_ticks(
+        &self,
+        _instrument: &str,
+        _start: DateTime<Utc>,
+        _end: DateTime<Utc>,
+    ) -> Result<Vec<Tick>, DataError> {
+        Err(DataError::ApiError(format!(
+            "{} does not provide tick data",
+            self.name()
+        )))
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -0,0 +1,515 @@
+use chrono::{DateTime, NaiveTime, Utc};
+use propbot_core::{Bar, Tick, Timeframe, TradingCalendar};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+/// How input data is grouped into bars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BarSpec {
+    /// Fixed time periods (see [`Timeframe::period_start`] for alignment).
+    Time(Timeframe),
+    /// A bar every N ticks (or N input bars when building from bars).
+    TickCount(u32),
+    /// A bar once the traded volume reaches the threshold.
+    Volume(Decimal),
+    /// A bar once its high-low range reaches the given price distance.
+    Range(Decimal),
+    /// Renko bricks of the given size; a reversal needs two bricks of movement.
+    Renko(Decimal),
+}
+
+/// Incremental bar builder for both batch and streaming use.
+///
+/// Feed ticks or lower-timeframe bars in time order; each push returns the bars
+/// it completed. In batch mode call [`flush`](BarBuilder::flush) at the end to
+/// get the last partial bar. In live mode call [`poll`](BarBuilder::poll) on a
+/// timer to close time bars whose period has elapsed without new data.
+#[derive(Debug, Clone)]
+pub struct BarBuilder {
+    spec: BarSpec,
+    current: Option<Bar>,
+    /// End of the current time bar's period.
+    period_end: DateTime<Utc>,
+    /// Inputs (ticks or bars) merged into the current bar.
+    count: u32,
+    /// Close of the last Renko brick and whether it went up.
+    renko: Option<(Decimal, Option<bool>)>,
+    /// Volume traded since the last Renko brick.
+    renko_volume: Decimal,
+}
+
+impl BarBuilder {
+    pub fn new(spec: BarSpec) -> Self {
+        Self {
+            spec,
+            current: None,
+            period_end: DateTime::<Utc>::MIN_UTC,
+            count: 0,
+            renko: None,
+            renko_volume: Decimal::ZERO,
+        }
+    }
+
+    pub fn spec(&self) -> BarSpec {
+        self.spec
+    }
+
+    /// Add a tick, using its last price and volume.
+    pub fn push_tick(&mut self, tick: &Tick) -> Vec<Bar> {
+        let bar = Bar {
+            instrument: tick.instrument.clone(),
+            timestamp: tick.timestamp,
+            open: tick.last,
+            high: tick.last,
+            low: tick.last,
+            close: tick.last,
+            volume: tick.volume,
+        };
+        self.push_bar(&bar)
+    }
+
+    /// Add a lower-timeframe bar.
+    ///
+    /// Time, tick-count and volume bars merge whole input bars. Range and Renko
+    /// bars walk the bar's prices as open → low → high → close for up bars and
+    /// open → high → low → close for down bars.
+    pub fn push_bar(&mut self, bar: &Bar) -> Vec<Bar> {
+        match self.spec {
+            BarSpec::Time(timeframe) => self.push_time(bar, timeframe),
+            BarSpec::TickCount(n) => {
+                self.merge(bar);
+                self.count += 1;
+                if self.count >= n.max(1) {
+                    self.close().into_iter().collect()
+                } else {
+                    Vec::new()
+                }
+            }
+            BarSpec::Volume(threshold) => {
+                self.merge(bar);
+                match &self.current {
+                    Some(current) if current.volume >= threshold => {
+                        self.close().into_iter().collect()
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            BarSpec::Range(range) => {
+                let mut closed = Vec::new();
+                for (price, volume) in price_path(bar) {
+                    let point = Bar {
+                        open: price,
+                        high: price,
+                        low: price,
+                        close: price,
+                        volume,
+                        ..bar.clone()
+                    };
+                    self.merge(&point);
+                    if let Some(current) = &self.current {
+                        if current.high - current.low >= range {
+                            closed.extend(self.close());
+                        }
+                    }
+                }
+                closed
+            }
+            BarSpec::Renko(size) => {
+                let mut closed = Vec::new();
+                for (price, volume) in price_path(bar) {
+                    self.renko_volume += volume;
+                    closed.extend(self.push_renko(&bar.instrument, bar.timestamp, price, size));
+                }
+                closed
+            }
+        }
+    }
+
+    /// Close the current time bar if its period has ended by `now`.
+    ///
+    /// Only time bars close on the clock; other specs always return `None`.
+    pub fn poll(&mut self, now: DateTime<Utc>) -> Option<Bar> {
+        match self.spec {
+            BarSpec::Time(_) if now >= self.period_end => self.close(),
+            _ => None,
+        }
+    }
+
+    /// Take the partially built bar, if any (end of a batch).
+    ///
+    /// Renko has no partial brick, so this returns `None` for it.
+    pub fn flush(&mut self) -> Option<Bar> {
+        self.close()
+    }
+
+    fn push_time(&mut self, bar: &Bar, timeframe: Timeframe) -> Vec<Bar> {
+        let mut closed = Vec::new();
+        if bar.timestamp >= self.period_end {
+            closed.extend(self.close());
+            self.period_end = timeframe.period_end(bar.timestamp);
+        }
+        let start = timeframe.period_start(bar.timestamp);
+        self.merge(bar);
+        if let Some(current) = self.current.as_mut() {
+            current.timestamp = start;
+        }
+        closed
+    }
+
+    fn merge(&mut self, bar: &Bar) {
+        match self.current.as_mut() {
+            Some(current) => {
+                current.high = current.high.max(bar.high);
+                current.low = current.low.min(bar.low);
+                current.close = bar.close;
+                current.volume += bar.volume;
+            }
+            None => self.current = Some(bar.clone()),
+        }
+    }
+
+    fn close(&mut self) -> Option<Bar> {
+        self.count = 0;
+        self.current.take()
+    }
+
+    fn push_renko(
+        &mut self,
+        instrument: &str,
+        timestamp: DateTime<Utc>,
+        price: Decimal,
+        size: Decimal,
+    ) -> Vec<Bar> {
+        let mut bricks = Vec::new();
+        let Some((mut last, mut up)) = self.renko else {
+            self.renko = Some((price, None));
+            return bricks;
+        };
+        if size <= Decimal::ZERO {
+            return bricks;
+        }
+
+        loop {
+            // Continuing needs one brick of movement, reversing needs two
+            let (open, close, brick_up) = if up != Some(false) && price >= last + size {
+                (last, last + size, true)
+            } else if up != Some(true) && price <= last - size {
+                (last, last - size, false)
+            } else if up == Some(true) && price <= last - size * Decimal::TWO {
+                (last - size, last - size * Decimal::TWO, false)
+            } else if up == Some(false) && price >= last + size * Decimal::TWO {
+                (last + size, last + size * Decimal::TWO, true)
+            } else {
+                break;
+            };
+
+            bricks.push(Bar {
+                instrument: instrument.to_string(),
+                timestamp,
+                open,
+                high: open.max(close),
+                low: open.min(close),
+                close,
+                volume: std::mem::take(&mut self.renko_volume),
+            });
+            last = close;
+            up = Some(brick_up);
+        }
+
+        self.renko = Some((last, up));
+        bricks
+    }
+}
+
+/// Aggregate ticks into bars, including the final partial bar.
+pub fn aggregate_ticks(ticks: &[Tick], spec: BarSpec) -> Vec<Bar> {
+    let mut builder = BarBuilder::new(spec);
+    let mut bars: Vec<Bar> = ticks.iter().flat_map(|t| builder.push_tick(t)).collect();
+    bars.extend(builder.flush());
+    bars
+}
+
+/// Aggregate lower-timeframe bars into bars, including the final partial bar.
+pub fn aggregate_bars(bars: &[Bar], spec: BarSpec) -> Vec<Bar> {
+    let mut builder = BarBuilder::new(spec);
+    let mut out: Vec<Bar> = bars.iter().flat_map(|b| builder.push_bar(b)).collect();
+    out.extend(builder.flush());
+    out
+}
+
+/// Guess the timeframe of a bar series from the smallest gap between bars.
+pub fn infer_timeframe(bars: &[Bar]) -> Option<Timeframe> {
+    let gap = bars
+        .windows(2)
+        .map(|w| (w[1].timestamp - w[0].timestamp).num_seconds())
+        .filter(|s| *s > 0)
+        .min()?;
+    let units = |unit: i64| u32::try_from(gap / unit).ok();
+    Some(match gap {
+        g if g >= 28 * 86_400 => Timeframe::Monthly,
+        g if g % (7 * 86_400) == 0 => Timeframe::Weekly,
+        g if g % 86_400 == 0 => Timeframe::Daily,
+        g if g % 3_600 == 0 => Timeframe::Hour(units(3_600)?),
+        g if g % 60 == 0 => Timeframe::Minute(units(60)?),
+        _ => Timeframe::Second(units(1)?),
+    })
+}
+
+/// Convert stored bars to the requested timeframe.
+///
+/// Returns the bars unchanged if they are already at `timeframe`, aggregates
+/// them if `timeframe` is a coarser multiple, and returns `None` if it can't
+/// be built from them.
+pub fn resample(bars: Vec<Bar>, timeframe: Timeframe) -> Option<Vec<Bar>> {
+    let Some(source) = infer_timeframe(&bars) else {
+        // Zero or one bar: nothing to aggregate
+        return Some(bars);
+    };
+    if source == timeframe {
+        Some(bars)
+    } else if timeframe.builds_from(source) {
+        Some(aggregate_bars(&bars, BarSpec::Time(timeframe)))
+    } else {
+        None
+    }
+}
+
+/// Build one bar per trading session of `calendar`.
+///
+/// Unlike [`Timeframe::Daily`] bars, which split at midnight UTC, sessions
+/// follow the exchange: an evening open counts towards the next day and early
+/// closes end the day early. Each bar is dated at midnight UTC of its trading
+/// date. Input bars while the market is closed are dropped.
+pub fn aggregate_sessions(bars: &[Bar], calendar: &TradingCalendar) -> Vec<Bar> {
+    let mut sessions: Vec<Bar> = Vec::new();
+    for bar in bars.iter().filter(|b| calendar.is_open(b.timestamp)) {
+        let day = calendar
+            .session_date(bar.timestamp)
+            .and_time(NaiveTime::MIN)
+            .and_utc();
+        match sessions.last_mut() {
+            Some(current) if current.timestamp == day => {
+                current.high = current.high.max(bar.high);
+                current.low = current.low.min(bar.low);
+                current.close = bar.close;
+                current.volume += bar.volume;
+            }
+            _ => sessions.push(Bar {
+                timestamp: day,
+                ..bar.clone()
+            }),
+        }
+    }
+    sessions
+}
+
+/// The distinct prices a bar is assumed to have visited, in order, with its
+/// volume on the close.
+fn price_path(bar: &Bar) -> Vec<(Decimal, Decimal)> {
+    let (first, second) = if bar.close >= bar.open {
+        (bar.low, bar.high)
+    } else {
+        (bar.high, bar.low)
+    };
+    let mut path: Vec<(Decimal, Decimal)> = Vec::with_capacity(4);
+    for price in [bar.open, first, second, bar.close] {
+        if path.last().map(|(p, _)| *p) != Some(price) {
+            path.push((price, Decimal::ZERO));
+        }
+    }
+    if let Some(last) = path.last_mut() {
+        last.1 = bar.volume;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn tick(second: i64, last: Decimal, volume: Decimal) -> Tick {
+        Tick {
+            instrument: "ES".to_string(),
+            timestamp: "2024-01-02T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+                + chrono::Duration::seconds(second),
+            bid: last,
+            ask: last,
+            last,
+            volume,
+        }
+    }
+
+    fn minute_bar(minute: i64, open: Decimal, close: Decimal) -> Bar {
+        Bar {
+            instrument: "ES".to_string(),
+            timestamp: "2024-01-02T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+                + chrono::Duration::minutes(minute),
+            open,
+            high: open.max(close) + dec!(1),
+            low: open.min(close) - dec!(1),
+            close,
+            volume: dec!(100),
+        }
+    }
+
+    #[test]
+    fn test_time_bars_from_ticks() {
+        let ticks = vec![
+            tick(0, dec!(4700), dec!(1)),
+            tick(20, dec!(4702), dec!(2)),
+            tick(59, dec!(4699), dec!(1)),
+            tick(61, dec!(4701), dec!(3)),
+        ];
+        let bars = aggregate_ticks(&ticks, BarSpec::Time(Timeframe::Minute(1)));
+
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0].open, dec!(4700));
+        assert_eq!(bars[0].high, dec!(4702));
+        assert_eq!(bars[0].low, dec!(4699));
+        assert_eq!(bars[0].close, dec!(4699));
+        assert_eq!(bars[0].volume, dec!(4));
+        assert_eq!(
+            bars[1].timestamp,
+            ticks[3].timestamp - chrono::Duration::seconds(1)
+        );
+    }
+
+    #[test]
+    fn test_streaming_time_bar_waits_for_period_end() {
+        let mut builder = BarBuilder::new(BarSpec::Time(Timeframe::Minute(5)));
+        for minute in 0..4 {
+            assert!(builder
+                .push_bar(&minute_bar(minute, dec!(4700), dec!(4701)))
+                .is_empty());
+        }
+        let period_end = minute_bar(5, dec!(0), dec!(0)).timestamp;
+        assert!(builder
+            .poll(period_end - chrono::Duration::seconds(1))
+            .is_none());
+
+        let five = builder.push_bar(&minute_bar(5, dec!(4701), dec!(4702)));
+        assert_eq!(five.len(), 1);
+        assert_eq!(five[0].volume, dec!(400));
+        assert!(builder
+            .poll(period_end + chrono::Duration::minutes(5))
+            .is_some());
+    }
+
+    #[test]
+    fn test_gap_closes_incomplete_period() {
+        let mut builder = BarBuilder::new(BarSpec::Time(Timeframe::Hour(1)));
+        // 14:30 and 14:31, then nothing until 17:15
+        assert!(builder
+            .push_bar(&minute_bar(0, dec!(4700), dec!(4705)))
+            .is_empty());
+        assert!(builder
+            .push_bar(&minute_bar(1, dec!(4705), dec!(4703)))
+            .is_empty());
+
+        let closed = builder.push_bar(&minute_bar(165, dec!(4710), dec!(4712)));
+        // Only the part-filled 14:00 hour closes; the empty hours are skipped
+        assert_eq!(closed.len(), 1);
+        assert_eq!(
+            closed[0].timestamp,
+            "2024-01-02T14:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(closed[0].open, dec!(4700));
+        assert_eq!(closed[0].close, dec!(4703));
+        assert_eq!(closed[0].volume, dec!(200));
+
+        // The 17:00 hour is still open until 18:00
+        let six_pm = "2024-01-02T18:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert!(builder
+            .poll(six_pm - chrono::Duration::seconds(1))
+            .is_none());
+        assert_eq!(builder.poll(six_pm).unwrap().close, dec!(4712));
+    }
+
+    #[test]
+    fn test_tick_count_and_volume_bars() {
+        let ticks: Vec<Tick> = (0..5).map(|i| tick(i, dec!(4700), dec!(2))).collect();
+
+        let by_count = aggregate_ticks(&ticks, BarSpec::TickCount(2));
+        assert_eq!(by_count.len(), 3);
+        assert_eq!(by_count[2].volume, dec!(2));
+
+        let by_volume = aggregate_ticks(&ticks, BarSpec::Volume(dec!(5)));
+        assert_eq!(by_volume.len(), 2);
+        assert_eq!(by_volume[0].volume, dec!(6));
+    }
+
+    #[test]
+    fn test_range_bars() {
+        let prices = [dec!(4700), dec!(4701), dec!(4702), dec!(4701), dec!(4699)];
+        let ticks: Vec<Tick> = prices
+            .iter()
+            .enumerate()
+            .map(|(i, p)| tick(i as i64, *p, dec!(1)))
+            .collect();
+
+        let bars = aggregate_ticks(&ticks, BarSpec::Range(dec!(2)));
+        assert_eq!(bars.len(), 2);
+        assert_eq!((bars[0].low, bars[0].high), (dec!(4700), dec!(4702)));
+        assert_eq!((bars[1].open, bars[1].close), (dec!(4701), dec!(4699)));
+    }
+
+    #[test]
+    fn test_renko_needs_two_bricks_to_reverse() {
+        let prices = [dec!(4700), dec!(4704), dec!(4701), dec!(4699), dec!(4695)];
+        let ticks: Vec<Tick> = prices
+            .iter()
+            .enumerate()
+            .map(|(i, p)| tick(i as i64, *p, dec!(1)))
+            .collect();
+
+        let bricks = aggregate_ticks(&ticks, BarSpec::Renko(dec!(2)));
+        let closes: Vec<Decimal> = bricks.iter().map(|b| b.close).collect();
+        // Up to 4704, 4701 isn't a reversal yet, 4699 reverses to 4700, 4695 adds two more
+        assert_eq!(
+            closes,
+            vec![dec!(4702), dec!(4704), dec!(4700), dec!(4698), dec!(4696)]
+        );
+    }
+
+    #[test]
+    fn test_resample_minutes_to_five_minutes() {
+        let bars: Vec<Bar> = (0..10)
+            .map(|m| minute_bar(m, dec!(4700), dec!(4701)))
+            .collect();
+
+        assert_eq!(infer_timeframe(&bars), Some(Timeframe::Minute(1)));
+        let five = resample(bars.clone(), Timeframe::Minute(5)).unwrap();
+        assert_eq!(five.len(), 2);
+        assert_eq!(
+            resample(bars.clone(), Timeframe::Minute(1)).unwrap().len(),
+            10
+        );
+        assert!(resample(bars, Timeframe::Second(30)).is_none());
+        assert!(!Timeframe::Minute(7).builds_from(Timeframe::Minute(5)));
+    }
+
+    #[test]
+    fn test_sessions_follow_the_exchange_calendar() {
+        // Tuesday 14:30 UTC, the 22:30 maintenance break (16:00-17:00 CST),
+        // then Tuesday evening and early Wednesday, which both trade for
+        // Wednesday
+        let bars: Vec<Bar> = [0, 480, 540, 660]
+            .into_iter()
+            .map(|m| minute_bar(m, dec!(4700), dec!(4701)))
+            .collect();
+
+        let sessions = aggregate_sessions(&bars, &TradingCalendar::cme_equity());
+        assert_eq!(
+            sessions
+                .iter()
+                .map(|b| (b.timestamp.to_rfc3339(), b.volume))
+                .collect::<Vec<_>>(),
+            vec![
+                ("2024-01-02T00:00:00+00:00".to_string(), dec!(100)),
+                ("2024-01-03T00:00:00+00:00".to_string(), dec!(200)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! Binance spot klines through the public REST API.
+//!
+//! No API key is needed. Requests are rate limited locally, and a `429` or
+//! `418` response is retried after the delay Binance asks for.
+
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use propbot_core::{Bar, DataError, HistoricalDataApi, Timeframe};
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::download::RateLimiter;
+
+/// Production REST endpoint.
+pub const BINANCE_BASE_URL: &str = "https://api.binance.com";
+
+/// Most klines Binance returns per request.
+const PAGE_SIZE: usize = 1000;
+
+/// Times a rate-limited request is retried before giving up.
+const MAX_RETRIES: u32 = 5;
+
+/// Downloads bars from Binance's `/api/v3/klines` endpoint.
+pub struct BinanceApi {
+    base_url: String,
+    client: reqwest::Client,
+    limiter: RateLimiter,
+}
+
+impl BinanceApi {
+    pub fn new() -> Self {
+        Self::with_base_url(BINANCE_BASE_URL)
+    }
+
+    /// Use another endpoint, such as the testnet or a mock server.
+    pub fn with_base_url(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+            // Klines cost 2 of the 6000 request weight allowed per minute
+            limiter: RateLimiter::new(Duration::from_millis(100)),
+        }
+    }
+
+    /// Set the minimum time between requests.
+    pub fn with_rate_limit(mut self, interval: Duration) -> Self {
+        self.limiter = RateLimiter::new(interval);
+        self
+    }
+
+    /// GET `path`, retrying when rate limited.
+    async fn get(&self, path: &str, query: &[(&str, String)]) -> Result<Value, DataError> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut attempt = 0;
+        loop {
+            self.limiter.wait().await;
+            let response = self
+                .client
+                .get(&url)
+                .query(query)
+                .send()
+                .await
+                .map_err(|e| DataError::ApiError(format!("Binance request failed: {}", e)))?;
+
+            let status = response.status();
+            if (status.as_u16() == 429 || status.as_u16() == 418) && attempt < MAX_RETRIES {
+                let delay = response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse().ok())
+                    .map(Duration::from_secs)
+                    .unwrap_or_else(|| Duration::from_secs(1 << attempt));
+                tracing::warn!(status = %status, delay = ?delay, "Binance rate limit hit, backing off");
+                self.limiter.back_off(delay).await;
+                attempt += 1;
+                continue;
+            }
+            if !status.is_success() {
+                let body = response.text().await.unwrap_or_default();
+                return Err(DataError::ApiError(format!(
+                    "Binance returned {}: {}",
+                    status, body
+                )));
+            }
+            return response
+                .json()
+                .await
+                .map_err(|e| DataError::ApiError(format!("Invalid Binance response: {}", e)));
+        }
+    }
+}
+
+impl Default for BinanceApi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl HistoricalDataApi for BinanceApi {
+    fn name(&self) -> &str {
+        "binance"
+    }
+
+    async fn fetch_bars(
+        &self,
+        instrument: &str,
+        timeframe: Timeframe,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Bar>, DataError> {
+        let interval = interval(timeframe)
+            .ok_or_else(|| DataError::ApiError(format!("Binance has no {} klines", timeframe)))?;
+        let query = [
+            ("symbol", instrument.to_string()),
+            ("interval", interval.to_string()),
+            ("startTime", start.timestamp_millis().to_string()),
+            ("endTime", end.timestamp_millis().to_string()),
+            ("limit", PAGE_SIZE.to_string()),
+        ];
+        let body = self.get("/api/v3/klines", &query).await?;
+        let rows = body
+            .as_array()
+            .ok_or_else(|| DataError::ApiError("Binance klines are not an array".into()))?;
+        rows.iter()
+            .map(|row| parse_kline(instrument, row))
+            .collect()
+    }
+}
+
+/// Binance's name for a kline interval.
+fn interval(timeframe: Timeframe) -> Option<&'static str> {
+    Some(match timeframe {
+        Timeframe::Second(1) => "1s",
+        Timeframe::Minute(1) => "1m",
+        Timeframe::Minute(3) => "3m",
+        Timeframe::Minute(5) => "5m",
+        Timeframe::Minute(15) => "15m",
+        Timeframe::Minute(30) => "30m",
+        Timeframe::Hour(1) => "1h",
+        Timeframe::Hour(2) => "2h",
+        Timeframe::Hour(4) => "4h",
+        Timeframe::Hour(6) => "6h",
+        Timeframe::Hour(8) => "8h",
+        Timeframe::Hour(12) => "12h",
+        Timeframe::Daily => "1d",
+        Timeframe::Weekly => "1w",
+        Timeframe::Monthly => "1M",
+        _ => return None,
+    })
+}
+
+/// Parse one kline: `[open_time, open, high, low, close, volume, close_time, ...]`.
+fn parse_kline(instrument: &str, row: &Value) -> Result<Bar, DataError> {
+    let invalid = || DataError::ApiError(format!("Invalid Binance kline: {}", row));
+    let fields = row
+        .as_array()
+        .filter(|f| f.len() >= 6)
+        .ok_or_else(invalid)?;
+    let price = |i: usize| -> Result<Decimal, DataError> {
+        fields[i]
+            .as_str()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(invalid)
+    };
+    let timestamp = fields[0]
+        .as_i64()
+        .and_then(|ms| Utc.timestamp_millis_opt(ms).single())
+        .ok_or_else(invalid)?;
+    Ok(Bar {
+        instrument: instrument.to_string(),
+        timestamp,
+        open: price(1)?,
+        high: price(2)?,
+        low: price(3)?,
+        close: price(4)?,
+        volume: price(5)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::bar_pages;
+    use axum::extract::{Query, State};
+    use axum::http::{header, StatusCode};
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use futures_util::TryStreamExt;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const START_MS: i64 = 1_704_067_200_000; // 2024-01-01T00:00:00Z
+    const MINUTE_MS: i64 = 60_000;
+    const BARS: i64 = 2_500;
+
+    #[derive(Default)]
+    struct Mock {
+        requests: AtomicUsize,
+    }
+
+    /// Serves `BARS` one-minute klines for BTCUSDT, rate limiting the first request.
+    async fn klines(
+        State(mock): State<Arc<Mock>>,
+        Query(query): Query<HashMap<String, String>>,
+    ) -> Response {
+        if mock.requests.fetch_add(1, Ordering::SeqCst) == 0 {
+            return (StatusCode::TOO_MANY_REQUESTS, [(header::RETRY_AFTER, "0")]).into_response();
+        }
+        if query["symbol"] != "BTCUSDT" {
+            let error = serde_json::json!({"code": -1121, "msg": "Invalid symbol."});
+            return (StatusCode::BAD_REQUEST, Json(error)).into_response();
+        }
+        let param = |name: &str| query[name].parse::<i64>().unwrap();
+        let (start, end, limit) = (param("startTime"), param("endTime"), param("limit"));
+        let rows: Vec<Value> = (0..BARS)
+            .map(|i| START_MS + i * MINUTE_MS)
+            .filter(|t| *t >= start && *t <= end)
+            .take(limit as usize)
+            .map(|t| {
+                let price = 42_000 + (t - START_MS) / MINUTE_MS;
+                serde_json::json!([
+                    t,
+                    format!("{}.00", price),
+                    format!("{}.50", price),
+                    format!("{}.00", price - 1),
+                    format!("{}.25", price),
+                    "1.5",
+                    t + MINUTE_MS - 1,
+                    "0",
+                    10,
+                    "0",
+                    "0",
+                    "0"
+                ])
+            })
+            .collect();
+        Json(rows).into_response()
+    }
+
+    async fn mock_server() -> (String, Arc<Mock>) {
+        let mock = Arc::new(Mock::default());
+        let app = Router::new()
+            .route("/api/v3/klines", get(klines))
+            .with_state(mock.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, mock)
+    }
+
+    #[tokio::test]
+    async fn test_bar_pages_downloads_all_klines() {
+        let (url, mock) = mock_server().await;
+        let api = BinanceApi::with_base_url(url).with_rate_limit(Duration::ZERO);
+        let start = Utc.timestamp_millis_opt(START_MS).unwrap();
+        let end = start + chrono::Duration::days(7);
+
+        let pages: Vec<Vec<Bar>> = bar_pages(&api, "BTCUSDT", Timeframe::Minute(1), start, end)
+            .try_collect()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            pages.iter().map(Vec::len).collect::<Vec<_>>(),
+            [1000, 1000, 500]
+        );
+        let bars: Vec<Bar> = pages.into_iter().flatten().collect();
+        assert!(bars
+            .windows(2)
+            .all(|w| w[1].timestamp - w[0].timestamp == chrono::Duration::minutes(1)));
+        assert_eq!(bars[0].open, Decimal::from(42_000));
+        assert_eq!(bars[BARS as usize - 1].close.to_string(), "44499.25");
+        // One rate-limited request, three pages and an empty page
+        assert_eq!(mock.requests.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_bars_reports_api_errors() {
+        let (url, _mock) = mock_server().await;
+        let api = BinanceApi::with_base_url(url).with_rate_limit(Duration::ZERO);
+        let start = Utc.timestamp_millis_opt(START_MS).unwrap();
+        let end = start + chrono::Duration::days(1);
+
+        let err = api
+            .fetch_bars("NOPE", Timeframe::Minute(1), start, end)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Invalid symbol"), "{}", err);
+
+        let err = api
+            .fetch_bars("BTCUSDT", Timeframe::Minute(2), start, end)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DataError::ApiError(_)));
+    }
+}
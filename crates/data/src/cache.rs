@@ -0,0 +1,736 @@
+//! Binary columnar cache for bars and ticks.
+//!
+//! Parsing CSV into `Decimal`s dominates load time for large histories, so
+//! imported data can be written once to a compact file and read back without
+//! any text parsing. Each file holds one instrument's rows sorted by time, and
+//! bar files a single timeframe:
+//!
+//! ```text
+//! magic "PBCACHE\0" | version u16 | kind u8 | reserved u8
+//! instrument length u32 | instrument UTF-8 | row count u64
+//! timestamp column: i64 nanoseconds since the epoch, little-endian
+//! one column per field: 16-byte `Decimal::serialize()` values
+//! ```
+//!
+//! Because columns are fixed-width and timestamps sorted, a date range is
+//! located by binary search on the timestamp column and only those rows are
+//! read from each column.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use propbot_core::{Bar, BarStream, DataError, DataProvider, Tick, TickStream, Timeframe};
+use rust_decimal::Decimal;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::aggregation::{aggregate_ticks, BarSpec};
+use crate::streaming::{aggregate_tick_stream, failed, resample_stream};
+
+const MAGIC: &[u8; 8] = b"PBCACHE\0";
+const VERSION: u16 = 1;
+const KIND_BARS: u8 = 0;
+const KIND_TICKS: u8 = 1;
+const BAR_COLUMNS: usize = 5;
+const TICK_COLUMNS: usize = 4;
+/// Rows read per chunk when streaming.
+const STREAM_CHUNK_ROWS: u64 = 65_536;
+
+/// Write bars for a single instrument to a cache file, replacing it.
+pub fn write_bars(path: &Path, bars: &[Bar]) -> Result<(), DataError> {
+    let mut bars = bars.to_vec();
+    bars.sort_by_key(|b| b.timestamp);
+    let instrument = single_instrument(bars.iter().map(|b| b.instrument.as_str()))?;
+    let columns: [fn(&Bar) -> Decimal; BAR_COLUMNS] =
+        [|b| b.open, |b| b.high, |b| b.low, |b| b.close, |b| b.volume];
+    write_file(
+        path,
+        KIND_BARS,
+        &instrument,
+        &bars,
+        |b| b.timestamp,
+        &columns,
+    )
+}
+
+/// Write ticks for a single instrument to a cache file, replacing it.
+pub fn write_ticks(path: &Path, ticks: &[Tick]) -> Result<(), DataError> {
+    let mut ticks = ticks.to_vec();
+    ticks.sort_by_key(|t| t.timestamp);
+    let instrument = single_instrument(ticks.iter().map(|t| t.instrument.as_str()))?;
+    let columns: [fn(&Tick) -> Decimal; TICK_COLUMNS] =
+        [|t| t.bid, |t| t.ask, |t| t.last, |t| t.volume];
+    write_file(
+        path,
+        KIND_TICKS,
+        &instrument,
+        &ticks,
+        |t| t.timestamp,
+        &columns,
+    )
+}
+
+/// Merge bars into a cache file, creating it if missing.
+///
+/// As with the database, a bar replaces the cached bar with the same
+/// timestamp and every other cached bar is kept, so importing one month after
+/// another leaves both in the cache.
+pub fn merge_bars(path: &Path, bars: &[Bar]) -> Result<(), DataError> {
+    if !path.exists() {
+        return write_bars(path, bars);
+    }
+    let incoming: HashSet<_> = bars.iter().map(|b| b.timestamp).collect();
+    let mut merged = read_bars(path, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC)?;
+    merged.retain(|b| !incoming.contains(&b.timestamp));
+    merged.extend_from_slice(bars);
+    write_bars(path, &merged)
+}
+
+/// Merge ticks into a cache file, creating it if missing.
+///
+/// Ticks sharing a timestamp can't be matched one for one, so the cached
+/// ticks within the new ticks' time span are replaced by them and the rest
+/// are kept.
+pub fn merge_ticks(path: &Path, ticks: &[Tick]) -> Result<(), DataError> {
+    if !path.exists() {
+        return write_ticks(path, ticks);
+    }
+    let span = ticks.iter().map(|t| t.timestamp);
+    let (Some(first), Some(last)) = (span.clone().min(), span.max()) else {
+        return Ok(());
+    };
+    let mut merged = read_ticks(path, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC)?;
+    merged.retain(|t| t.timestamp < first || t.timestamp > last);
+    merged.extend_from_slice(ticks);
+    write_ticks(path, &merged)
+}
+
+/// Read the bars between `start` and `end` (inclusive) from a cache file.
+pub fn read_bars(
+    path: &Path,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<Bar>, DataError> {
+    read_range(path, KIND_BARS, BAR_COLUMNS, start, end).map(bars_from)
+}
+
+/// Read the ticks between `start` and `end` (inclusive) from a cache file.
+pub fn read_ticks(
+    path: &Path,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<Tick>, DataError> {
+    read_range(path, KIND_TICKS, TICK_COLUMNS, start, end).map(ticks_from)
+}
+
+/// Stream the bars between `start` and `end` (inclusive) from a cache file,
+/// reading a chunk of rows at a time.
+pub fn stream_bars(
+    path: &Path,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<BarStream<'static>, DataError> {
+    stream_range(path, KIND_BARS, BAR_COLUMNS, start, end, bars_from)
+}
+
+/// Stream the ticks between `start` and `end` (inclusive) from a cache file.
+pub fn stream_ticks(
+    path: &Path,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<TickStream<'static>, DataError> {
+    stream_range(path, KIND_TICKS, TICK_COLUMNS, start, end, ticks_from)
+}
+
+fn bars_from(columns: Columns) -> Vec<Bar> {
+    let Columns {
+        instrument,
+        timestamps,
+        values,
+    } = columns;
+    timestamps
+        .into_iter()
+        .enumerate()
+        .map(|(i, timestamp)| Bar {
+            instrument: instrument.clone(),
+            timestamp,
+            open: values[0][i],
+            high: values[1][i],
+            low: values[2][i],
+            close: values[3][i],
+            volume: values[4][i],
+        })
+        .collect()
+}
+
+fn ticks_from(columns: Columns) -> Vec<Tick> {
+    let Columns {
+        instrument,
+        timestamps,
+        values,
+    } = columns;
+    timestamps
+        .into_iter()
+        .enumerate()
+        .map(|(i, timestamp)| Tick {
+            instrument: instrument.clone(),
+            timestamp,
+            bid: values[0][i],
+            ask: values[1][i],
+            last: values[2][i],
+            volume: values[3][i],
+        })
+        .collect()
+}
+
+/// Cache file for an instrument's bars of one timeframe within `directory`.
+pub fn bars_path(directory: &Path, instrument: &str, timeframe: Timeframe) -> PathBuf {
+    directory.join(format!("{}.{}.bars.pbc", instrument, timeframe))
+}
+
+/// Cache file for an instrument's ticks within `directory`.
+pub fn ticks_path(directory: &Path, instrument: &str) -> PathBuf {
+    directory.join(format!("{}.ticks.pbc", instrument))
+}
+
+/// The instrument and timeframe of a bar cache file name.
+fn parse_bars_file_name(name: &str) -> Option<(&str, Timeframe)> {
+    let (instrument, timeframe) = name.strip_suffix(".bars.pbc")?.rsplit_once('.')?;
+    Some((instrument, timeframe.parse().ok()?))
+}
+
+/// A data provider backed by columnar cache files.
+///
+/// Reads `{instrument}.{timeframe}.bars.pbc` and `{instrument}.ticks.pbc` from
+/// a directory, loading only the requested date range. Like
+/// [`PostgresDataProvider`](crate::PostgresDataProvider), it reads the
+/// requested timeframe if cached, otherwise the coarsest cached one it builds
+/// from.
+pub struct CachedDataProvider {
+    pub directory: PathBuf,
+}
+
+impl CachedDataProvider {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// Path of the cached bars to build `timeframe` from, if any.
+    fn source_bars_path(
+        &self,
+        instrument: &str,
+        timeframe: Timeframe,
+    ) -> Result<Option<PathBuf>, DataError> {
+        let entries = match std::fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(DataError::IoError(e)),
+        };
+        let mut cached = Vec::new();
+        for entry in entries {
+            let name = entry.map_err(DataError::IoError)?.file_name();
+            match parse_bars_file_name(&name.to_string_lossy()) {
+                Some((cached_instrument, tf)) if cached_instrument == instrument => cached.push(tf),
+                _ => {}
+            }
+        }
+        Ok(crate::pick_source_timeframe(&cached, timeframe)
+            .map(|source| bars_path(&self.directory, instrument, source)))
+    }
+
+    fn missing_bars(&self, instrument: &str, timeframe: Timeframe) -> DataError {
+        DataError::NotFound(format!(
+            "No cached bars for {} that build {} bars in {}",
+            instrument,
+            timeframe,
+            self.directory.display()
+        ))
+    }
+}
+
+#[async_trait]
+impl DataProvider for CachedDataProvider {
+    async fn load_bars(
+        &self,
+        instrument: &str,
+        timeframe: Timeframe,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Bar>, DataError> {
+        let Some(path) = self.source_bars_path(instrument, timeframe)? else {
+            // Fall back to building bars from cached ticks
+            if ticks_path(&self.directory, instrument).exists() {
+                let ticks = self.load_ticks(instrument, start, end).await?;
+                return Ok(aggregate_ticks(&ticks, BarSpec::Time(timeframe)));
+            }
+            return Err(self.missing_bars(instrument, timeframe));
+        };
+        let bars = read_bars(&path, start, end)?;
+        crate::resample_or_err(bars, instrument, timeframe)
+    }
+
+    async fn load_ticks(
+        &self,
+        instrument: &str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Tick>, DataError> {
+        let path = ticks_path(&self.directory, instrument);
+        if !path.exists() {
+            return Err(DataError::NotFound(format!(
+                "Tick cache file not found: {}",
+                path.display()
+            )));
+        }
+        read_ticks(&path, start, end)
+    }
+
+    async fn available_instruments(&self) -> Result<Vec<String>, DataError> {
+        let mut instruments = Vec::new();
+        let entries = std::fs::read_dir(&self.directory).map_err(DataError::IoError)?;
+        for entry in entries {
+            let name = entry.map_err(DataError::IoError)?.file_name();
+            let name = name.to_string_lossy();
+            if let Some(instrument) = parse_bars_file_name(&name)
+                .map(|(instrument, _)| instrument)
+                .or_else(|| name.strip_suffix(".ticks.pbc"))
+            {
+                instruments.push(instrument.to_string());
+            }
+        }
+        instruments.sort();
+        instruments.dedup();
+        Ok(instruments)
+    }
+
+    /// Reads the cache a chunk of rows at a time.
+    fn stream_bars<'a>(
+        &'a self,
+        instrument: &'a str,
+        timeframe: Timeframe,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BarStream<'a> {
+        let path = match self.source_bars_path(instrument, timeframe) {
+            Ok(Some(path)) => path,
+            Ok(None) => {
+                // Fall back to building bars from cached ticks
+                if ticks_path(&self.directory, instrument).exists() {
+                    let ticks = self.stream_ticks(instrument, start, end);
+                    return aggregate_tick_stream(ticks, BarSpec::Time(timeframe));
+                }
+                return failed(self.missing_bars(instrument, timeframe));
+            }
+            Err(e) => return failed(e),
+        };
+        match stream_bars(&path, start, end) {
+            Ok(bars) => resample_stream(bars, instrument, timeframe),
+            Err(e) => failed(e),
+        }
+    }
+
+    fn stream_ticks<'a>(
+        &'a self,
+        instrument: &'a str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> TickStream<'a> {
+        let path = ticks_path(&self.directory, instrument);
+        if !path.exists() {
+            return failed(DataError::NotFound(format!(
+                "Tick cache file not found: {}",
+                path.display()
+            )));
+        }
+        stream_ticks(&path, start, end).unwrap_or_else(failed)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Internal helpers
+// ---------------------------------------------------------------------------
+
+fn single_instrument<'a>(mut names: impl Iterator<Item = &'a str>) -> Result<String, DataError> {
+    let first = names.next().unwrap_or_default();
+    if names.any(|n| n != first) {
+        return Err(DataError::ParseError(
+            "A cache file holds a single instrument".into(),
+        ));
+    }
+    Ok(first.to_string())
+}
+
+fn write_file<T>(
+    path: &Path,
+    kind: u8,
+    instrument: &str,
+    rows: &[T],
+    timestamp: fn(&T) -> DateTime<Utc>,
+    columns: &[fn(&T) -> Decimal],
+) -> Result<(), DataError> {
+    // Write beside the target and rename, so readers never see a partial file
+    let tmp = path.with_extension("pbc.tmp");
+    let mut out = BufWriter::new(File::create(&tmp)?);
+
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&[kind, 0])?;
+    out.write_all(&(instrument.len() as u32).to_le_bytes())?;
+    out.write_all(instrument.as_bytes())?;
+    out.write_all(&(rows.len() as u64).to_le_bytes())?;
+
+    for row in rows {
+        let ts = timestamp(row);
+        let nanos = ts.timestamp_nanos_opt().ok_or_else(|| {
+            DataError::ParseError(format!("Timestamp {} is outside the cacheable range", ts))
+        })?;
+        out.write_all(&nanos.to_le_bytes())?;
+    }
+    for column in columns {
+        for row in rows {
+            out.write_all(&column(row).serialize())?;
+        }
+    }
+    out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+struct Header {
+    instrument: String,
+    rows: u64,
+    /// Byte offset of the timestamp column.
+    data_start: u64,
+}
+
+fn read_header(file: &mut File, kind: u8) -> Result<Header, DataError> {
+    let invalid = |what: &str| DataError::ParseError(format!("Invalid cache file: {}", what));
+
+    let mut fixed = [0u8; 16];
+    file.read_exact(&mut fixed)?;
+    if &fixed[..8] != MAGIC {
+        return Err(invalid("bad magic"));
+    }
+    if u16::from_le_bytes([fixed[8], fixed[9]]) != VERSION {
+        return Err(invalid("unsupported version"));
+    }
+    if fixed[10] != kind {
+        return Err(invalid(if kind == KIND_BARS {
+            "contains ticks, not bars"
+        } else {
+            "contains bars, not ticks"
+        }));
+    }
+    let name_len = u32::from_le_bytes(fixed[12..16].try_into().unwrap()) as usize;
+    let mut name = vec![0u8; name_len];
+    file.read_exact(&mut name)?;
+    let instrument = String::from_utf8(name).map_err(|_| invalid("bad instrument name"))?;
+    let rows = read_u64(file)?;
+
+    Ok(Header {
+        instrument,
+        rows,
+        data_start: 16 + name_len as u64 + 8,
+    })
+}
+
+fn read_u64(file: &mut File) -> Result<u64, DataError> {
+    let mut buf = [0u8; 8];
+    file.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Rows read from a cache file, column by column.
+struct Columns {
+    instrument: String,
+    timestamps: Vec<DateTime<Utc>>,
+    values: Vec<Vec<Decimal>>,
+}
+
+/// An open cache file positioned over the rows of a date range.
+struct RangeReader {
+    file: File,
+    header: Header,
+    column_count: usize,
+    /// Next row to read and one past the last row in range.
+    next: u64,
+    end: u64,
+}
+
+impl RangeReader {
+    fn open(
+        path: &Path,
+        kind: u8,
+        column_count: usize,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Self, DataError> {
+        let mut file = File::open(path)?;
+        let header = read_header(&mut file, kind)?;
+
+        let expected = header.data_start + header.rows * (8 + 16 * column_count as u64);
+        if file.metadata()?.len() != expected {
+            return Err(DataError::ParseError(format!(
+                "Invalid cache file: {} is truncated",
+                path.display()
+            )));
+        }
+
+        let to_nanos = |t: DateTime<Utc>| t.timestamp_nanos_opt();
+        let start_nanos = to_nanos(start).unwrap_or(i64::MIN);
+        let end_nanos = to_nanos(end).unwrap_or(i64::MAX);
+        let first = lower_bound(&mut file, &header, |ts| ts >= start_nanos)?;
+        let last = lower_bound(&mut file, &header, |ts| ts > end_nanos)?;
+
+        Ok(Self {
+            file,
+            header,
+            column_count,
+            next: first,
+            end: last.max(first),
+        })
+    }
+
+    fn is_done(&self) -> bool {
+        self.next >= self.end
+    }
+
+    /// Read up to `max_rows` of the remaining rows.
+    fn read_chunk(&mut self, max_rows: u64) -> Result<Columns, DataError> {
+        let (file, header) = (&mut self.file, &self.header);
+        let first = self.next;
+        let count = (self.end - first).min(max_rows) as usize;
+
+        file.seek(SeekFrom::Start(header.data_start + first * 8))?;
+        let mut buf = vec![0u8; count * 8];
+        file.read_exact(&mut buf)?;
+        let timestamps = buf
+            .chunks_exact(8)
+            .map(|c| DateTime::from_timestamp_nanos(i64::from_le_bytes(c.try_into().unwrap())))
+            .collect();
+
+        let columns_start = header.data_start + header.rows * 8;
+        let mut columns = Vec::with_capacity(self.column_count);
+        let mut buf = vec![0u8; count * 16];
+        for column in 0..self.column_count as u64 {
+            file.seek(SeekFrom::Start(
+                columns_start + column * header.rows * 16 + first * 16,
+            ))?;
+            file.read_exact(&mut buf)?;
+            columns.push(
+                buf.chunks_exact(16)
+                    .map(|c| Decimal::deserialize(c.try_into().unwrap()))
+                    .collect(),
+            );
+        }
+
+        self.next += count as u64;
+        Ok(Columns {
+            instrument: header.instrument.clone(),
+            timestamps,
+            values: columns,
+        })
+    }
+}
+
+/// Read the timestamps and decimal columns of rows within `[start, end]`.
+fn read_range(
+    path: &Path,
+    kind: u8,
+    column_count: usize,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Columns, DataError> {
+    RangeReader::open(path, kind, column_count, start, end)?.read_chunk(u64::MAX)
+}
+
+/// Stream a range in chunks of [`STREAM_CHUNK_ROWS`], converting each with `rows`.
+fn stream_range<T: Send + 'static>(
+    path: &Path,
+    kind: u8,
+    column_count: usize,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    rows: fn(Columns) -> Vec<T>,
+) -> Result<BoxStream<'static, Result<T, DataError>>, DataError> {
+    let reader = RangeReader::open(path, kind, column_count, start, end)?;
+    Ok(stream::unfold(Some(reader), move |reader| async move {
+        let mut reader = reader.filter(|r| !r.is_done())?;
+        match reader.read_chunk(STREAM_CHUNK_ROWS) {
+            Ok(columns) => {
+                let items: Vec<_> = rows(columns).into_iter().map(Ok).collect();
+                Some((items, Some(reader)))
+            }
+            Err(e) => Some((vec![Err(e)], None)),
+        }
+    })
+    .flat_map(stream::iter)
+    .boxed())
+}
+
+/// Index of the first row whose timestamp satisfies `pred` (which must be
+/// monotonic over the sorted column), or the row count if none does.
+fn lower_bound(
+    file: &mut File,
+    header: &Header,
+    pred: impl Fn(i64) -> bool,
+) -> Result<u64, DataError> {
+    let (mut lo, mut hi) = (0, header.rows);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        file.seek(SeekFrom::Start(header.data_start + mid * 8))?;
+        if pred(read_u64(file)? as i64) {
+            hi = mid;
+        } else {
+            lo = mid + 1;
+        }
+    }
+    Ok(lo)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+
+    fn bars() -> Vec<Bar> {
+        let start: DateTime<Utc> = "2024-01-02T14:30:00Z".parse().unwrap();
+        (0..100)
+            .map(|i| Bar {
+                instrument: "ES".to_string(),
+                timestamp: start + Duration::minutes(i),
+                open: dec!(4700) + Decimal::from(i),
+                high: dec!(4701.25) + Decimal::from(i),
+                low: dec!(4699.5) + Decimal::from(i),
+                close: dec!(4700.75) + Decimal::from(i),
+                volume: Decimal::from(i * 10),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_bars_round_trip_with_range_pushdown() {
+        let path = std::env::temp_dir().join(format!("propbot_cache_{}.pbc", std::process::id()));
+        let bars = bars();
+        write_bars(&path, &bars).unwrap();
+
+        let all = read_bars(&path, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC).unwrap();
+        let some = read_bars(&path, bars[10].timestamp, bars[19].timestamp).unwrap();
+        let none = read_bars(
+            &path,
+            bars[99].timestamp + Duration::minutes(1),
+            DateTime::<Utc>::MAX_UTC,
+        )
+        .unwrap();
+        let wrong_kind = read_ticks(&path, bars[0].timestamp, bars[99].timestamp);
+        let streamed: Vec<Bar> = stream_bars(&path, bars[10].timestamp, bars[19].timestamp)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        std::fs::remove_file(&path).ok();
+
+        let key = |b: &Bar| (b.timestamp, b.open, b.high, b.low, b.close, b.volume);
+        assert!(all.iter().map(key).eq(bars.iter().map(key)));
+        assert!(some.iter().map(key).eq(bars[10..20].iter().map(key)));
+        assert!(streamed.iter().map(key).eq(some.iter().map(key)));
+        assert_eq!(all[0].instrument, "ES");
+        assert!(none.is_empty());
+        assert!(wrong_kind.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_provider_resamples_cached_bars() {
+        let dir = std::env::temp_dir().join(format!("propbot_cache_dir_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_bars(&bars_path(&dir, "ES", Timeframe::Minute(1)), &bars()).unwrap();
+
+        let provider = CachedDataProvider::new(&dir);
+        let hourly = provider
+            .load_bars(
+                "ES",
+                Timeframe::Hour(1),
+                DateTime::<Utc>::MIN_UTC,
+                DateTime::<Utc>::MAX_UTC,
+            )
+            .await
+            .unwrap();
+        let instruments = provider.available_instruments().await.unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(instruments, vec!["ES".to_string()]);
+        // 14:30-15:00, 15:00-16:00 and 16:00-16:10
+        assert_eq!(hourly.len(), 3);
+        assert_eq!(
+            hourly[1].volume,
+            (30..90).map(|i| Decimal::from(i * 10)).sum()
+        );
+    }
+    #[tokio::test]
+    async fn test_merge_keeps_earlier_imports_per_timeframe() {
+        let dir = std::env::temp_dir().join(format!("propbot_cache_merge_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bars = bars();
+        let minute = bars_path(&dir, "ES", Timeframe::Minute(1));
+        merge_bars(&minute, &bars[..60]).unwrap();
+        // The second import overlaps the first by ten bars with new prices
+        let mut later = bars[50..].to_vec();
+        for bar in &mut later {
+            bar.close += dec!(1);
+        }
+        merge_bars(&minute, &later).unwrap();
+        let daily = Bar {
+            timestamp: "2024-01-02T00:00:00Z".parse().unwrap(),
+            ..bars[0].clone()
+        };
+        merge_bars(&bars_path(&dir, "ES", Timeframe::Daily), &[daily]).unwrap();
+
+        let ticks_at = |minutes: std::ops::Range<i64>, last| {
+            minutes
+                .map(|i| Tick {
+                    instrument: "ES".to_string(),
+                    timestamp: bars[0].timestamp + Duration::minutes(i),
+                    bid: last,
+                    ask: last,
+                    last,
+                    volume: dec!(1),
+                })
+                .collect::<Vec<_>>()
+        };
+        let ticks = ticks_path(&dir, "ES");
+        merge_ticks(&ticks, &ticks_at(0..10, dec!(4700))).unwrap();
+        merge_ticks(&ticks, &ticks_at(5..15, dec!(4701))).unwrap();
+
+        let provider = CachedDataProvider::new(&dir);
+        let load = |timeframe| {
+            provider.load_bars(
+                "ES",
+                timeframe,
+                DateTime::<Utc>::MIN_UTC,
+                DateTime::<Utc>::MAX_UTC,
+            )
+        };
+        let minutes = load(Timeframe::Minute(1)).await.unwrap();
+        let days = load(Timeframe::Daily).await.unwrap();
+        let cached_ticks = read_ticks(&ticks, DateTime::<Utc>::MIN_UTC, DateTime::<Utc>::MAX_UTC);
+        let instruments = provider.available_instruments().await.unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(minutes.len(), 100);
+        assert_eq!(minutes[49].close, bars[49].close);
+        assert_eq!(minutes[50].close, bars[50].close + dec!(1));
+        // Daily bars come from the daily cache, not the minute one
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].volume, bars[0].volume);
+        let cached_ticks = cached_ticks.unwrap();
+        assert_eq!(cached_ticks.len(), 15);
+        assert_eq!(cached_ticks[4].last, dec!(4700));
+        assert_eq!(cached_ticks[5].last, dec!(4701));
+        assert_eq!(instruments, vec!["ES".to_string()]);
+    }
+}
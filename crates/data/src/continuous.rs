@@ -0,0 +1,439 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use propbot_core::{Bar, ContractRoll, DataError};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Bars for a single futures contract month (e.g. ESH4).
+#[derive(Debug, Clone)]
+pub struct ContractData {
+    pub symbol: String,
+    pub expiry: NaiveDate,
+    pub bars: Vec<Bar>,
+    /// Daily open interest, if available (needed for [`RollRule::OpenInterestCrossover`]).
+    pub open_interest: BTreeMap<NaiveDate, Decimal>,
+}
+
+impl ContractData {
+    pub fn new(symbol: impl Into<String>, expiry: NaiveDate, bars: Vec<Bar>) -> Self {
+        Self {
+            symbol: symbol.into(),
+            expiry,
+            bars,
+            open_interest: BTreeMap::new(),
+        }
+    }
+}
+
+/// When to move from the front contract to the next one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RollRule {
+    /// A fixed number of calendar days before the front contract expires.
+    DaysBeforeExpiry(u32),
+    /// Once the next contract traded more volume than the front on the previous day.
+    VolumeCrossover,
+    /// Once the next contract's open interest exceeded the front's on the previous day.
+    OpenInterestCrossover,
+}
+
+/// How prices before a roll are adjusted to remove the gap between contracts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdjustmentMethod {
+    /// Raw prices; the series jumps at each roll.
+    #[default]
+    None,
+    /// Shift earlier bars by the price gap (keeps point moves, can go negative).
+    Difference,
+    /// Scale earlier bars by the price ratio (keeps percentage moves).
+    Ratio,
+}
+
+/// A continuous series stitched from contract months.
+#[derive(Debug, Clone)]
+pub struct ContinuousSeries {
+    pub bars: Vec<Bar>,
+    pub rolls: Vec<ContractRoll>,
+}
+
+/// Stitches per-contract bars into a continuous series.
+///
+/// The most recent contract keeps its actual prices; adjustments are applied
+/// backwards to earlier contracts. Roll decisions only use data available
+/// before the roll bar.
+#[derive(Debug, Clone)]
+pub struct ContinuousContractBuilder {
+    symbol: String,
+    roll_rule: RollRule,
+    adjustment: AdjustmentMethod,
+}
+
+impl ContinuousContractBuilder {
+    pub fn new(symbol: impl Into<String>) -> Self {
+        Self {
+            symbol: symbol.into(),
+            roll_rule: RollRule::DaysBeforeExpiry(8),
+            adjustment: AdjustmentMethod::None,
+        }
+    }
+
+    pub fn roll_rule(mut self, roll_rule: RollRule) -> Self {
+        self.roll_rule = roll_rule;
+        self
+    }
+
+    pub fn adjustment(mut self, adjustment: AdjustmentMethod) -> Self {
+        self.adjustment = adjustment;
+        self
+    }
+
+    pub fn build(&self, mut contracts: Vec<ContractData>) -> Result<ContinuousSeries, DataError> {
+        if contracts.is_empty() {
+            return Err(DataError::NotFound(format!(
+                "No contracts to build {} from",
+                self.symbol
+            )));
+        }
+        contracts.sort_by_key(|c| c.expiry);
+        let indexed: Vec<IndexedContract> = contracts.iter().map(IndexedContract::new).collect();
+
+        let timestamps: BTreeSet<DateTime<Utc>> = contracts
+            .iter()
+            .flat_map(|c| c.bars.iter().map(|b| b.timestamp))
+            .collect();
+
+        let mut bars = Vec::new();
+        // (index of first bar from the new contract, raw roll)
+        let mut rolls: Vec<(usize, RawRoll)> = Vec::new();
+        let mut active = 0;
+
+        for t in timestamps {
+            while active + 1 < contracts.len()
+                && self.should_roll(&indexed[active], &indexed[active + 1], t)
+            {
+                let (from, to) = (&indexed[active], &indexed[active + 1]);
+                // Nothing to stitch if the old contract never contributed a bar
+                if let (Some(from_price), Some(to_price), false) =
+                    (from.close_before(t), to.price_at_roll(t), bars.is_empty())
+                {
+                    rolls.push((
+                        bars.len(),
+                        RawRoll {
+                            timestamp: t,
+                            from: from.contract.symbol.clone(),
+                            to: to.contract.symbol.clone(),
+                            from_price,
+                            to_price,
+                        },
+                    ));
+                }
+                active += 1;
+            }
+
+            if let Some(bar) = indexed[active].bars.get(&t) {
+                bars.push(Bar {
+                    instrument: self.symbol.clone(),
+                    ..(*bar).clone()
+                });
+            }
+        }
+
+        Ok(self.adjust(bars, rolls))
+    }
+
+    fn should_roll(
+        &self,
+        front: &IndexedContract,
+        next: &IndexedContract,
+        t: DateTime<Utc>,
+    ) -> bool {
+        // Can't roll into a contract that hasn't started trading
+        if next.close_before(t).is_none() && !next.bars.contains_key(&t) {
+            return false;
+        }
+        let date = t.date_naive();
+        // Always roll off an expired or exhausted contract
+        if date > front.contract.expiry || front.bars.range(t..).next().is_none() {
+            return true;
+        }
+        match self.roll_rule {
+            RollRule::DaysBeforeExpiry(days) => {
+                date >= front.contract.expiry - Duration::days(i64::from(days))
+            }
+            RollRule::VolumeCrossover => crossed(&front.daily_volume, &next.daily_volume, date),
+            RollRule::OpenInterestCrossover => crossed(
+                &front.contract.open_interest,
+                &next.contract.open_interest,
+                date,
+            ),
+        }
+    }
+
+    /// Apply the adjustment method backwards from the latest contract.
+    fn adjust(&self, mut bars: Vec<Bar>, rolls: Vec<(usize, RawRoll)>) -> ContinuousSeries {
+        // Adjustment applying to bars from each roll onwards (latest contract is untouched)
+        let mut after = vec![Adjustment::NONE; rolls.len() + 1];
+        for (k, (_, roll)) in rolls.iter().enumerate().rev() {
+            after[k] = after[k + 1].then(self.adjustment, roll.from_price, roll.to_price);
+        }
+
+        let mut segment = 0;
+        for (i, bar) in bars.iter_mut().enumerate() {
+            while segment < rolls.len() && rolls[segment].0 <= i {
+                segment += 1;
+            }
+            let adj = after[segment];
+            bar.open = adj.apply(bar.open);
+            bar.high = adj.apply(bar.high);
+            bar.low = adj.apply(bar.low);
+            bar.close = adj.apply(bar.close);
+        }
+
+        let rolls = rolls
+            .into_iter()
+            .enumerate()
+            .map(|(k, (_, roll))| ContractRoll {
+                instrument: self.symbol.clone(),
+                timestamp: roll.timestamp,
+                from_contract: roll.from,
+                to_contract: roll.to,
+                exit_price: after[k].apply(roll.from_price),
+                entry_price: after[k + 1].apply(roll.to_price),
+                price_gap: roll.to_price - roll.from_price,
+            })
+            .collect();
+
+        ContinuousSeries { bars, rolls }
+    }
+}
+
+/// Write a series' rolls to CSV, one row per [`ContractRoll`] with its field
+/// names as headers.
+pub fn write_rolls_csv(path: &Path, rolls: &[ContractRoll]) -> Result<(), DataError> {
+    let mut writer = csv::Writer::from_path(path)
+        .map_err(|e| DataError::ParseError(format!("Failed to create CSV: {}", e)))?;
+    for roll in rolls {
+        writer
+            .serialize(roll)
+            .map_err(|e| DataError::ParseError(format!("CSV write error: {}", e)))?;
+    }
+    writer.flush().map_err(DataError::IoError)
+}
+
+/// Read rolls written by [`write_rolls_csv`], in time order.
+pub fn read_rolls_csv(path: &Path) -> Result<Vec<ContractRoll>, DataError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .map_err(|e| DataError::ParseError(format!("Failed to open CSV: {}", e)))?;
+    let mut rolls = reader
+        .deserialize()
+        .collect::<Result<Vec<ContractRoll>, _>>()
+        .map_err(|e| DataError::ParseError(format!("Invalid roll record: {}", e)))?;
+    rolls.sort_by_key(|r| r.timestamp);
+    Ok(rolls)
+}
+
+struct RawRoll {
+    timestamp: DateTime<Utc>,
+    from: String,
+    to: String,
+    from_price: Decimal,
+    to_price: Decimal,
+}
+
+/// An additive offset followed by a multiplicative factor.
+#[derive(Debug, Clone, Copy)]
+struct Adjustment {
+    offset: Decimal,
+    factor: Decimal,
+}
+
+impl Adjustment {
+    const NONE: Adjustment = Adjustment {
+        offset: Decimal::ZERO,
+        factor: Decimal::ONE,
+    };
+
+    /// Extend this adjustment to cover an earlier roll.
+    fn then(self, method: AdjustmentMethod, from: Decimal, to: Decimal) -> Adjustment {
+        match method {
+            AdjustmentMethod::None => self,
+            AdjustmentMethod::Difference => Adjustment {
+                offset: self.offset + (to - from),
+                ..self
+            },
+            AdjustmentMethod::Ratio if !from.is_zero() => Adjustment {
+                factor: self.factor * to / from,
+                ..self
+            },
+            AdjustmentMethod::Ratio => self,
+        }
+    }
+
+    fn apply(&self, price: Decimal) -> Decimal {
+        (price + self.offset) * self.factor
+    }
+}
+
+struct IndexedContract<'a> {
+    contract: &'a ContractData,
+    bars: BTreeMap<DateTime<Utc>, &'a Bar>,
+    daily_volume: BTreeMap<NaiveDate, Decimal>,
+}
+
+impl<'a> IndexedContract<'a> {
+    fn new(contract: &'a ContractData) -> Self {
+        let mut daily_volume = BTreeMap::new();
+        for bar in &contract.bars {
+            *daily_volume
+                .entry(bar.timestamp.date_naive())
+                .or_insert(Decimal::ZERO) += bar.volume;
+        }
+        Self {
+            contract,
+            bars: contract.bars.iter().map(|b| (b.timestamp, b)).collect(),
+            daily_volume,
+        }
+    }
+
+    fn close_before(&self, t: DateTime<Utc>) -> Option<Decimal> {
+        self.bars.range(..t).next_back().map(|(_, b)| b.close)
+    }
+
+    /// Price the new contract is entered at: its last close, or the roll bar's open.
+    fn price_at_roll(&self, t: DateTime<Utc>) -> Option<Decimal> {
+        self.close_before(t)
+            .or_else(|| self.bars.get(&t).map(|b| b.open))
+    }
+}
+
+/// Whether `next` beat `front` on the front's last recorded day before `date`.
+fn crossed(
+    front: &BTreeMap<NaiveDate, Decimal>,
+    next: &BTreeMap<NaiveDate, Decimal>,
+    date: NaiveDate,
+) -> bool {
+    front
+        .range(..date)
+        .next_back()
+        .is_some_and(|(day, front_value)| next.get(day).is_some_and(|v| v > front_value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn daily_bars(start: &str, days: i64, close: Decimal, volume: Decimal) -> Vec<Bar> {
+        let start: DateTime<Utc> = start.parse().unwrap();
+        (0..days)
+            .map(|d| Bar {
+                instrument: String::new(),
+                timestamp: start + Duration::days(d),
+                open: close,
+                high: close + dec!(5),
+                low: close - dec!(5),
+                close,
+                volume,
+            })
+            .collect()
+    }
+
+    fn contracts() -> Vec<ContractData> {
+        let expiry_h = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+        let expiry_m = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        vec![
+            ContractData::new(
+                "ESM4",
+                expiry_m,
+                daily_bars("2024-03-01T21:00:00Z", 20, dec!(5050), dec!(100)),
+            ),
+            ContractData::new(
+                "ESH4",
+                expiry_h,
+                daily_bars("2024-03-01T21:00:00Z", 15, dec!(5000), dec!(1000)),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_days_before_expiry_with_difference_adjustment() {
+        let series = ContinuousContractBuilder::new("ES")
+            .roll_rule(RollRule::DaysBeforeExpiry(8))
+            .adjustment(AdjustmentMethod::Difference)
+            .build(contracts())
+            .unwrap();
+
+        assert_eq!(series.rolls.len(), 1);
+        let roll = &series.rolls[0];
+        assert_eq!(roll.from_contract, "ESH4");
+        assert_eq!(roll.to_contract, "ESM4");
+        assert_eq!(
+            roll.timestamp.date_naive(),
+            NaiveDate::from_ymd_opt(2024, 3, 7).unwrap()
+        );
+        assert_eq!(roll.price_gap, dec!(50));
+        assert_eq!(roll.exit_price, roll.entry_price);
+
+        // Back-adjusted front bars line up with the unadjusted next contract
+        assert_eq!(series.bars.len(), 20);
+        assert!(series
+            .bars
+            .iter()
+            .all(|b| b.close == dec!(5050) && b.instrument == "ES"));
+    }
+
+    #[test]
+    fn test_volume_crossover_without_adjustment() {
+        let mut contracts = contracts();
+        // Volume moves to ESM4 from 2024-03-11
+        for bar in contracts[0].bars.iter_mut().skip(10) {
+            bar.volume = dec!(5000);
+        }
+        let series = ContinuousContractBuilder::new("ES")
+            .roll_rule(RollRule::VolumeCrossover)
+            .build(contracts)
+            .unwrap();
+
+        let roll = &series.rolls[0];
+        // Decided on the 11th's volume, so the roll happens on the next day
+        assert_eq!(
+            roll.timestamp.date_naive(),
+            NaiveDate::from_ymd_opt(2024, 3, 12).unwrap()
+        );
+        assert_eq!(
+            (roll.exit_price, roll.entry_price),
+            (dec!(5000), dec!(5050))
+        );
+        assert_eq!(series.bars[10].close, dec!(5000));
+        assert_eq!(series.bars[11].close, dec!(5050));
+    }
+
+    #[test]
+    fn test_ratio_adjustment() {
+        let series = ContinuousContractBuilder::new("ES")
+            .adjustment(AdjustmentMethod::Ratio)
+            .build(contracts())
+            .unwrap();
+
+        assert_eq!(series.bars[0].close, dec!(5050));
+        assert_eq!(series.bars[0].high, dec!(5005) * dec!(5050) / dec!(5000));
+    }
+
+    #[test]
+    fn test_rolls_csv_round_trip() {
+        let series = ContinuousContractBuilder::new("ES")
+            .build(contracts())
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("propbot_rolls_{}.csv", std::process::id()));
+
+        write_rolls_csv(&path, &series.rolls).unwrap();
+        let rolls = read_rolls_csv(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(rolls, series.rolls);
+    }
+}
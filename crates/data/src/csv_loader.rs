@@ -1,135 +1,464 @@
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
 use propbot_core::{Bar, DataError, Tick};
 use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::str::FromStr;
 
+/// A CSV column, by header name (case-insensitive) or zero-based index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColumnRef {
+    Index(usize),
+    Name(String),
+}
+
+/// Describes the layout of a bar CSV file.
+///
+/// Every field is optional; anything left unset falls back to the default
+/// behaviour of [`load_bars_from_csv`] (header aliases, format detection, UTC).
+/// Usually loaded from TOML:
+///
+/// ```toml
+/// delimiter = ";"
+/// date = "Date"
+/// time = "Time"
+/// date_format = "%d/%m/%Y"
+/// time_format = "%H:%M"
+/// timezone = "Europe/Berlin"
+/// price_multiplier = "0.01"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CsvSchema {
+    /// Field delimiter (default `,`).
+    pub delimiter: Option<char>,
+    /// The file has no header row. Columns must then be given by index;
+    /// unset columns default to `timestamp,open,high,low,close,volume` order.
+    pub no_headers: bool,
+    /// Combined date and time column.
+    pub timestamp: Option<ColumnRef>,
+    /// Separate date column (used with `time` instead of `timestamp`).
+    pub date: Option<ColumnRef>,
+    /// Separate time-of-day column.
+    pub time: Option<ColumnRef>,
+    pub open: Option<ColumnRef>,
+    pub high: Option<ColumnRef>,
+    pub low: Option<ColumnRef>,
+    pub close: Option<ColumnRef>,
+    pub volume: Option<ColumnRef>,
+    /// chrono format string for the timestamp column, or `unix` / `unix_ms`.
+    /// Formats containing `%z` carry their own offset and ignore `timezone`.
+    pub timestamp_format: Option<String>,
+    /// chrono format string for the date column.
+    pub date_format: Option<String>,
+    /// chrono format string for the time column.
+    pub time_format: Option<String>,
+    /// Time zone of timestamps without an offset (default UTC): a fixed UTC
+    /// offset like `-05:00`, or an IANA name like `America/New_York` that
+    /// follows daylight saving. Epoch timestamps are absolute and ignore it.
+    pub timezone: Option<String>,
+    /// Applied to open, high, low and close (e.g. `0.01` for prices in cents).
+    pub price_multiplier: Option<Decimal>,
+    /// Instrument symbol to assign (default: the file name).
+    pub instrument: Option<String>,
+}
+
+impl CsvSchema {
+    /// Load a schema from a TOML file.
+    pub fn from_toml_file(path: &Path) -> Result<Self, DataError> {
+        let text = std::fs::read_to_string(path)?;
+        Self::from_toml_str(&text)
+    }
+
+    pub fn from_toml_str(text: &str) -> Result<Self, DataError> {
+        let schema: Self = toml::from_str(text)
+            .map_err(|e| DataError::ParseError(format!("Invalid CSV schema: {}", e)))?;
+        schema.zone()?;
+        Ok(schema)
+    }
+
+    /// The configured source time zone.
+    pub fn zone(&self) -> Result<SourceZone, DataError> {
+        match self.timezone.as_deref().map(str::trim) {
+            None | Some("UTC") | Some("utc") | Some("Z") => Ok(SourceZone::default()),
+            Some(tz) => FixedOffset::from_str(tz)
+                .map(SourceZone::Offset)
+                .or_else(|_| Tz::from_str(tz).map(SourceZone::Named))
+                .map_err(|_| {
+                    DataError::ParseError(format!(
+                        "Unsupported timezone '{}': use a UTC offset like -05:00 or an IANA name like America/New_York",
+                        tz
+                    ))
+                }),
+        }
+    }
+
+    fn csv_reader(&self, path: &Path) -> Result<csv::Reader<std::fs::File>, DataError> {
+        let mut builder = csv::ReaderBuilder::new();
+        builder
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .has_headers(!self.no_headers);
+        if let Some(delimiter) = self.delimiter {
+            let delimiter = u8::try_from(delimiter).map_err(|_| {
+                DataError::ParseError(format!("Delimiter '{}' is not a single byte", delimiter))
+            })?;
+            builder.delimiter(delimiter);
+        }
+        builder
+            .from_path(path)
+            .map_err(|e| DataError::ParseError(format!("Failed to open CSV: {}", e)))
+    }
+}
+
+/// Time zone that timestamps without an offset are read in.
+#[derive(Debug, Clone, Copy)]
+pub enum SourceZone {
+    Offset(FixedOffset),
+    Named(Tz),
+}
+
+impl Default for SourceZone {
+    fn default() -> Self {
+        SourceZone::Offset(FixedOffset::east_opt(0).unwrap())
+    }
+}
+
+impl SourceZone {
+    fn is_utc(&self) -> bool {
+        match self {
+            SourceZone::Offset(offset) => offset.local_minus_utc() == 0,
+            SourceZone::Named(tz) => *tz == Tz::UTC,
+        }
+    }
+
+    /// The instant of a local time. Of the two instants of a time repeated
+    /// when daylight saving ends, the earlier is taken.
+    pub fn localize(&self, naive: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            SourceZone::Offset(offset) => localize(naive, *offset),
+            SourceZone::Named(tz) => tz
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc)),
+        }
+    }
+}
+
 /// Load OHLCV bars from a CSV file.
 ///
 /// Expected columns (case-insensitive, flexible ordering):
 /// `timestamp` (or `date`, `datetime`), `open`, `high`, `low`, `close`, `volume`
 ///
-/// Supports common date formats.
+/// Supports common date formats. Use [`load_bars_from_csv_with`] for other layouts.
 pub fn load_bars_from_csv(path: &Path) -> Result<Vec<Bar>, DataError> {
-    let instrument = path
-        .file_stem()
-        .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| "unknown".to_string());
-
-    let mut reader = csv::ReaderBuilder::new()
-        .flexible(true)
-        .trim(csv::Trim::All)
-        .from_path(path)
-        .map_err(|e| DataError::ParseError(format!("Failed to open CSV: {}", e)))?;
-
-    let headers = reader
-        .headers()
-        .map_err(|e| DataError::ParseError(format!("Failed to read headers: {}", e)))?
-        .clone();
-
-    let col_map = resolve_bar_columns(&headers)?;
-
-    let mut bars = Vec::new();
-    for result in reader.records() {
-        let record = result.map_err(|e| DataError::ParseError(format!("CSV record error: {}", e)))?;
-
-        let timestamp = parse_timestamp(&record[col_map.timestamp])?;
-        let open = parse_decimal(&record[col_map.open], "open")?;
-        let high = parse_decimal(&record[col_map.high], "high")?;
-        let low = parse_decimal(&record[col_map.low], "low")?;
-        let close = parse_decimal(&record[col_map.close], "close")?;
+    load_bars_from_csv_with(path, &CsvSchema::default())
+}
+
+/// Load OHLCV bars from a CSV file laid out as described by `schema`.
+pub fn load_bars_from_csv_with(path: &Path, schema: &CsvSchema) -> Result<Vec<Bar>, DataError> {
+    let mut bars = read_bars_from_csv_with(path, schema)?;
+    // Sort by timestamp
+    bars.sort_by_key(|b| b.timestamp);
+    Ok(bars)
+}
+
+/// Read OHLCV bars from a CSV file in file order, without sorting.
+///
+/// Same columns as [`load_bars_from_csv`]; used where row order matters (validation).
+pub fn read_bars_from_csv(path: &Path) -> Result<Vec<Bar>, DataError> {
+    read_bars_from_csv_with(path, &CsvSchema::default())
+}
+
+/// Read bars as described by `schema`, in file order.
+pub fn read_bars_from_csv_with(path: &Path, schema: &CsvSchema) -> Result<Vec<Bar>, DataError> {
+    CsvBarReader::open(path, schema)?.collect()
+}
+
+/// Reads a bar CSV file one row at a time, in file order.
+///
+/// Used to stream files too large to load at once.
+pub struct CsvBarReader {
+    records: csv::StringRecordsIntoIter<std::fs::File>,
+    schema: CsvSchema,
+    columns: BarColumnMap,
+    zone: SourceZone,
+    multiplier: Decimal,
+    instrument: String,
+}
+
+impl CsvBarReader {
+    pub fn open(path: &Path, schema: &CsvSchema) -> Result<Self, DataError> {
+        let instrument = schema.instrument.clone().unwrap_or_else(|| {
+            path.file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "unknown".to_string())
+        });
+        let zone = schema.zone()?;
+        let multiplier = schema.price_multiplier.unwrap_or(Decimal::ONE);
+
+        let mut reader = schema.csv_reader(path)?;
+
+        let headers = if schema.no_headers {
+            csv::StringRecord::new()
+        } else {
+            reader
+                .headers()
+                .map_err(|e| DataError::ParseError(format!("Failed to read headers: {}", e)))?
+                .clone()
+        };
+
+        let columns = resolve_bar_columns(&headers, schema)?;
+
+        Ok(Self {
+            records: reader.into_records(),
+            schema: schema.clone(),
+            columns,
+            zone,
+            multiplier,
+            instrument,
+        })
+    }
+
+    fn parse(&self, record: &csv::StringRecord) -> Result<Bar, DataError> {
+        let col_map = &self.columns;
+        let multiplier = self.multiplier;
+        let field = |idx: usize, name: &str| {
+            record.get(idx).ok_or_else(|| {
+                DataError::ParseError(format!(
+                    "Missing {} column (index {}) on line {}",
+                    name,
+                    idx,
+                    record.position().map(|p| p.line()).unwrap_or_default()
+                ))
+            })
+        };
+
+        let timestamp = match col_map.timestamp {
+            TimestampColumns::Combined(idx) => parse_timestamp_with(
+                field(idx, "timestamp")?,
+                self.schema.timestamp_format.as_deref(),
+                self.zone,
+            )?,
+            TimestampColumns::Split(date_idx, time_idx) => parse_date_time(
+                field(date_idx, "date")?,
+                field(time_idx, "time")?,
+                &self.schema,
+                self.zone,
+            )?,
+        };
+        let open = parse_decimal(field(col_map.open, "open")?, "open")? * multiplier;
+        let high = parse_decimal(field(col_map.high, "high")?, "high")? * multiplier;
+        let low = parse_decimal(field(col_map.low, "low")?, "low")? * multiplier;
+        let close = parse_decimal(field(col_map.close, "close")?, "close")? * multiplier;
         let volume = if let Some(vol_idx) = col_map.volume {
-            parse_decimal(&record[vol_idx], "volume")?
+            parse_decimal(field(vol_idx, "volume")?, "volume")?
         } else {
             Decimal::ZERO
         };
 
-        bars.push(Bar {
-            instrument: instrument.clone(),
+        Ok(Bar {
+            instrument: self.instrument.clone(),
             timestamp,
             open,
             high,
             low,
             close,
             volume,
-        });
+        })
     }
+}
 
-    // Sort by timestamp
-    bars.sort_by_key(|b| b.timestamp);
-    Ok(bars)
+impl Iterator for CsvBarReader {
+    type Item = Result<Bar, DataError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.records.next()?;
+        Some(
+            record
+                .map_err(|e| DataError::ParseError(format!("CSV record error: {}", e)))
+                .and_then(|record| self.parse(&record)),
+        )
+    }
+}
+
+/// Write bars to a CSV file with RFC 3339 timestamps.
+pub fn write_bars_to_csv(path: &Path, bars: &[Bar]) -> Result<(), DataError> {
+    let mut writer = csv::Writer::from_path(path)
+        .map_err(|e| DataError::ParseError(format!("Failed to create CSV: {}", e)))?;
+    writer
+        .write_record(["timestamp", "open", "high", "low", "close", "volume"])
+        .map_err(|e| DataError::ParseError(format!("CSV write error: {}", e)))?;
+    for bar in bars {
+        writer
+            .write_record([
+                bar.timestamp.to_rfc3339(),
+                bar.open.to_string(),
+                bar.high.to_string(),
+                bar.low.to_string(),
+                bar.close.to_string(),
+                bar.volume.to_string(),
+            ])
+            .map_err(|e| DataError::ParseError(format!("CSV write error: {}", e)))?;
+    }
+    writer.flush().map_err(DataError::IoError)
 }
 
 /// Load tick data from a CSV file.
 ///
 /// Expected columns: `timestamp`, `bid`, `ask`, `last`, `volume`
 pub fn load_ticks_from_csv(path: &Path) -> Result<Vec<Tick>, DataError> {
-    let instrument = path
-        .file_stem()
-        .map(|s| {
-            let name = s.to_string_lossy().to_string();
-            name.strip_suffix("_ticks").unwrap_or(&name).to_string()
+    load_ticks_from_csv_with(path, &CsvSchema::default())
+}
+
+/// Load tick data from a CSV file, using the schema's delimiter, timestamp
+/// format and timezone. See [`CsvTickReader::open_with`].
+pub fn load_ticks_from_csv_with(path: &Path, schema: &CsvSchema) -> Result<Vec<Tick>, DataError> {
+    let mut ticks = CsvTickReader::open_with(path, schema)?.collect::<Result<Vec<_>, _>>()?;
+    ticks.sort_by_key(|t| t.timestamp);
+    Ok(ticks)
+}
+
+/// Reads a tick CSV file one row at a time, in file order.
+pub struct CsvTickReader {
+    records: csv::StringRecordsIntoIter<std::fs::File>,
+    instrument: String,
+    timestamp_format: Option<String>,
+    zone: SourceZone,
+    ts_col: usize,
+    bid_col: usize,
+    ask_col: usize,
+    last_col: Option<usize>,
+    vol_col: Option<usize>,
+}
+
+impl CsvTickReader {
+    pub fn open(path: &Path) -> Result<Self, DataError> {
+        Self::open_with(path, &CsvSchema::default())
+    }
+
+    /// Open a tick file read with the schema's `delimiter`,
+    /// `timestamp_format`, `timezone` and `instrument`.
+    ///
+    /// Tick files always have the named tick columns, so a schema that maps
+    /// bar columns or scales prices is rejected.
+    pub fn open_with(path: &Path, schema: &CsvSchema) -> Result<Self, DataError> {
+        let bar_only = schema.no_headers
+            || schema.price_multiplier.is_some()
+            || [
+                &schema.timestamp,
+                &schema.date,
+                &schema.time,
+                &schema.open,
+                &schema.high,
+                &schema.low,
+                &schema.close,
+                &schema.volume,
+            ]
+            .iter()
+            .any(|c| c.is_some());
+        if bar_only {
+            return Err(DataError::ParseError(
+                "Tick CSVs need timestamp, bid and ask headers: only delimiter, timestamp_format, timezone and instrument apply to them".to_string(),
+            ));
+        }
+        let zone = schema.zone()?;
+        let instrument = schema.instrument.clone().unwrap_or_else(|| {
+            path.file_stem()
+                .map(|s| {
+                    let name = s.to_string_lossy().to_string();
+                    name.strip_suffix("_ticks").unwrap_or(&name).to_string()
+                })
+                .unwrap_or_else(|| "unknown".to_string())
+        });
+
+        let mut reader = schema.csv_reader(path)?;
+
+        let headers = reader
+            .headers()
+            .map_err(|e| DataError::ParseError(format!("Failed to read headers: {}", e)))?
+            .clone();
+
+        let ts_col = find_column(&headers, &["timestamp", "date", "datetime", "time"])
+            .ok_or_else(|| DataError::ParseError("No timestamp column found".into()))?;
+        let bid_col = find_column(&headers, &["bid"])
+            .ok_or_else(|| DataError::ParseError("No bid column found".into()))?;
+        let ask_col = find_column(&headers, &["ask"])
+            .ok_or_else(|| DataError::ParseError("No ask column found".into()))?;
+        let last_col = find_column(&headers, &["last", "price"]);
+        let vol_col = find_column(&headers, &["volume", "vol", "size"]);
+
+        Ok(Self {
+            records: reader.into_records(),
+            instrument,
+            timestamp_format: schema.timestamp_format.clone(),
+            zone,
+            ts_col,
+            bid_col,
+            ask_col,
+            last_col,
+            vol_col,
         })
-        .unwrap_or_else(|| "unknown".to_string());
-
-    let mut reader = csv::ReaderBuilder::new()
-        .flexible(true)
-        .trim(csv::Trim::All)
-        .from_path(path)
-        .map_err(|e| DataError::ParseError(format!("Failed to open CSV: {}", e)))?;
-
-    let headers = reader
-        .headers()
-        .map_err(|e| DataError::ParseError(format!("Failed to read headers: {}", e)))?
-        .clone();
-
-    let ts_col = find_column(&headers, &["timestamp", "date", "datetime", "time"])
-        .ok_or_else(|| DataError::ParseError("No timestamp column found".into()))?;
-    let bid_col = find_column(&headers, &["bid"])
-        .ok_or_else(|| DataError::ParseError("No bid column found".into()))?;
-    let ask_col = find_column(&headers, &["ask"])
-        .ok_or_else(|| DataError::ParseError("No ask column found".into()))?;
-    let last_col = find_column(&headers, &["last", "price"]);
-    let vol_col = find_column(&headers, &["volume", "vol", "size"]);
-
-    let mut ticks = Vec::new();
-    for result in reader.records() {
-        let record = result.map_err(|e| DataError::ParseError(format!("CSV record error: {}", e)))?;
-
-        let timestamp = parse_timestamp(&record[ts_col])?;
-        let bid = parse_decimal(&record[bid_col], "bid")?;
-        let ask = parse_decimal(&record[ask_col], "ask")?;
-        let last = if let Some(idx) = last_col {
-            parse_decimal(&record[idx], "last")?
+    }
+
+    fn parse(&self, record: &csv::StringRecord) -> Result<Tick, DataError> {
+        let field = |idx: usize| record.get(idx).unwrap_or_default();
+        let timestamp = parse_timestamp_with(
+            field(self.ts_col),
+            self.timestamp_format.as_deref(),
+            self.zone,
+        )?;
+        let bid = parse_decimal(field(self.bid_col), "bid")?;
+        let ask = parse_decimal(field(self.ask_col), "ask")?;
+        let last = if let Some(idx) = self.last_col {
+            parse_decimal(field(idx), "last")?
         } else {
             (bid + ask) / Decimal::TWO
         };
-        let volume = if let Some(idx) = vol_col {
-            parse_decimal(&record[idx], "volume")?
+        let volume = if let Some(idx) = self.vol_col {
+            parse_decimal(field(idx), "volume")?
         } else {
             Decimal::ZERO
         };
 
-        ticks.push(Tick {
-            instrument: instrument.clone(),
+        Ok(Tick {
+            instrument: self.instrument.clone(),
             timestamp,
             bid,
             ask,
             last,
             volume,
-        });
+        })
     }
+}
 
-    ticks.sort_by_key(|t| t.timestamp);
-    Ok(ticks)
+impl Iterator for CsvTickReader {
+    type Item = Result<Tick, DataError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = self.records.next()?;
+        Some(
+            record
+                .map_err(|e| DataError::ParseError(format!("CSV record error: {}", e)))
+                .and_then(|record| self.parse(&record)),
+        )
+    }
 }
 
 // ---------------------------------------------------------------------------
 // Internal helpers
 // ---------------------------------------------------------------------------
 
+enum TimestampColumns {
+    Combined(usize),
+    /// Date and time-of-day columns.
+    Split(usize, usize),
+}
+
 struct BarColumnMap {
-    timestamp: usize,
+    timestamp: TimestampColumns,
     open: usize,
     high: usize,
     low: usize,
@@ -137,26 +466,60 @@ struct BarColumnMap {
     volume: Option<usize>,
 }
 
-fn resolve_bar_columns(headers: &csv::StringRecord) -> Result<BarColumnMap, DataError> {
-    let ts = find_column(headers, &["timestamp", "date", "datetime", "time"])
-        .ok_or_else(|| DataError::ParseError("No timestamp column found".into()))?;
-    let open = find_column(headers, &["open", "o"])
-        .ok_or_else(|| DataError::ParseError("No open column found".into()))?;
-    let high = find_column(headers, &["high", "h"])
-        .ok_or_else(|| DataError::ParseError("No high column found".into()))?;
-    let low = find_column(headers, &["low", "l"])
-        .ok_or_else(|| DataError::ParseError("No low column found".into()))?;
-    let close = find_column(headers, &["close", "c"])
-        .ok_or_else(|| DataError::ParseError("No close column found".into()))?;
-    let volume = find_column(headers, &["volume", "vol", "v"]);
+fn resolve_bar_columns(
+    headers: &csv::StringRecord,
+    schema: &CsvSchema,
+) -> Result<BarColumnMap, DataError> {
+    // Explicit column, else header alias, else position in a header-less file
+    let column = |explicit: &Option<ColumnRef>, aliases: &[&str], position: usize, name: &str| {
+        match explicit {
+            Some(ColumnRef::Index(i)) => Ok(Some(*i)),
+            Some(ColumnRef::Name(n)) if schema.no_headers => Err(DataError::ParseError(format!(
+                "Column '{}' given by name but the file has no headers",
+                n
+            ))),
+            Some(ColumnRef::Name(n)) => find_column(headers, &[n.to_lowercase().as_str()])
+                .map(Some)
+                .ok_or_else(|| DataError::ParseError(format!("No {} column '{}' found", name, n))),
+            None if schema.no_headers => Ok(Some(position)),
+            None => Ok(find_column(headers, aliases)),
+        }
+    };
+    let required = |explicit: &Option<ColumnRef>, aliases: &[&str], position: usize, name: &str| {
+        column(explicit, aliases, position, name)?
+            .ok_or_else(|| DataError::ParseError(format!("No {} column found", name)))
+    };
+
+    let timestamp = match (&schema.date, &schema.time) {
+        (Some(_), Some(_)) => TimestampColumns::Split(
+            required(&schema.date, &[], 0, "date")?,
+            required(&schema.time, &[], 1, "time")?,
+        ),
+        (None, None) => TimestampColumns::Combined(required(
+            &schema.timestamp,
+            &["timestamp", "date", "datetime", "time"],
+            0,
+            "timestamp",
+        )?),
+        _ => {
+            return Err(DataError::ParseError(
+                "Separate date and time columns must both be set".into(),
+            ))
+        }
+    };
+    // Header-less files with split date/time shift the price columns right by one
+    let first = match timestamp {
+        TimestampColumns::Combined(_) => 1,
+        TimestampColumns::Split(..) => 2,
+    };
 
     Ok(BarColumnMap {
-        timestamp: ts,
-        open,
-        high,
-        low,
-        close,
-        volume,
+        timestamp,
+        open: required(&schema.open, &["open", "o"], first, "open")?,
+        high: required(&schema.high, &["high", "h"], first + 1, "high")?,
+        low: required(&schema.low, &["low", "l"], first + 2, "low")?,
+        close: required(&schema.close, &["close", "c"], first + 3, "close")?,
+        volume: column(&schema.volume, &["volume", "vol", "v"], first + 4, "volume")?,
     })
 }
 
@@ -172,11 +535,85 @@ fn find_column(headers: &csv::StringRecord, names: &[&str]) -> Option<usize> {
     None
 }
 
-fn parse_decimal(s: &str, field: &str) -> Result<Decimal, DataError> {
+pub(crate) fn parse_decimal(s: &str, field: &str) -> Result<Decimal, DataError> {
     Decimal::from_str(s.trim())
         .map_err(|e| DataError::ParseError(format!("Failed to parse {} '{}': {}", field, s, e)))
 }
 
+/// Parse a timestamp with an explicit format, or by detection when `format` is `None`.
+/// Date/time strings without an offset are taken to be in `zone`.
+fn parse_timestamp_with(
+    s: &str,
+    format: Option<&str>,
+    zone: SourceZone,
+) -> Result<DateTime<Utc>, DataError> {
+    let s = s.trim();
+    let invalid = || DataError::ParseError(format!("Unable to parse timestamp: '{}'", s));
+    let Some(format) = format else {
+        let utc = parse_timestamp(s)?;
+        // Detected timestamps come back as UTC; reinterpret naive ones in the
+        // source zone. Epoch seconds and RFC 3339 are already absolute.
+        let absolute = s.parse::<i64>().is_ok() || DateTime::parse_from_rfc3339(s).is_ok();
+        if zone.is_utc() || absolute {
+            return Ok(utc);
+        }
+        return zone.localize(utc.naive_utc()).ok_or_else(invalid);
+    };
+
+    match format {
+        "unix" => s
+            .parse::<i64>()
+            .ok()
+            .and_then(|ts| DateTime::from_timestamp(ts, 0))
+            .ok_or_else(invalid),
+        "unix_ms" => s
+            .parse::<i64>()
+            .ok()
+            .and_then(DateTime::from_timestamp_millis)
+            .ok_or_else(invalid),
+        _ if format.contains("%z") || format.contains("%:z") => DateTime::parse_from_str(s, format)
+            .map(|dt| dt.with_timezone(&Utc))
+            .map_err(|_| invalid()),
+        _ => NaiveDateTime::parse_from_str(s, format)
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(s, format)
+                    .ok()
+                    .map(|d| d.and_time(NaiveTime::MIN))
+            })
+            .and_then(|naive| zone.localize(naive))
+            .ok_or_else(invalid),
+    }
+}
+
+/// Combine separate date and time fields.
+fn parse_date_time(
+    date: &str,
+    time: &str,
+    schema: &CsvSchema,
+    zone: SourceZone,
+) -> Result<DateTime<Utc>, DataError> {
+    let combined = format!("{} {}", date.trim(), time.trim());
+    match (&schema.date_format, &schema.time_format) {
+        (None, None) => parse_timestamp_with(&combined, None, zone),
+        (date_format, time_format) => {
+            let format = format!(
+                "{} {}",
+                date_format.as_deref().unwrap_or("%Y-%m-%d"),
+                time_format.as_deref().unwrap_or("%H:%M:%S")
+            );
+            parse_timestamp_with(&combined, Some(&format), zone)
+        }
+    }
+}
+
+pub(crate) fn localize(naive: NaiveDateTime, offset: FixedOffset) -> Option<DateTime<Utc>> {
+    offset
+        .from_local_datetime(&naive)
+        .single()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
 fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, DataError> {
     let s = s.trim();
 
@@ -185,7 +622,9 @@ fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, DataError> {
         return Ok(dt.with_timezone(&Utc));
     }
 
-    // Common formats (without timezone, assume UTC)
+    // Common formats (without timezone, assume UTC). Day-first dates are
+    // deliberately absent: they can't be told apart from month-first ones, so
+    // they need an explicit `timestamp_format`.
     let formats = [
         "%Y-%m-%d %H:%M:%S",
         "%Y-%m-%d %H:%M:%S%.f",
@@ -195,7 +634,6 @@ fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, DataError> {
         "%m/%d/%Y %H:%M",
         "%Y-%m-%d",
         "%Y%m%d %H:%M:%S",
-        "%d/%m/%Y %H:%M:%S",
     ];
 
     for fmt in &formats {
@@ -218,7 +656,151 @@ fn parse_timestamp(s: &str) -> Result<DateTime<Utc>, DataError> {
     }
 
     Err(DataError::ParseError(format!(
-        "Unable to parse timestamp: '{}'",
+        "Unable to parse timestamp: '{}' (set timestamp_format in a CSV schema)",
         s
     )))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::write_temp;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_schema_from_toml() {
+        let schema = CsvSchema::from_toml_str(
+            r#"
+            delimiter = ";"
+            date = "Date"
+            time = 1
+            date_format = "%d/%m/%Y"
+            timezone = "-05:00"
+            price_multiplier = "0.01"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(schema.delimiter, Some(';'));
+        assert_eq!(schema.date, Some(ColumnRef::Name("Date".to_string())));
+        assert_eq!(schema.time, Some(ColumnRef::Index(1)));
+        assert_eq!(schema.price_multiplier, Some(dec!(0.01)));
+
+        assert!(CsvSchema::from_toml_str("timezone = \"America/New_York\"").is_ok());
+        assert!(CsvSchema::from_toml_str("timezone = \"Eastern\"").is_err());
+        assert!(CsvSchema::from_toml_str("delimeter = \";\"").is_err());
+    }
+
+    #[test]
+    fn test_split_date_time_with_offset_and_multiplier() {
+        let path = write_temp(
+            "split.csv",
+            "Date;Time;O;H;L;C;Vol\n05/03/2024;09:30;470000;471000;469000;470500;12\n",
+        );
+        let schema = CsvSchema {
+            delimiter: Some(';'),
+            date: Some(ColumnRef::Name("Date".to_string())),
+            time: Some(ColumnRef::Name("Time".to_string())),
+            date_format: Some("%d/%m/%Y".to_string()),
+            time_format: Some("%H:%M".to_string()),
+            timezone: Some("-05:00".to_string()),
+            price_multiplier: Some(dec!(0.01)),
+            instrument: Some("ES".to_string()),
+            ..Default::default()
+        };
+        let bars = load_bars_from_csv_with(&path, &schema).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(bars.len(), 1);
+        assert_eq!(bars[0].instrument, "ES");
+        assert_eq!(
+            bars[0].timestamp,
+            "2024-03-05T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(bars[0].open, dec!(4700));
+        assert_eq!(bars[0].close, dec!(4705));
+        assert_eq!(bars[0].volume, dec!(12));
+    }
+
+    #[test]
+    fn test_headerless_file_uses_positions() {
+        let path = write_temp(
+            "headerless.csv",
+            "1709647200,4700,4710,4690,4705,3\n1709647260,4705,4715,4700,4712,4\n",
+        );
+        let schema = CsvSchema {
+            no_headers: true,
+            timestamp_format: Some("unix".to_string()),
+            ..Default::default()
+        };
+        let bars = load_bars_from_csv_with(&path, &schema).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(bars.len(), 2);
+        assert_eq!(
+            bars[1].timestamp,
+            "2024-03-05T14:01:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(bars[1].high, dec!(4715));
+        assert_eq!(bars[1].volume, dec!(4));
+    }
+
+    #[test]
+    fn test_named_timezone_follows_dst_and_epochs_stay_absolute() {
+        let path = write_temp(
+            "named_tz.csv",
+            "timestamp,open,high,low,close\n\
+             2024-01-02 09:30:00,1,1,1,1\n\
+             2024-07-02 09:30:00,1,1,1,1\n\
+             1709647200,1,1,1,1\n",
+        );
+        let schema = CsvSchema {
+            timezone: Some("America/New_York".to_string()),
+            ..Default::default()
+        };
+        let bars = read_bars_from_csv_with(&path, &schema).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let times: Vec<String> = bars.iter().map(|b| b.timestamp.to_rfc3339()).collect();
+        assert_eq!(
+            times,
+            vec![
+                "2024-01-02T14:30:00+00:00",
+                "2024-07-02T13:30:00+00:00",
+                "2024-03-05T14:00:00+00:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tick_schema_sets_timezone_and_rejects_bar_columns() {
+        let path = write_temp(
+            "ticks_tz.csv",
+            "time;bid;ask\n2024-03-05 09:30:00;4700.00;4700.25\n",
+        );
+        let schema = CsvSchema {
+            delimiter: Some(';'),
+            timezone: Some("-05:00".to_string()),
+            ..Default::default()
+        };
+        let ticks = load_ticks_from_csv_with(&path, &schema);
+        let bar_schema = CsvSchema {
+            close: Some(ColumnRef::Index(2)),
+            ..schema
+        };
+        let rejected = load_ticks_from_csv_with(&path, &bar_schema);
+        std::fs::remove_file(&path).ok();
+
+        let ticks = ticks.unwrap();
+        assert_eq!(
+            ticks[0].timestamp,
+            "2024-03-05T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(ticks[0].last, dec!(4700.125));
+        assert!(rejected.is_err());
+    }
+
+    #[test]
+    fn test_day_first_dates_are_not_guessed() {
+        assert!(parse_timestamp("13/03/2024 09:30:00").is_err());
+    }
+}
@@ -1,7 +1,24 @@
 use chrono::{DateTime, Utc};
-use propbot_core::{Bar, BacktestResult, Tick};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use propbot_core::{BacktestResult, Bar, Fill, Order, OrderUpdate, Tick, Timeframe, Trade};
 use rust_decimal::Decimal;
-use sqlx::{PgPool, Row};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder, Row};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Rows fetched per query when streaming.
+const STREAM_CHUNK_SIZE: i64 = 10_000;
+
+/// Rows per multi-row insert, well under Postgres' 65535 bind parameter limit.
+const INSERT_CHUNK_SIZE: usize = 5_000;
+
+/// Rows per multi-row insert into the wider order and trade tables.
+const LOG_CHUNK_SIZE: usize = 2_000;
 
 /// Run embedded migrations.
 pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::migrate::MigrateError> {
@@ -9,39 +26,102 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::migrate::MigrateE
     Ok(())
 }
 
-/// Load bars from the database.
+/// An instrument and timeframe stored in the bars table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredSeries {
+    pub instrument: String,
+    pub timeframe: Timeframe,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub bars: i64,
+}
+
+/// Load bars of one timeframe from the database.
 pub async fn load_bars(
     pool: &PgPool,
     instrument: &str,
+    timeframe: Timeframe,
     start: DateTime<Utc>,
     end: DateTime<Utc>,
 ) -> Result<Vec<Bar>, sqlx::Error> {
     let rows = sqlx::query(
         "SELECT instrument, timestamp, open, high, low, close, volume
          FROM bars
-         WHERE instrument = $1 AND timestamp >= $2 AND timestamp <= $3
+         WHERE instrument = $1 AND timeframe = $2 AND timestamp >= $3 AND timestamp <= $4
          ORDER BY timestamp ASC",
     )
     .bind(instrument)
+    .bind(timeframe.to_string())
     .bind(start)
     .bind(end)
     .fetch_all(pool)
     .await?;
 
-    let bars = rows
-        .iter()
-        .map(|r| Bar {
-            instrument: r.get("instrument"),
-            timestamp: r.get("timestamp"),
-            open: r.get("open"),
-            high: r.get("high"),
-            low: r.get("low"),
-            close: r.get("close"),
-            volume: r.get("volume"),
-        })
-        .collect();
+    Ok(rows.iter().map(bar_from_row).collect())
+}
 
-    Ok(bars)
+/// Stream bars from the database in chunks of [`STREAM_CHUNK_SIZE`] rows.
+///
+/// Each chunk is a separate keyset-paginated query on the primary key, so no
+/// connection or transaction is held between chunks.
+pub fn stream_bars<'a>(
+    pool: &'a PgPool,
+    instrument: &'a str,
+    timeframe: Timeframe,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> BoxStream<'a, Result<Vec<Bar>, sqlx::Error>> {
+    stream::try_unfold(Some(None), move |after| async move {
+        let Some(after) = after else {
+            return Ok(None);
+        };
+        let bars = bar_chunk(pool, instrument, timeframe, start, end, after).await?;
+        let full = bars.len() as i64 == STREAM_CHUNK_SIZE;
+        let next = full.then(|| bars.last().map(|b| b.timestamp));
+        Ok((!bars.is_empty()).then_some((bars, next)))
+    })
+    .boxed()
+}
+
+/// The next chunk of bars after `after` (or from `start` when `None`).
+async fn bar_chunk(
+    pool: &PgPool,
+    instrument: &str,
+    timeframe: Timeframe,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    after: Option<DateTime<Utc>>,
+) -> Result<Vec<Bar>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT instrument, timestamp, open, high, low, close, volume
+         FROM bars
+         WHERE instrument = $1 AND timeframe = $2 AND timestamp >= $3 AND timestamp <= $4
+           AND ($5::timestamptz IS NULL OR timestamp > $5)
+         ORDER BY timestamp ASC
+         LIMIT $6",
+    )
+    .bind(instrument)
+    .bind(timeframe.to_string())
+    .bind(start)
+    .bind(end)
+    .bind(after)
+    .bind(STREAM_CHUNK_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(bar_from_row).collect())
+}
+
+fn bar_from_row(r: &PgRow) -> Bar {
+    Bar {
+        instrument: r.get("instrument"),
+        timestamp: r.get("timestamp"),
+        open: r.get("open"),
+        high: r.get("high"),
+        low: r.get("low"),
+        close: r.get("close"),
+        volume: r.get("volume"),
+    }
 }
 
 /// Load ticks from the database.
@@ -55,7 +135,7 @@ pub async fn load_ticks(
         "SELECT instrument, timestamp, bid, ask, last_price, volume
          FROM ticks
          WHERE instrument = $1 AND timestamp >= $2 AND timestamp <= $3
-         ORDER BY timestamp ASC",
+         ORDER BY timestamp ASC, seq ASC",
     )
     .bind(instrument)
     .bind(start)
@@ -63,100 +143,320 @@ pub async fn load_ticks(
     .fetch_all(pool)
     .await?;
 
-    let ticks = rows
-        .iter()
-        .map(|r| Tick {
-            instrument: r.get("instrument"),
-            timestamp: r.get("timestamp"),
-            bid: r.get("bid"),
-            ask: r.get("ask"),
-            last: r.get::<Decimal, _>("last_price"),
-            volume: r.get("volume"),
-        })
-        .collect();
+    Ok(rows.iter().map(tick_from_row).collect())
+}
+
+/// Stream ticks from the database in chunks of [`STREAM_CHUNK_SIZE`] rows.
+///
+/// Pages on `(timestamp, seq)`, since several ticks can share a timestamp.
+pub fn stream_ticks<'a>(
+    pool: &'a PgPool,
+    instrument: &'a str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> BoxStream<'a, Result<Vec<Tick>, sqlx::Error>> {
+    stream::try_unfold(Some((start, -1)), move |after| async move {
+        let Some(after) = after else {
+            return Ok(None);
+        };
+        let rows = tick_chunk(pool, instrument, end, after).await?;
+        let next = match rows.last() {
+            Some(last) if rows.len() as i64 == STREAM_CHUNK_SIZE => {
+                Some((last.get("timestamp"), last.get("seq")))
+            }
+            _ => None,
+        };
+        let ticks: Vec<Tick> = rows.iter().map(tick_from_row).collect();
+        Ok((!ticks.is_empty()).then_some((ticks, next)))
+    })
+    .boxed()
+}
+
+/// The next chunk of tick rows after `(timestamp, seq)`.
+async fn tick_chunk(
+    pool: &PgPool,
+    instrument: &str,
+    end: DateTime<Utc>,
+    (after_ts, after_seq): (DateTime<Utc>, i32),
+) -> Result<Vec<PgRow>, sqlx::Error> {
+    sqlx::query(
+        "SELECT seq, instrument, timestamp, bid, ask, last_price, volume
+         FROM ticks
+         WHERE instrument = $1 AND timestamp >= $2 AND timestamp <= $3
+           AND (timestamp, seq) > ($2, $4)
+         ORDER BY timestamp ASC, seq ASC
+         LIMIT $5",
+    )
+    .bind(instrument)
+    .bind(after_ts)
+    .bind(end)
+    .bind(after_seq)
+    .bind(STREAM_CHUNK_SIZE)
+    .fetch_all(pool)
+    .await
+}
 
-    Ok(ticks)
+fn tick_from_row(r: &PgRow) -> Tick {
+    Tick {
+        instrument: r.get("instrument"),
+        timestamp: r.get("timestamp"),
+        bid: r.get("bid"),
+        ask: r.get("ask"),
+        last: r.get::<Decimal, _>("last_price"),
+        volume: r.get("volume"),
+    }
+}
+
+/// Insert or update bars of one timeframe in a single transaction, using
+/// multi-row inserts.
+///
+/// Returns the number of rows written.
+pub async fn insert_bars(
+    pool: &PgPool,
+    bars: &[Bar],
+    timeframe: Timeframe,
+) -> Result<u64, sqlx::Error> {
+    insert_bars_with_progress(pool, bars, timeframe, |_, _| {}).await
 }
 
-/// Insert bars into the database.
-pub async fn insert_bars(pool: &PgPool, bars: &[Bar]) -> Result<u64, sqlx::Error> {
+/// Like [`insert_bars`], calling `progress(written, total)` after each batch.
+pub async fn insert_bars_with_progress(
+    pool: &PgPool,
+    bars: &[Bar],
+    timeframe: Timeframe,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<u64, sqlx::Error> {
+    let timeframe = timeframe.to_string();
+    let unique = dedupe_bars(bars);
+    let mut tx = pool.begin().await?;
     let mut count = 0u64;
-    for bar in bars {
-        sqlx::query(
-            "INSERT INTO bars (instrument, timestamp, open, high, low, close, volume)
-             VALUES ($1, $2, $3, $4, $5, $6, $7)
-             ON CONFLICT (instrument, timestamp) DO UPDATE
+    let mut done = 0;
+    for chunk in unique.chunks(INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "INSERT INTO bars (instrument, timeframe, timestamp, open, high, low, close, volume) ",
+        );
+        query.push_values(chunk, |mut row, bar| {
+            row.push_bind(&bar.instrument)
+                .push_bind(&timeframe)
+                .push_bind(bar.timestamp)
+                .push_bind(bar.open)
+                .push_bind(bar.high)
+                .push_bind(bar.low)
+                .push_bind(bar.close)
+                .push_bind(bar.volume);
+        });
+        query.push(
+            " ON CONFLICT (instrument, timeframe, timestamp) DO UPDATE
              SET open = EXCLUDED.open, high = EXCLUDED.high,
                  low = EXCLUDED.low, close = EXCLUDED.close, volume = EXCLUDED.volume",
-        )
-        .bind(&bar.instrument)
-        .bind(bar.timestamp)
-        .bind(bar.open)
-        .bind(bar.high)
-        .bind(bar.low)
-        .bind(bar.close)
-        .bind(bar.volume)
-        .execute(pool)
-        .await?;
-        count += 1;
+        );
+        count += query.build().execute(&mut *tx).await?.rows_affected();
+
+        done += chunk.len();
+        progress(done, unique.len());
     }
+    tx.commit().await?;
     Ok(count)
 }
 
-/// Insert ticks into the database.
+/// One bar per instrument and timestamp, in first-seen order, with later
+/// duplicates replacing earlier ones: one statement can't upsert a key twice.
+fn dedupe_bars(bars: &[Bar]) -> Vec<&Bar> {
+    let mut index: HashMap<(&str, DateTime<Utc>), usize> = HashMap::new();
+    let mut unique: Vec<&Bar> = Vec::with_capacity(bars.len());
+    for bar in bars {
+        match index.entry((bar.instrument.as_str(), bar.timestamp)) {
+            Entry::Occupied(e) => unique[*e.get()] = bar,
+            Entry::Vacant(e) => {
+                e.insert(unique.len());
+                unique.push(bar);
+            }
+        }
+    }
+    unique
+}
+
+/// Sort ticks by instrument and time and number those sharing a timestamp
+/// from 0, keeping their order within it.
+fn number_ticks(ticks: &[Tick]) -> Vec<(&Tick, i32)> {
+    // Stable sort, so ticks at the same time keep their order
+    let mut ordered: Vec<&Tick> = ticks.iter().collect();
+    ordered.sort_by(|a, b| (&a.instrument, a.timestamp).cmp(&(&b.instrument, b.timestamp)));
+    let mut numbered: Vec<(&Tick, i32)> = Vec::with_capacity(ordered.len());
+    for tick in ordered {
+        let seq = match numbered.last() {
+            Some((prev, seq))
+                if prev.instrument == tick.instrument && prev.timestamp == tick.timestamp =>
+            {
+                seq + 1
+            }
+            _ => 0,
+        };
+        numbered.push((tick, seq));
+    }
+    numbered
+}
+
+/// First and last timestamp of each instrument's ticks, from the output of
+/// [`number_ticks`].
+fn tick_ranges<'a>(numbered: &[(&'a Tick, i32)]) -> Vec<(&'a str, DateTime<Utc>, DateTime<Utc>)> {
+    let mut ranges: Vec<(&str, DateTime<Utc>, DateTime<Utc>)> = Vec::new();
+    for (tick, _) in numbered {
+        match ranges.last_mut() {
+            Some((instrument, _, end)) if *instrument == tick.instrument => *end = tick.timestamp,
+            _ => ranges.push((&tick.instrument, tick.timestamp, tick.timestamp)),
+        }
+    }
+    ranges
+}
+
+/// Insert ticks in a single transaction, using multi-row inserts.
+///
+/// The ticks replace everything stored for their instrument between their
+/// first and last timestamp, so re-importing a file, or one overlapping it,
+/// leaves exactly its ticks in that range. Ticks sharing an instrument and
+/// timestamp are numbered in the order given. Returns the number of rows
+/// written.
 pub async fn insert_ticks(pool: &PgPool, ticks: &[Tick]) -> Result<u64, sqlx::Error> {
+    insert_ticks_with_progress(pool, ticks, |_, _| {}).await
+}
+
+/// Like [`insert_ticks`], calling `progress(written, total)` after each batch.
+pub async fn insert_ticks_with_progress(
+    pool: &PgPool,
+    ticks: &[Tick],
+    mut progress: impl FnMut(usize, usize),
+) -> Result<u64, sqlx::Error> {
+    let numbered = number_ticks(ticks);
+
+    let mut tx = pool.begin().await?;
+    for (instrument, start, end) in tick_ranges(&numbered) {
+        sqlx::query("DELETE FROM ticks WHERE instrument = $1 AND timestamp BETWEEN $2 AND $3")
+            .bind(instrument)
+            .bind(start)
+            .bind(end)
+            .execute(&mut *tx)
+            .await?;
+    }
     let mut count = 0u64;
-    for tick in ticks {
-        sqlx::query(
-            "INSERT INTO ticks (instrument, timestamp, bid, ask, last_price, volume)
-             VALUES ($1, $2, $3, $4, $5, $6)",
-        )
-        .bind(&tick.instrument)
-        .bind(tick.timestamp)
-        .bind(tick.bid)
-        .bind(tick.ask)
-        .bind(tick.last)
-        .bind(tick.volume)
-        .execute(pool)
-        .await?;
-        count += 1;
+    let mut done = 0;
+    for chunk in numbered.chunks(INSERT_CHUNK_SIZE) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "INSERT INTO ticks (instrument, timestamp, seq, bid, ask, last_price, volume) ",
+        );
+        query.push_values(chunk, |mut row, (tick, seq)| {
+            row.push_bind(&tick.instrument)
+                .push_bind(tick.timestamp)
+                .push_bind(*seq)
+                .push_bind(tick.bid)
+                .push_bind(tick.ask)
+                .push_bind(tick.last)
+                .push_bind(tick.volume);
+        });
+        count += query.build().execute(&mut *tx).await?.rows_affected();
+
+        done += chunk.len();
+        progress(done, ticks.len());
     }
+    tx.commit().await?;
     Ok(count)
 }
 
-/// Get available instruments from the bars table.
-pub async fn available_instruments(pool: &PgPool) -> Result<Vec<String>, sqlx::Error> {
-    let rows = sqlx::query("SELECT DISTINCT instrument FROM bars ORDER BY instrument")
-        .fetch_all(pool)
-        .await?;
-    Ok(rows.iter().map(|r| r.get("instrument")).collect())
+/// List the instruments and timeframes in the bars table with their date ranges.
+pub async fn available_instruments(pool: &PgPool) -> Result<Vec<StoredSeries>, sqlx::Error> {
+    let rows = sqlx::query(
+        "SELECT instrument, timeframe, MIN(timestamp) AS start, MAX(timestamp) AS end_ts,
+                COUNT(*) AS bars
+         FROM bars
+         GROUP BY instrument, timeframe
+         ORDER BY instrument, timeframe",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.iter()
+        .map(|r| {
+            let timeframe: String = r.get("timeframe");
+            Ok(StoredSeries {
+                instrument: r.get("instrument"),
+                timeframe: timeframe
+                    .parse()
+                    .map_err(|e: String| sqlx::Error::ColumnDecode {
+                        index: "timeframe".to_string(),
+                        source: e.into(),
+                    })?,
+                start: r.get("start"),
+                end: r.get("end_ts"),
+                bars: r.get("bars"),
+            })
+        })
+        .collect()
+}
+
+/// Timestamps of the oldest and newest stored bars of one timeframe, if any.
+pub async fn stored_bar_range(
+    pool: &PgPool,
+    instrument: &str,
+    timeframe: Timeframe,
+) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>, sqlx::Error> {
+    let (first, last): (Option<DateTime<Utc>>, Option<DateTime<Utc>>) = sqlx::query_as(
+        "SELECT MIN(timestamp), MAX(timestamp) FROM bars WHERE instrument = $1 AND timeframe = $2",
+    )
+    .bind(instrument)
+    .bind(timeframe.to_string())
+    .fetch_one(pool)
+    .await?;
+    Ok(first.zip(last))
+}
+
+/// Timestamps of the oldest and newest stored ticks, if any.
+pub async fn stored_tick_range(
+    pool: &PgPool,
+    instrument: &str,
+) -> Result<Option<(DateTime<Utc>, DateTime<Utc>)>, sqlx::Error> {
+    let (first, last): (Option<DateTime<Utc>>, Option<DateTime<Utc>>) =
+        sqlx::query_as("SELECT MIN(timestamp), MAX(timestamp) FROM ticks WHERE instrument = $1")
+            .bind(instrument)
+            .fetch_one(pool)
+            .await?;
+    Ok(first.zip(last))
 }
 
-/// Save a backtest result.
+/// Save a backtest result, with its trades and order log under the result's ID.
 pub async fn save_backtest_result(
     pool: &PgPool,
     result: &BacktestResult,
 ) -> Result<(), sqlx::Error> {
     let trades_json = serde_json::to_value(&result.trades).unwrap_or_default();
     let equity_json = serde_json::to_value(&result.equity_curve).unwrap_or_default();
+    let risk_events_json = serde_json::to_value(&result.risk_events).unwrap_or_default();
+    let monthly_json = serde_json::to_value(&result.monthly_returns).unwrap_or_default();
+    let daily_json = serde_json::to_value(&result.daily_returns).unwrap_or_default();
 
+    let mut tx = pool.begin().await?;
     sqlx::query(
         "INSERT INTO backtest_results (
-            id, strategy_id, instrument, start_date, end_date,
+            id, strategy_id, instrument, timeframe, start_date, end_date,
             initial_balance, final_balance, total_trades, winning_trades, losing_trades,
             gross_profit, gross_loss, net_profit, max_drawdown, max_drawdown_percent,
-            win_rate, profit_factor, sharpe_ratio, sortino_ratio,
+            win_rate, profit_factor, sharpe_ratio, sortino_ratio, volatility,
             avg_trade_pnl, avg_winner, avg_loser, total_commission,
-            equity_curve, trades
+            calmar_ratio, mar_ratio, expectancy, largest_winner, largest_loser,
+            max_consecutive_wins, max_consecutive_losses,
+            max_drawdown_duration_hours, max_drawdown_recovery_hours,
+            avg_trade_duration_hours, time_in_market_percent, avg_mae, avg_mfe,
+            equity_curve, trades, risk_events, monthly_returns, daily_returns
         ) VALUES (
             $1, $2, $3, $4, $5, $6, $7, $8, $9, $10,
             $11, $12, $13, $14, $15, $16, $17, $18, $19,
-            $20, $21, $22, $23, $24, $25
+            $20, $21, $22, $23, $24, $25, $26, $27, $28, $29,
+            $30, $31, $32, $33, $34, $35, $36, $37, $38, $39, $40, $41, $42, $43
         )",
     )
     .bind(result.id)
     .bind(&result.strategy_id)
     .bind(&result.instrument)
+    .bind(result.timeframe.map(|t| t.to_string()))
     .bind(result.start_date)
     .bind(result.end_date)
     .bind(result.initial_balance)
@@ -173,14 +473,728 @@ pub async fn save_backtest_result(
     .bind(result.profit_factor)
     .bind(result.sharpe_ratio)
     .bind(result.sortino_ratio)
+    .bind(result.volatility)
     .bind(result.avg_trade_pnl)
     .bind(result.avg_winner)
     .bind(result.avg_loser)
     .bind(result.total_commission)
+    .bind(result.calmar_ratio)
+    .bind(result.mar_ratio)
+    .bind(result.expectancy)
+    .bind(result.largest_winner)
+    .bind(result.largest_loser)
+    .bind(result.max_consecutive_wins as i64)
+    .bind(result.max_consecutive_losses as i64)
+    .bind(result.max_drawdown_duration_hours)
+    .bind(result.max_drawdown_recovery_hours)
+    .bind(result.avg_trade_duration_hours)
+    .bind(result.time_in_market_percent)
+    .bind(result.avg_mae)
+    .bind(result.avg_mfe)
     .bind(&equity_json)
     .bind(&trades_json)
-    .execute(pool)
+    .bind(&risk_events_json)
+    .bind(&monthly_json)
+    .bind(&daily_json)
+    .execute(&mut *tx)
     .await?;
 
+    write_order_updates(&mut tx, result.id, &result.order_log).await?;
+    write_trades(&mut tx, result.id, &result.trades).await?;
+    tx.commit().await?;
+
     Ok(())
 }
+
+/// Which orders and trades to load; `None` fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionFilter {
+    pub strategy_id: Option<String>,
+    pub instrument: Option<String>,
+    /// Backtest result ID or live session ID.
+    pub run_id: Option<Uuid>,
+    /// Orders created, order updates made, or trades closed at or after this time.
+    pub start: Option<DateTime<Utc>>,
+    /// ... and at or before this time.
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl ExecutionFilter {
+    /// Append a WHERE clause on `table`'s columns, with `time_column` for the date range.
+    fn push_where(&self, query: &mut QueryBuilder<Postgres>, table: &str, time_column: &str) {
+        query.push(" WHERE TRUE");
+        if let Some(strategy_id) = &self.strategy_id {
+            query
+                .push(format!(" AND {}.strategy_id = ", table))
+                .push_bind(strategy_id.clone());
+        }
+        if let Some(instrument) = &self.instrument {
+            query
+                .push(format!(" AND {}.instrument = ", table))
+                .push_bind(instrument.clone());
+        }
+        if let Some(run_id) = self.run_id {
+            query
+                .push(format!(" AND {}.run_id = ", table))
+                .push_bind(run_id);
+        }
+        if let Some(start) = self.start {
+            query
+                .push(format!(" AND {} >= ", time_column))
+                .push_bind(start);
+        }
+        if let Some(end) = self.end {
+            query
+                .push(format!(" AND {} <= ", time_column))
+                .push_bind(end);
+        }
+    }
+}
+
+/// Record order state changes from a run (a backtest or a live session).
+///
+/// Each change is appended to `order_events`, and `orders` is brought up to
+/// each order's latest state, so updates must be recorded in the order they
+/// happened. Returns the number of events written.
+pub async fn record_order_updates(
+    pool: &PgPool,
+    run_id: Uuid,
+    updates: &[OrderUpdate],
+) -> Result<u64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let count = write_order_updates(&mut tx, run_id, updates).await?;
+    tx.commit().await?;
+    Ok(count)
+}
+
+/// Record closed trades from a run. Trades already stored are left alone.
+/// Returns the number of trades written.
+pub async fn record_trades(
+    pool: &PgPool,
+    run_id: Uuid,
+    trades: &[Trade],
+) -> Result<u64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let count = write_trades(&mut tx, run_id, trades).await?;
+    tx.commit().await?;
+    Ok(count)
+}
+
+async fn write_order_updates(
+    conn: &mut PgConnection,
+    run_id: Uuid,
+    updates: &[OrderUpdate],
+) -> Result<u64, sqlx::Error> {
+    // Latest state of each order; one upsert can't touch a row twice
+    let mut latest: Vec<&Order> = Vec::new();
+    let mut index: HashMap<Uuid, usize> = HashMap::new();
+    for update in updates {
+        match index.entry(update.order.id) {
+            Entry::Occupied(e) => latest[*e.get()] = &update.order,
+            Entry::Vacant(e) => {
+                e.insert(latest.len());
+                latest.push(&update.order);
+            }
+        }
+    }
+
+    for chunk in latest.chunks(LOG_CHUNK_SIZE) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "INSERT INTO orders (id, run_id, instrument, side, order_type, quantity,
+                 filled_quantity, price, stop_price, trail, time_in_force, status,
+                 strategy_id, broker_order_id, created_at, updated_at) ",
+        );
+        let rows = chunk
+            .iter()
+            .map(|&order| {
+                Ok((
+                    order,
+                    enum_name(&order.side)?,
+                    enum_name(&order.order_type)?,
+                    enum_name(&order.status)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+        query.push_values(rows, |mut row, (order, side, order_type, status)| {
+            row.push_bind(order.id)
+                .push_bind(run_id)
+                .push_bind(&order.instrument)
+                .push_bind(side)
+                .push_bind(order_type)
+                .push_bind(order.quantity)
+                .push_bind(order.filled_quantity)
+                .push_bind(order.price)
+                .push_bind(order.stop_price)
+                .push_bind(
+                    order
+                        .trail
+                        .map(|t| serde_json::to_value(t).unwrap_or_default()),
+                )
+                .push_bind(serde_json::to_value(order.time_in_force).unwrap_or_default())
+                .push_bind(status)
+                .push_bind(&order.strategy_id)
+                .push_bind(&order.broker_order_id)
+                .push_bind(order.created_at)
+                .push_bind(order.updated_at);
+        });
+        query.push(
+            " ON CONFLICT (id) DO UPDATE
+             SET quantity = EXCLUDED.quantity, filled_quantity = EXCLUDED.filled_quantity,
+                 price = EXCLUDED.price, stop_price = EXCLUDED.stop_price,
+                 trail = EXCLUDED.trail, status = EXCLUDED.status,
+                 broker_order_id = EXCLUDED.broker_order_id, updated_at = EXCLUDED.updated_at",
+        );
+        query.build().execute(&mut *conn).await?;
+    }
+
+    let mut count = 0;
+    for chunk in updates.chunks(LOG_CHUNK_SIZE) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "INSERT INTO order_events (order_id, kind, status, quantity, filled_quantity,
+                 price, stop_price, fill_quantity, fill_price, commission, reason, timestamp) ",
+        );
+        let rows = chunk
+            .iter()
+            .map(|update| {
+                Ok((
+                    update,
+                    enum_name(&update.kind)?,
+                    enum_name(&update.order.status)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+        query.push_values(rows, |mut row, (update, kind, status)| {
+            let order = &update.order;
+            row.push_bind(order.id)
+                .push_bind(kind)
+                .push_bind(status)
+                .push_bind(order.quantity)
+                .push_bind(order.filled_quantity)
+                .push_bind(order.price)
+                .push_bind(order.stop_price)
+                .push_bind(update.fill.as_ref().map(|f| f.quantity))
+                .push_bind(update.fill.as_ref().map(|f| f.price))
+                .push_bind(update.fill.as_ref().map(|f| f.commission))
+                .push_bind(&update.reason)
+                .push_bind(update.timestamp);
+        });
+        count += query.build().execute(&mut *conn).await?.rows_affected();
+    }
+    Ok(count)
+}
+
+async fn write_trades(
+    conn: &mut PgConnection,
+    run_id: Uuid,
+    trades: &[Trade],
+) -> Result<u64, sqlx::Error> {
+    let mut count = 0;
+    for chunk in trades.chunks(LOG_CHUNK_SIZE) {
+        let mut query = QueryBuilder::<Postgres>::new(
+            "INSERT INTO trades (id, run_id, instrument, side, quantity, entry_price,
+                 exit_price, pnl, commission, entry_time, exit_time, strategy_id, mae, mfe) ",
+        );
+        let rows = chunk
+            .iter()
+            .map(|trade| Ok((trade, enum_name(&trade.side)?)))
+            .collect::<Result<Vec<_>, sqlx::Error>>()?;
+        query.push_values(rows, |mut row, (trade, side)| {
+            row.push_bind(trade.id)
+                .push_bind(run_id)
+                .push_bind(&trade.instrument)
+                .push_bind(side)
+                .push_bind(trade.quantity)
+                .push_bind(trade.entry_price)
+                .push_bind(trade.exit_price)
+                .push_bind(trade.pnl)
+                .push_bind(trade.commission)
+                .push_bind(trade.entry_time)
+                .push_bind(trade.exit_time)
+                .push_bind(&trade.strategy_id)
+                .push_bind(trade.mae)
+                .push_bind(trade.mfe);
+        });
+        query.push(" ON CONFLICT (id) DO NOTHING");
+        count += query.build().execute(&mut *conn).await?.rows_affected();
+    }
+    Ok(count)
+}
+
+/// Load orders in their latest state, oldest first.
+pub async fn load_orders(
+    pool: &PgPool,
+    filter: &ExecutionFilter,
+) -> Result<Vec<Order>, sqlx::Error> {
+    let mut query = QueryBuilder::<Postgres>::new(
+        "SELECT id, instrument, side, order_type, quantity, filled_quantity, price,
+             stop_price, trail, time_in_force, status, strategy_id, broker_order_id,
+             created_at, updated_at
+         FROM orders o",
+    );
+    filter.push_where(&mut query, "o", "o.created_at");
+    query.push(" ORDER BY o.created_at, o.id");
+
+    let rows = query.build().fetch_all(pool).await?;
+    rows.iter().map(order_from_row).collect()
+}
+
+/// Load the order log: every state change of the matching orders, in the
+/// order they happened.
+pub async fn load_order_updates(
+    pool: &PgPool,
+    filter: &ExecutionFilter,
+) -> Result<Vec<OrderUpdate>, sqlx::Error> {
+    let mut query = QueryBuilder::<Postgres>::new(
+        "SELECT o.id, o.instrument, o.side, o.order_type, e.quantity, e.filled_quantity,
+             e.price, e.stop_price, o.trail, o.time_in_force, e.status, o.strategy_id,
+             o.broker_order_id, o.created_at, e.timestamp AS updated_at,
+             e.kind, e.fill_quantity, e.fill_price, e.commission, e.reason
+         FROM order_events e
+         JOIN orders o ON o.id = e.order_id",
+    );
+    filter.push_where(&mut query, "o", "e.timestamp");
+    query.push(" ORDER BY e.id");
+
+    let rows = query.build().fetch_all(pool).await?;
+    rows.iter()
+        .map(|r| {
+            let order = order_from_row(r)?;
+            let fill = match (r.try_get("fill_quantity")?, r.try_get("fill_price")?) {
+                (Some(quantity), Some(price)) => Some(Fill {
+                    order_id: order.id,
+                    instrument: order.instrument.clone(),
+                    side: order.side,
+                    quantity,
+                    price,
+                    commission: r
+                        .try_get::<Option<Decimal>, _>("commission")?
+                        .unwrap_or_default(),
+                    timestamp: order.updated_at,
+                }),
+                _ => None,
+            };
+            Ok(OrderUpdate {
+                kind: parse_enum(r.try_get("kind")?)?,
+                timestamp: order.updated_at,
+                fill,
+                reason: r.try_get("reason")?,
+                order,
+            })
+        })
+        .collect()
+}
+
+/// Load closed trades, oldest exit first.
+pub async fn load_trades(
+    pool: &PgPool,
+    filter: &ExecutionFilter,
+) -> Result<Vec<Trade>, sqlx::Error> {
+    let mut query = QueryBuilder::<Postgres>::new(
+        "SELECT id, instrument, side, quantity, entry_price, exit_price, pnl, commission,
+             entry_time, exit_time, strategy_id, mae, mfe
+         FROM trades t",
+    );
+    filter.push_where(&mut query, "t", "t.exit_time");
+    query.push(" ORDER BY t.exit_time, t.id");
+
+    let rows = query.build().fetch_all(pool).await?;
+    rows.iter()
+        .map(|r| {
+            Ok(Trade {
+                id: r.try_get("id")?,
+                instrument: r.try_get("instrument")?,
+                side: parse_enum(r.try_get("side")?)?,
+                quantity: r.try_get("quantity")?,
+                entry_price: r.try_get("entry_price")?,
+                exit_price: r.try_get("exit_price")?,
+                pnl: r.try_get("pnl")?,
+                commission: r.try_get("commission")?,
+                entry_time: r.try_get("entry_time")?,
+                exit_time: r.try_get("exit_time")?,
+                strategy_id: r.try_get("strategy_id")?,
+                mae: r.try_get("mae")?,
+                mfe: r.try_get("mfe")?,
+            })
+        })
+        .collect()
+}
+
+fn order_from_row(r: &PgRow) -> Result<Order, sqlx::Error> {
+    Ok(Order {
+        id: r.try_get("id")?,
+        instrument: r.try_get("instrument")?,
+        side: parse_enum(r.try_get("side")?)?,
+        order_type: parse_enum(r.try_get("order_type")?)?,
+        quantity: r.try_get("quantity")?,
+        filled_quantity: r.try_get("filled_quantity")?,
+        price: r.try_get("price")?,
+        stop_price: r.try_get("stop_price")?,
+        trail: r
+            .try_get::<Option<Value>, _>("trail")?
+            .map(from_json)
+            .transpose()?,
+        time_in_force: from_json(r.try_get("time_in_force")?)?,
+        status: parse_enum(r.try_get("status")?)?,
+        created_at: r.try_get("created_at")?,
+        updated_at: r.try_get("updated_at")?,
+        strategy_id: r.try_get("strategy_id")?,
+        broker_order_id: r.try_get("broker_order_id")?,
+    })
+}
+
+/// The serde name of a unit enum variant (e.g. `"buy"`), as stored in text columns.
+fn enum_name<T: Serialize>(value: &T) -> Result<String, sqlx::Error> {
+    match serde_json::to_value(value) {
+        Ok(Value::String(name)) => Ok(name),
+        Ok(other) => Err(sqlx::Error::Encode(
+            format!("not a unit enum variant: {}", other).into(),
+        )),
+        Err(e) => Err(sqlx::Error::Encode(Box::new(e))),
+    }
+}
+
+fn parse_enum<T: DeserializeOwned>(name: String) -> Result<T, sqlx::Error> {
+    from_json(Value::String(name))
+}
+
+/// The summary metric columns of `backtest_results`, comma separated.
+fn metric_columns() -> String {
+    BacktestResult::metric_names()
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn from_json<T: DeserializeOwned>(value: Value) -> Result<T, sqlx::Error> {
+    serde_json::from_value(value).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+/// A threshold on a summary metric, parsed from e.g. `sharpe_ratio>=1.5`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricFilter {
+    /// One of [`BacktestResult::METRICS`].
+    pub metric: String,
+    /// `<`, `<=`, `>` or `>=`.
+    pub comparison: String,
+    pub value: Decimal,
+}
+
+impl std::str::FromStr for MetricFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let at = s
+            .find(['<', '>'])
+            .ok_or_else(|| format!("Expected <metric><op><value>, e.g. sharpe_ratio>=1: {}", s))?;
+        let (metric, rest) = s.split_at(at);
+        let split = if rest[1..].starts_with('=') { 2 } else { 1 };
+        let (comparison, value) = rest.split_at(split);
+        let metric = metric.trim();
+        if !BacktestResult::metric_names().any(|m| m == metric) {
+            return Err(format!(
+                "Unknown metric: {} (available: {})",
+                metric,
+                metric_columns()
+            ));
+        }
+        let value = value
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid value in {}: {}", s, e))?;
+        Ok(Self {
+            metric: metric.to_string(),
+            comparison: comparison.to_string(),
+            value,
+        })
+    }
+}
+
+/// Which saved backtests to list; `None` fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct BacktestFilter {
+    pub strategy_id: Option<String>,
+    pub instrument: Option<String>,
+    /// Backtests whose test period ends at or after this time.
+    pub start: Option<DateTime<Utc>>,
+    /// Backtests whose test period starts at or before this time.
+    pub end: Option<DateTime<Utc>>,
+    /// Metric thresholds, all of which must hold.
+    pub metrics: Vec<MetricFilter>,
+    /// Most results to return, newest first.
+    pub limit: Option<i64>,
+}
+
+/// List saved backtests, newest first.
+///
+/// The results carry their summary metrics only: the equity curve, trades
+/// and order log are left empty. Use [`load_backtest_result`] for those.
+pub async fn list_backtest_results(
+    pool: &PgPool,
+    filter: &BacktestFilter,
+) -> Result<Vec<BacktestResult>, sqlx::Error> {
+    let mut query = QueryBuilder::<Postgres>::new(format!(
+        "SELECT id, strategy_id, instrument, timeframe, start_date, end_date, initial_balance, {}
+         FROM backtest_results WHERE TRUE",
+        metric_columns()
+    ));
+    if let Some(strategy_id) = &filter.strategy_id {
+        query
+            .push(" AND strategy_id = ")
+            .push_bind(strategy_id.clone());
+    }
+    if let Some(instrument) = &filter.instrument {
+        query
+            .push(" AND instrument = ")
+            .push_bind(instrument.clone());
+    }
+    if let Some(start) = filter.start {
+        query.push(" AND end_date >= ").push_bind(start);
+    }
+    if let Some(end) = filter.end {
+        query.push(" AND start_date <= ").push_bind(end);
+    }
+    for threshold in &filter.metrics {
+        // Both are checked against fixed lists, so safe to splice in
+        let known_metric = BacktestResult::metric_names().any(|m| m == threshold.metric);
+        let known_comparison = ["<", "<=", ">", ">="].contains(&threshold.comparison.as_str());
+        if !known_metric || !known_comparison {
+            return Err(sqlx::Error::Protocol(format!(
+                "Invalid metric filter: {}{}",
+                threshold.metric, threshold.comparison
+            )));
+        }
+        query
+            .push(format!(
+                " AND {} {} ",
+                threshold.metric, threshold.comparison
+            ))
+            .push_bind(threshold.value);
+    }
+    query.push(" ORDER BY created_at DESC, id");
+    if let Some(limit) = filter.limit {
+        query.push(" LIMIT ").push_bind(limit);
+    }
+
+    let rows = query.build().fetch_all(pool).await?;
+    rows.iter().map(backtest_from_row).collect()
+}
+
+/// Load a saved backtest with its equity curve, trades and order log.
+pub async fn load_backtest_result(
+    pool: &PgPool,
+    id: Uuid,
+) -> Result<Option<BacktestResult>, sqlx::Error> {
+    let row = sqlx::query(&format!(
+        "SELECT id, strategy_id, instrument, timeframe, start_date, end_date, initial_balance, {},
+             equity_curve, trades, risk_events, monthly_returns, daily_returns
+         FROM backtest_results WHERE id = $1",
+        metric_columns()
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    let mut result = backtest_from_row(&row)?;
+    if let Some(curve) = row.try_get::<Option<Value>, _>("equity_curve")? {
+        result.equity_curve = from_json(curve)?;
+    }
+    if let Some(trades) = row.try_get::<Option<Value>, _>("trades")? {
+        result.trades = from_json(trades)?;
+    }
+    if let Some(events) = row.try_get::<Option<Value>, _>("risk_events")? {
+        result.risk_events = from_json(events)?;
+    }
+    if let Some(months) = row.try_get::<Option<Value>, _>("monthly_returns")? {
+        result.monthly_returns = from_json(months)?;
+    }
+    if let Some(days) = row.try_get::<Option<Value>, _>("daily_returns")? {
+        result.daily_returns = from_json(days)?;
+    }
+    let run = ExecutionFilter {
+        run_id: Some(id),
+        ..Default::default()
+    };
+    result.order_log = load_order_updates(pool, &run).await?;
+    Ok(Some(result))
+}
+
+/// Delete a saved backtest with its recorded orders and trades. Returns
+/// whether it existed.
+pub async fn delete_backtest_result(pool: &PgPool, id: Uuid) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    // Order events go with their orders
+    sqlx::query("DELETE FROM orders WHERE run_id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM trades WHERE run_id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    let deleted = sqlx::query("DELETE FROM backtest_results WHERE id = $1")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+    Ok(deleted > 0)
+}
+
+fn backtest_from_row(r: &PgRow) -> Result<BacktestResult, sqlx::Error> {
+    let count = |name: &str| -> Result<usize, sqlx::Error> {
+        Ok(r.try_get::<i64, _>(name)?.max(0) as usize)
+    };
+    let timeframe = r
+        .try_get::<Option<String>, _>("timeframe")?
+        .map(|t| t.parse())
+        .transpose()
+        .map_err(|e: String| sqlx::Error::ColumnDecode {
+            index: "timeframe".to_string(),
+            source: e.into(),
+        })?;
+    Ok(BacktestResult {
+        id: r.try_get("id")?,
+        strategy_id: r.try_get("strategy_id")?,
+        instrument: r.try_get("instrument")?,
+        timeframe,
+        start_date: r.try_get("start_date")?,
+        end_date: r.try_get("end_date")?,
+        initial_balance: r.try_get("initial_balance")?,
+        final_balance: r.try_get("final_balance")?,
+        total_trades: count("total_trades")?,
+        winning_trades: count("winning_trades")?,
+        losing_trades: count("losing_trades")?,
+        gross_profit: r.try_get("gross_profit")?,
+        gross_loss: r.try_get("gross_loss")?,
+        net_profit: r.try_get("net_profit")?,
+        max_drawdown: r.try_get("max_drawdown")?,
+        max_drawdown_percent: r.try_get("max_drawdown_percent")?,
+        win_rate: r.try_get("win_rate")?,
+        profit_factor: r.try_get("profit_factor")?,
+        sharpe_ratio: r.try_get("sharpe_ratio")?,
+        sortino_ratio: r.try_get("sortino_ratio")?,
+        volatility: r.try_get("volatility")?,
+        avg_trade_pnl: r.try_get("avg_trade_pnl")?,
+        avg_winner: r.try_get("avg_winner")?,
+        avg_loser: r.try_get("avg_loser")?,
+        total_commission: r.try_get("total_commission")?,
+        calmar_ratio: r.try_get("calmar_ratio")?,
+        mar_ratio: r.try_get("mar_ratio")?,
+        expectancy: r.try_get("expectancy")?,
+        largest_winner: r.try_get("largest_winner")?,
+        largest_loser: r.try_get("largest_loser")?,
+        max_consecutive_wins: count("max_consecutive_wins")?,
+        max_consecutive_losses: count("max_consecutive_losses")?,
+        max_drawdown_duration_hours: r.try_get("max_drawdown_duration_hours")?,
+        max_drawdown_recovery_hours: r.try_get("max_drawdown_recovery_hours")?,
+        avg_trade_duration_hours: r.try_get("avg_trade_duration_hours")?,
+        time_in_market_percent: r.try_get("time_in_market_percent")?,
+        avg_mae: r.try_get("avg_mae")?,
+        avg_mfe: r.try_get("avg_mfe")?,
+        monthly_returns: Vec::new(),
+        daily_returns: Vec::new(),
+        equity_curve: Vec::new(),
+        trades: Vec::new(),
+        order_log: Vec::new(),
+        risk_events: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_parse_metric_filter() {
+        let filter: MetricFilter = "sharpe_ratio>=1.5".parse().unwrap();
+        assert_eq!(
+            filter,
+            MetricFilter {
+                metric: "sharpe_ratio".to_string(),
+                comparison: ">=".to_string(),
+                value: dec!(1.5),
+            }
+        );
+        let filter: MetricFilter = "max_drawdown_percent < 10".parse().unwrap();
+        assert_eq!((filter.comparison.as_str(), filter.value), ("<", dec!(10)));
+
+        assert!("sharpe>1".parse::<MetricFilter>().is_err());
+        assert!("net_profit=1".parse::<MetricFilter>().is_err());
+        assert!("net_profit>lots".parse::<MetricFilter>().is_err());
+    }
+
+    fn tick(instrument: &str, second: i64, last: Decimal) -> Tick {
+        Tick {
+            instrument: instrument.to_string(),
+            timestamp: "2024-03-05T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+                + chrono::Duration::seconds(second),
+            bid: last,
+            ask: last,
+            last,
+            volume: dec!(1),
+        }
+    }
+
+    #[test]
+    fn test_enum_name_rejects_non_unit_values() {
+        assert_eq!(enum_name(&propbot_core::Side::Buy).unwrap(), "buy");
+        assert!(matches!(enum_name(&1u8), Err(sqlx::Error::Encode(_))));
+    }
+
+    #[test]
+    fn test_dedupe_bars_keeps_first_position_and_last_values() {
+        let bar = |minute: i64, close: Decimal| Bar {
+            instrument: "ES".to_string(),
+            timestamp: "2024-03-05T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+                + chrono::Duration::minutes(minute),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: dec!(1),
+        };
+        let bars = vec![bar(0, dec!(1)), bar(1, dec!(2)), bar(0, dec!(3))];
+
+        let unique: Vec<_> = dedupe_bars(&bars).iter().map(|b| b.close).collect();
+        assert_eq!(unique, vec![dec!(3), dec!(2)]);
+    }
+
+    #[test]
+    fn test_ticks_are_numbered_within_a_timestamp_in_file_order() {
+        let ticks = vec![
+            tick("NQ", 0, dec!(9)),
+            tick("ES", 1, dec!(1)),
+            tick("ES", 0, dec!(2)),
+            tick("ES", 1, dec!(3)),
+            tick("ES", 1, dec!(4)),
+        ];
+
+        let numbered = number_ticks(&ticks);
+        let summary: Vec<_> = numbered
+            .iter()
+            .map(|(t, seq)| (t.instrument.as_str(), t.last, *seq))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("ES", dec!(2), 0),
+                ("ES", dec!(1), 0),
+                ("ES", dec!(3), 1),
+                ("ES", dec!(4), 2),
+                ("NQ", dec!(9), 0),
+            ]
+        );
+
+        let ranges = tick_ranges(&numbered);
+        assert_eq!(
+            ranges,
+            vec![
+                ("ES", ticks[2].timestamp, ticks[1].timestamp),
+                ("NQ", ticks[0].timestamp, ticks[0].timestamp),
+            ]
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! Paged, rate-limited downloads from [`HistoricalDataApi`] vendors.
+
+use chrono::{DateTime, Duration, Utc};
+use futures_util::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use propbot_core::{Bar, DataError, HistoricalDataApi, Tick, Timeframe};
+use sqlx::PgPool;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::db;
+
+/// Spaces requests out to stay under a vendor's rate limit.
+pub struct RateLimiter {
+    interval: std::time::Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// Allow one request per `interval`.
+    pub fn new(interval: std::time::Duration) -> Self {
+        Self {
+            interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until the next request is allowed.
+    pub async fn wait(&self) {
+        let mut next = self.next.lock().await;
+        tokio::time::sleep_until(*next).await;
+        *next = Instant::now() + self.interval;
+    }
+
+    /// Hold off all requests for `delay`, e.g. after the vendor asks us to.
+    pub async fn back_off(&self, delay: std::time::Duration) {
+        let mut next = self.next.lock().await;
+        *next = (*next).max(Instant::now() + delay);
+    }
+}
+
+/// Page through bars from `start` to `end`, one request per page.
+pub fn bar_pages<'a>(
+    api: &'a dyn HistoricalDataApi,
+    instrument: &'a str,
+    timeframe: Timeframe,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> BoxStream<'a, Result<Vec<Bar>, DataError>> {
+    stream::try_unfold(start, move |cursor| async move {
+        if cursor > end {
+            return Ok(None);
+        }
+        let bars = api.fetch_bars(instrument, timeframe, cursor, end).await?;
+        let Some(last) = bars.last() else {
+            return Ok(None);
+        };
+        if last.timestamp < cursor {
+            return Err(DataError::ApiError(format!(
+                "{} returned bars before {}",
+                api.name(),
+                cursor
+            )));
+        }
+        // Bars are at least a second apart
+        let next = last.timestamp + Duration::seconds(1);
+        Ok(Some((bars, next)))
+    })
+    .boxed()
+}
+
+/// Page through ticks from `start` to `end`, one request per page.
+///
+/// Several ticks can share a timestamp, so the ticks at the end of a page are
+/// held back and requested again with the next page rather than risk losing
+/// the rest of them.
+pub fn tick_pages<'a>(
+    api: &'a dyn HistoricalDataApi,
+    instrument: &'a str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> BoxStream<'a, Result<Vec<Tick>, DataError>> {
+    stream::try_unfold(start, move |cursor| async move {
+        if cursor > end {
+            return Ok(None);
+        }
+        let mut ticks = api.fetch_ticks(instrument, cursor, end).await?;
+        let Some(last) = ticks.last().map(|t| t.timestamp) else {
+            return Ok(None);
+        };
+        if ticks[0].timestamp == last {
+            // The whole page is at one time: take it and move past it
+            return Ok(Some((ticks, last + Duration::milliseconds(1))));
+        }
+        ticks.retain(|t| t.timestamp < last);
+        Ok(Some((ticks, last)))
+    })
+    .boxed()
+}
+
+/// Download bars into the database, skipping what is already stored.
+///
+/// Only the parts of `start..=end` before the oldest and after the newest
+/// stored bar are requested. Each page is stored as it arrives, so an
+/// interrupted download can simply be run again. The bars at the edges of
+/// the stored range are fetched again in case the newest was still forming
+/// when it was saved. Returns the number of rows written.
+pub async fn download_bars(
+    pool: &PgPool,
+    api: &dyn HistoricalDataApi,
+    instrument: &str,
+    timeframe: Timeframe,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<u64, DataError> {
+    let stored = db::stored_bar_range(pool, instrument, timeframe)
+        .await
+        .map_err(|e| DataError::DatabaseError(e.to_string()))?;
+
+    let mut count = 0;
+    for (from, to) in missing_ranges(start, end, stored) {
+        let mut pages = bar_pages(api, instrument, timeframe, from, to);
+        while let Some(page) = pages.try_next().await? {
+            count += db::insert_bars(pool, &page, timeframe)
+                .await
+                .map_err(|e| DataError::DatabaseError(e.to_string()))?;
+            tracing::info!(
+                vendor = api.name(),
+                instrument = instrument,
+                rows = count,
+                through = %page[page.len() - 1].timestamp,
+                "Downloaded bars"
+            );
+        }
+    }
+    Ok(count)
+}
+
+/// Download ticks into the database, skipping what is already stored.
+///
+/// Like [`download_bars`], only the parts of `start..=end` outside the stored
+/// range are requested. Ticks at the edges of the stored range are fetched
+/// again and replace the stored ones. Returns the number of rows written.
+pub async fn download_ticks(
+    pool: &PgPool,
+    api: &dyn HistoricalDataApi,
+    instrument: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<u64, DataError> {
+    let stored = db::stored_tick_range(pool, instrument)
+        .await
+        .map_err(|e| DataError::DatabaseError(e.to_string()))?;
+
+    let mut count = 0;
+    for (from, to) in missing_ranges(start, end, stored) {
+        let mut pages = tick_pages(api, instrument, from, to);
+        while let Some(page) = pages.try_next().await? {
+            count += db::insert_ticks(pool, &page)
+                .await
+                .map_err(|e| DataError::DatabaseError(e.to_string()))?;
+            tracing::info!(
+                vendor = api.name(),
+                instrument = instrument,
+                rows = count,
+                through = %page[page.len() - 1].timestamp,
+                "Downloaded ticks"
+            );
+        }
+    }
+    Ok(count)
+}
+
+/// The parts of `start..=end` to download, given the stored range.
+///
+/// Requests the gap before the oldest stored row and the gap after the newest
+/// one, each including the stored edge. A stored range that does not overlap
+/// `start..=end` says nothing about it, so the whole range is requested.
+fn missing_ranges(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    stored: Option<(DateTime<Utc>, DateTime<Utc>)>,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let Some((first, last)) = stored.filter(|&(first, last)| first <= end && last >= start) else {
+        return vec![(start, end)];
+    };
+    let mut ranges = Vec::new();
+    if start < first {
+        ranges.push((start, first));
+    }
+    if last < end {
+        tracing::info!(from = %last, "Resuming download");
+        ranges.push((last, end));
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn day(d: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, d, 0, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_missing_ranges_without_stored_data() {
+        assert_eq!(
+            missing_ranges(day(1), day(10), None),
+            vec![(day(1), day(10))]
+        );
+    }
+
+    #[test]
+    fn test_missing_ranges_fill_both_sides() {
+        assert_eq!(
+            missing_ranges(day(1), day(10), Some((day(4), day(6)))),
+            vec![(day(1), day(4)), (day(6), day(10))]
+        );
+    }
+
+    #[test]
+    fn test_missing_ranges_resume_from_newest() {
+        assert_eq!(
+            missing_ranges(day(3), day(10), Some((day(1), day(6)))),
+            vec![(day(6), day(10))]
+        );
+    }
+
+    #[test]
+    fn test_missing_ranges_skip_covered_range() {
+        assert!(missing_ranges(day(3), day(5), Some((day(1), day(6)))).is_empty());
+    }
+
+    #[test]
+    fn test_missing_ranges_ignore_stored_data_outside_range() {
+        // Newer data stored must not skip an older request
+        assert_eq!(
+            missing_ranges(day(1), day(5), Some((day(8), day(12)))),
+            vec![(day(1), day(5))]
+        );
+        assert_eq!(
+            missing_ranges(day(8), day(12), Some((day(1), day(5)))),
+            vec![(day(8), day(12))]
+        );
+    }
+}
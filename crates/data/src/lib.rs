@@ -1,21 +1,173 @@
+pub mod aggregation;
+pub mod binance;
+pub mod cache;
+pub mod continuous;
 pub mod csv_loader;
 pub mod db;
+pub mod download;
+pub mod metatrader;
+pub mod ninjatrader;
+pub mod streaming;
+pub mod synthetic;
+pub mod validation;
 
+use aggregation::{aggregate_ticks, BarSpec};
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
-use propbot_core::{Bar, DataError, DataProvider, Tick, Timeframe};
+use chrono::{DateTime, FixedOffset, Utc};
+use futures_util::stream::{self, StreamExt};
+use propbot_core::{Bar, BarStream, DataError, DataProvider, Tick, TickStream, Timeframe};
+use std::path::Path;
+use streaming::{
+    aggregate_bar_stream, aggregate_tick_stream, bar_time, chronological, failed, flatten_chunks,
+    resample_stream, tick_time, within,
+};
+
+/// Historical data file formats that can be imported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// Generic CSV, optionally described by a [`csv_loader::CsvSchema`].
+    Csv,
+    /// NinjaTrader `.txt` export.
+    NinjaTrader,
+    /// MetaTrader 5 tab-separated export.
+    Mt5,
+}
+
+impl std::fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportFormat::Csv => write!(f, "csv"),
+            ImportFormat::NinjaTrader => write!(f, "ninjatrader"),
+            ImportFormat::Mt5 => write!(f, "mt5"),
+        }
+    }
+}
+
+impl std::str::FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ImportFormat::Csv),
+            "ninjatrader" | "nt" | "nt8" => Ok(ImportFormat::NinjaTrader),
+            "mt5" | "metatrader" => Ok(ImportFormat::Mt5),
+            other => Err(format!(
+                "Unknown format '{}' (expected csv, ninjatrader or mt5)",
+                other
+            )),
+        }
+    }
+}
+
+impl ImportFormat {
+    /// Load bars from a file in this format, sorted by timestamp.
+    ///
+    /// Timestamps are read in the schema's `timezone` (UTC if unset) for
+    /// every format; `offset`, the UTC offset of the file's timestamps,
+    /// overrides it. `timeframe` is the bar interval when known, which
+    /// NinjaTrader exports need to move intraday bars from close to open.
+    pub fn load_bars(
+        self,
+        path: &Path,
+        instrument: &str,
+        schema: &csv_loader::CsvSchema,
+        offset: Option<FixedOffset>,
+        timeframe: Option<Timeframe>,
+    ) -> Result<Vec<Bar>, DataError> {
+        let zone = source_zone(schema, offset)?;
+        match self {
+            ImportFormat::Csv => {
+                let schema = csv_loader::CsvSchema {
+                    instrument: Some(instrument.to_string()),
+                    timezone: offset.map(|o| o.to_string()).or(schema.timezone.clone()),
+                    ..schema.clone()
+                };
+                csv_loader::load_bars_from_csv_with(path, &schema)
+            }
+            ImportFormat::NinjaTrader => ninjatrader::load_bars(path, instrument, zone, timeframe),
+            ImportFormat::Mt5 => metatrader::load_bars(path, instrument, zone),
+        }
+    }
+
+    /// Load ticks from a file in this format, sorted by timestamp.
+    ///
+    /// CSV tick files have `timestamp,bid,ask,last,volume` headers; the schema
+    /// can only set their delimiter, timestamp format and timezone. The
+    /// timezone and `offset` apply to every format as for bars.
+    pub fn load_ticks(
+        self,
+        path: &Path,
+        instrument: &str,
+        schema: &csv_loader::CsvSchema,
+        offset: Option<FixedOffset>,
+    ) -> Result<Vec<Tick>, DataError> {
+        let zone = source_zone(schema, offset)?;
+        let mut ticks = match self {
+            ImportFormat::Csv => {
+                let schema = csv_loader::CsvSchema {
+                    timezone: offset.map(|o| o.to_string()).or(schema.timezone.clone()),
+                    ..schema.clone()
+                };
+                csv_loader::load_ticks_from_csv_with(path, &schema)?
+            }
+            ImportFormat::NinjaTrader => ninjatrader::load_ticks(path, instrument, zone)?,
+            ImportFormat::Mt5 => metatrader::load_ticks(path, instrument, zone)?,
+        };
+        for tick in &mut ticks {
+            tick.instrument = instrument.to_string();
+        }
+        Ok(ticks)
+    }
+}
+
+/// The zone an import reads timestamps in: `offset` if given, else the schema's.
+fn source_zone(
+    schema: &csv_loader::CsvSchema,
+    offset: Option<FixedOffset>,
+) -> Result<csv_loader::SourceZone, DataError> {
+    match offset {
+        Some(offset) => Ok(csv_loader::SourceZone::Offset(offset)),
+        None => schema.zone(),
+    }
+}
+
+/// Convert stored bars to the requested timeframe, or explain why it can't be done.
+fn resample_or_err(
+    bars: Vec<Bar>,
+    instrument: &str,
+    timeframe: Timeframe,
+) -> Result<Vec<Bar>, DataError> {
+    let source = aggregation::infer_timeframe(&bars);
+    aggregation::resample(bars, timeframe).ok_or_else(|| {
+        DataError::NotFound(format!(
+            "Cannot build {} bars for {} from {} data",
+            timeframe,
+            instrument,
+            source.map(|t| t.to_string()).unwrap_or_default()
+        ))
+    })
+}
 
 /// A CSV-file-based data provider.
 pub struct CsvDataProvider {
     pub directory: std::path::PathBuf,
+    /// Layout of the bar files.
+    pub schema: csv_loader::CsvSchema,
 }
 
 impl CsvDataProvider {
     pub fn new(directory: impl Into<std::path::PathBuf>) -> Self {
         Self {
             directory: directory.into(),
+            schema: csv_loader::CsvSchema::default(),
         }
     }
+
+    /// Read bar files using a custom column layout.
+    pub fn with_schema(mut self, schema: csv_loader::CsvSchema) -> Self {
+        self.schema = schema;
+        self
+    }
 }
 
 #[async_trait]
@@ -23,23 +175,33 @@ impl DataProvider for CsvDataProvider {
     async fn load_bars(
         &self,
         instrument: &str,
-        _timeframe: Timeframe,
+        timeframe: Timeframe,
         start: DateTime<Utc>,
         end: DateTime<Utc>,
     ) -> Result<Vec<Bar>, DataError> {
         let file_path = self.directory.join(format!("{}.csv", instrument));
         if !file_path.exists() {
+            // Fall back to building bars from tick data
+            let tick_path = self.directory.join(format!("{}_ticks.csv", instrument));
+            if tick_path.exists() {
+                let ticks = self.load_ticks(instrument, start, end).await?;
+                return Ok(aggregate_ticks(&ticks, BarSpec::Time(timeframe)));
+            }
             return Err(DataError::NotFound(format!(
                 "CSV file not found: {}",
                 file_path.display()
             )));
         }
-        let bars = csv_loader::load_bars_from_csv(&file_path)?;
+        let mut bars = csv_loader::load_bars_from_csv_with(&file_path, &self.schema)?;
+        // The requested symbol wins over any name set in the schema
+        for bar in &mut bars {
+            bar.instrument = instrument.to_string();
+        }
         let filtered: Vec<Bar> = bars
             .into_iter()
             .filter(|b| b.timestamp >= start && b.timestamp <= end)
             .collect();
-        Ok(filtered)
+        resample_or_err(filtered, instrument, timeframe)
     }
 
     async fn load_ticks(
@@ -65,10 +227,9 @@ impl DataProvider for CsvDataProvider {
 
     async fn available_instruments(&self) -> Result<Vec<String>, DataError> {
         let mut instruments = Vec::new();
-        let entries = std::fs::read_dir(&self.directory)
-            .map_err(|e| DataError::IoError(e))?;
+        let entries = std::fs::read_dir(&self.directory).map_err(DataError::IoError)?;
         for entry in entries {
-            let entry = entry.map_err(|e| DataError::IoError(e))?;
+            let entry = entry.map_err(DataError::IoError)?;
             let path = entry.path();
             if path.extension().map(|e| e == "csv").unwrap_or(false) {
                 if let Some(stem) = path.file_stem() {
@@ -82,6 +243,66 @@ impl DataProvider for CsvDataProvider {
         instruments.sort();
         Ok(instruments)
     }
+
+    /// Reads the file a row at a time. Unlike `load_bars`, the file must be in
+    /// time order; the stream fails at the first out-of-order row.
+    fn stream_bars<'a>(
+        &'a self,
+        instrument: &'a str,
+        timeframe: Timeframe,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BarStream<'a> {
+        let file_path = self.directory.join(format!("{}.csv", instrument));
+        if !file_path.exists() {
+            // Fall back to building bars from tick data
+            let tick_path = self.directory.join(format!("{}_ticks.csv", instrument));
+            if tick_path.exists() {
+                let ticks = self.stream_ticks(instrument, start, end);
+                return aggregate_tick_stream(ticks, BarSpec::Time(timeframe));
+            }
+            return failed(DataError::NotFound(format!(
+                "CSV file not found: {}",
+                file_path.display()
+            )));
+        }
+        let reader = match csv_loader::CsvBarReader::open(&file_path, &self.schema) {
+            Ok(reader) => reader,
+            Err(e) => return failed(e),
+        };
+        let bars = stream::iter(reader.map(move |bar| {
+            bar.map(|mut bar| {
+                bar.instrument = instrument.to_string();
+                bar
+            })
+        }))
+        .boxed();
+        let bars = within(chronological(bars, bar_time), bar_time, start, end);
+        resample_stream(bars, instrument, timeframe)
+    }
+
+    /// Reads the file a row at a time; it must be in time order.
+    fn stream_ticks<'a>(
+        &'a self,
+        instrument: &'a str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> TickStream<'a> {
+        let file_path = self.directory.join(format!("{}_ticks.csv", instrument));
+        if !file_path.exists() {
+            return failed(DataError::NotFound(format!(
+                "Tick CSV file not found: {}",
+                file_path.display()
+            )));
+        }
+        match csv_loader::CsvTickReader::open(&file_path) {
+            Ok(reader) => {
+                let ticks = stream::iter(reader).boxed();
+                within(chronological(ticks, tick_time), tick_time, start, end)
+            }
+            Err(e) => failed(e),
+        }
+    }
 }
 
 /// A PostgreSQL-backed data provider.
@@ -93,6 +314,36 @@ impl PostgresDataProvider {
     pub fn new(pool: sqlx::PgPool) -> Self {
         Self { pool }
     }
+
+    /// The stored timeframe to build `timeframe` bars from: `timeframe` itself
+    /// if stored, otherwise the coarsest stored timeframe it builds from.
+    pub async fn source_timeframe(
+        &self,
+        instrument: &str,
+        timeframe: Timeframe,
+    ) -> Result<Option<Timeframe>, DataError> {
+        let stored: Vec<Timeframe> = db::available_instruments(&self.pool)
+            .await
+            .map_err(|e| DataError::DatabaseError(e.to_string()))?
+            .into_iter()
+            .filter(|s| s.instrument == instrument)
+            .map(|s| s.timeframe)
+            .collect();
+        Ok(pick_source_timeframe(&stored, timeframe))
+    }
+}
+
+/// `timeframe` if it is among the `stored` timeframes, otherwise the coarsest
+/// stored timeframe it builds from.
+fn pick_source_timeframe(stored: &[Timeframe], timeframe: Timeframe) -> Option<Timeframe> {
+    if stored.contains(&timeframe) {
+        return Some(timeframe);
+    }
+    stored
+        .iter()
+        .copied()
+        .filter(|source| timeframe.builds_from(*source))
+        .max_by_key(|source| source.duration())
 }
 
 #[async_trait]
@@ -100,13 +351,22 @@ impl DataProvider for PostgresDataProvider {
     async fn load_bars(
         &self,
         instrument: &str,
-        _timeframe: Timeframe,
+        timeframe: Timeframe,
         start: DateTime<Utc>,
         end: DateTime<Utc>,
     ) -> Result<Vec<Bar>, DataError> {
-        db::load_bars(&self.pool, instrument, start, end)
+        let Some(source) = self.source_timeframe(instrument, timeframe).await? else {
+            // Fall back to building bars from tick data
+            let ticks = self.load_ticks(instrument, start, end).await?;
+            return Ok(aggregate_ticks(&ticks, BarSpec::Time(timeframe)));
+        };
+        let bars = db::load_bars(&self.pool, instrument, source, start, end)
             .await
-            .map_err(|e| DataError::DatabaseError(e.to_string()))
+            .map_err(|e| DataError::DatabaseError(e.to_string()))?;
+        if source == timeframe {
+            return Ok(bars);
+        }
+        Ok(aggregation::aggregate_bars(&bars, BarSpec::Time(timeframe)))
     }
 
     async fn load_ticks(
@@ -121,8 +381,118 @@ impl DataProvider for PostgresDataProvider {
     }
 
     async fn available_instruments(&self) -> Result<Vec<String>, DataError> {
-        db::available_instruments(&self.pool)
+        let mut instruments: Vec<String> = db::available_instruments(&self.pool)
             .await
-            .map_err(|e| DataError::DatabaseError(e.to_string()))
+            .map_err(|e| DataError::DatabaseError(e.to_string()))?
+            .into_iter()
+            .map(|s| s.instrument)
+            .collect();
+        instruments.dedup();
+        Ok(instruments)
+    }
+
+    /// Fetches rows in chunks rather than all at once.
+    fn stream_bars<'a>(
+        &'a self,
+        instrument: &'a str,
+        timeframe: Timeframe,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> BarStream<'a> {
+        stream::once(async move {
+            let source = match self.source_timeframe(instrument, timeframe).await {
+                Ok(Some(source)) => source,
+                Ok(None) => {
+                    // Fall back to building bars from tick data
+                    let ticks = self.stream_ticks(instrument, start, end);
+                    return aggregate_tick_stream(ticks, BarSpec::Time(timeframe));
+                }
+                Err(e) => return failed(e),
+            };
+            let bars = flatten_chunks(
+                db::stream_bars(&self.pool, instrument, source, start, end),
+                |e| DataError::DatabaseError(e.to_string()),
+            );
+            if source == timeframe {
+                return bars;
+            }
+            aggregate_bar_stream(bars, BarSpec::Time(timeframe))
+        })
+        .flatten()
+        .boxed()
+    }
+
+    fn stream_ticks<'a>(
+        &'a self,
+        instrument: &'a str,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> TickStream<'a> {
+        flatten_chunks(db::stream_ticks(&self.pool, instrument, start, end), |e| {
+            DataError::DatabaseError(e.to_string())
+        })
+    }
+}
+
+/// Write a scratch file into the temp directory for a test. `name` is the
+/// file name and must be unique across the crate's tests.
+#[cfg(test)]
+pub(crate) fn write_temp(name: &str, contents: impl AsRef<[u8]>) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("propbot_{}_{}", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_timeframe_prefers_exact_then_coarsest() {
+        let stored = [
+            Timeframe::Minute(1),
+            Timeframe::Minute(5),
+            Timeframe::Minute(15),
+            Timeframe::Hour(1),
+        ];
+        let pick = |timeframe| pick_source_timeframe(&stored, timeframe);
+
+        assert_eq!(pick(Timeframe::Minute(5)), Some(Timeframe::Minute(5)));
+        // 30m builds from 1m, 5m and 15m; 1h doesn't divide it
+        assert_eq!(pick(Timeframe::Minute(30)), Some(Timeframe::Minute(15)));
+        assert_eq!(pick(Timeframe::Hour(4)), Some(Timeframe::Hour(1)));
+        // 7m only builds from 1m
+        assert_eq!(pick(Timeframe::Minute(7)), Some(Timeframe::Minute(1)));
+        // Nothing finer than 1m is stored
+        assert_eq!(pick(Timeframe::Second(30)), None);
+        assert_eq!(pick_source_timeframe(&[], Timeframe::Minute(1)), None);
+    }
+
+    #[test]
+    fn test_import_reads_export_in_schema_timezone() {
+        let path = write_temp("import_tz.txt", "20240715 093100;1;1;1;1;1\n");
+        let schema = csv_loader::CsvSchema {
+            timezone: Some("America/New_York".to_string()),
+            ..Default::default()
+        };
+        let load = |offset| {
+            ImportFormat::NinjaTrader
+                .load_bars(&path, "ES", &schema, offset, Some(Timeframe::Minute(1)))
+                .unwrap()[0]
+                .timestamp
+        };
+        let summer = load(None);
+        let fixed = load(FixedOffset::west_opt(5 * 3600));
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            summer,
+            "2024-07-15T13:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        // An explicit offset overrides the schema
+        assert_eq!(
+            fixed,
+            "2024-07-15T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
     }
 }
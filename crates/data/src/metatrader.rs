@@ -0,0 +1,245 @@
+//! Parsers for MetaTrader 5 history exports (Symbols > Bars/Ticks > Export).
+//!
+//! Files are tab-separated with `<NAME>` headers:
+//!
+//! - Bars: `<DATE> <TIME> <OPEN> <HIGH> <LOW> <CLOSE> <TICKVOL> <VOL> <SPREAD>`
+//!   (daily exports have no `<TIME>` column)
+//! - Ticks: `<DATE> <TIME> <BID> <ASK> <LAST> <VOLUME> <FLAGS>`, where a blank
+//!   price means "unchanged since the previous tick"
+//!
+//! Dates are `yyyy.MM.dd` in broker server time, which usually follows
+//! daylight saving, so callers pass the server's time zone. Bars are stamped
+//! at their open.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use propbot_core::{Bar, DataError, Tick};
+use rust_decimal::Decimal;
+use std::path::Path;
+
+use crate::csv_loader::{parse_decimal, SourceZone};
+
+/// Load bars from an MT5 export, sorted by timestamp.
+///
+/// Volume is `<VOL>` (exchange volume) when present and non-zero, otherwise
+/// `<TICKVOL>`, since forex and CFD symbols only report tick volume.
+pub fn load_bars(path: &Path, instrument: &str, zone: SourceZone) -> Result<Vec<Bar>, DataError> {
+    let (headers, rows) = read_rows(path)?;
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let required = |name: &str| {
+        column(name).ok_or_else(|| DataError::ParseError(format!("No <{}> column found", name)))
+    };
+    let date = required("DATE")?;
+    let time = column("TIME");
+    let (open, high, low, close) = (
+        required("OPEN")?,
+        required("HIGH")?,
+        required("LOW")?,
+        required("CLOSE")?,
+    );
+    let (tick_volume, real_volume) = (column("TICKVOL"), column("VOL"));
+
+    let mut bars = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let field = |idx: usize| row.get(idx).map(String::as_str).unwrap_or_default();
+        let volume_at = |idx: Option<usize>, name| match idx.map(field) {
+            Some(v) if !v.is_empty() => parse_decimal(v, name).map(Some),
+            _ => Ok(None),
+        };
+        let volume = match volume_at(real_volume, "vol")? {
+            Some(v) if !v.is_zero() => v,
+            _ => volume_at(tick_volume, "tickvol")?.unwrap_or(Decimal::ZERO),
+        };
+        bars.push(Bar {
+            instrument: instrument.to_string(),
+            timestamp: parse_timestamp(field(date), time.map(field), zone)?,
+            open: parse_decimal(field(open), "open")?,
+            high: parse_decimal(field(high), "high")?,
+            low: parse_decimal(field(low), "low")?,
+            close: parse_decimal(field(close), "close")?,
+            volume,
+        });
+    }
+    bars.sort_by_key(|b| b.timestamp);
+    Ok(bars)
+}
+
+/// Load ticks from an MT5 tick export, sorted by timestamp.
+///
+/// Blank prices carry forward from the previous tick. `last` falls back to the
+/// bid/ask midpoint for symbols without trade prices.
+pub fn load_ticks(path: &Path, instrument: &str, zone: SourceZone) -> Result<Vec<Tick>, DataError> {
+    let (headers, rows) = read_rows(path)?;
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let required = |name: &str| {
+        column(name).ok_or_else(|| DataError::ParseError(format!("No <{}> column found", name)))
+    };
+    let (date, time) = (required("DATE")?, required("TIME")?);
+    let (bid_col, ask_col) = (required("BID")?, required("ASK")?);
+    let (last_col, volume_col) = (column("LAST"), column("VOLUME"));
+
+    let mut ticks = Vec::with_capacity(rows.len());
+    let (mut bid, mut ask, mut last) = (None, None, None);
+    for row in &rows {
+        let field = |idx: usize| row.get(idx).map(String::as_str).unwrap_or_default();
+        let update = |idx: Option<usize>, name, current: Option<Decimal>| match idx.map(field) {
+            Some(v) if !v.is_empty() => parse_decimal(v, name).map(Some),
+            _ => Ok(current),
+        };
+        bid = update(Some(bid_col), "bid", bid)?;
+        ask = update(Some(ask_col), "ask", ask)?;
+        last = update(last_col, "last", last)?;
+        // Nothing to record until both sides of the book are known
+        let (Some(b), Some(a)) = (bid, ask) else {
+            continue;
+        };
+        let volume = match volume_col.map(field) {
+            Some(v) if !v.is_empty() => parse_decimal(v, "volume")?,
+            _ => Decimal::ZERO,
+        };
+        ticks.push(Tick {
+            instrument: instrument.to_string(),
+            timestamp: parse_timestamp(field(date), Some(field(time)), zone)?,
+            bid: b,
+            ask: a,
+            last: last.unwrap_or((b + a) / Decimal::TWO),
+            volume,
+        });
+    }
+    ticks.sort_by_key(|t| t.timestamp);
+    Ok(ticks)
+}
+
+/// Read the header names (without angle brackets) and the data rows.
+fn read_rows(path: &Path) -> Result<(Vec<String>, Vec<Vec<String>>), DataError> {
+    let text = decode(&std::fs::read(path)?)?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b'\t')
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+
+    let headers = reader
+        .headers()
+        .map_err(|e| DataError::ParseError(format!("Failed to read headers: {}", e)))?
+        .iter()
+        .map(|h| h.trim_matches(['<', '>']).to_uppercase())
+        .collect();
+    let rows = reader
+        .records()
+        .map(|r| {
+            r.map(|record| record.iter().map(str::to_string).collect())
+                .map_err(|e| DataError::ParseError(format!("CSV record error: {}", e)))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((headers, rows))
+}
+
+/// MT5 writes UTF-16LE with a BOM from some dialogs and UTF-8 from others.
+fn decode(bytes: &[u8]) -> Result<String, DataError> {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        return String::from_utf16(&units)
+            .map_err(|e| DataError::ParseError(format!("Invalid UTF-16 export: {}", e)));
+    }
+    let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+    String::from_utf8(bytes.to_vec())
+        .map_err(|e| DataError::ParseError(format!("Invalid UTF-8 export: {}", e)))
+}
+
+/// Parse `yyyy.MM.dd` plus an optional `HH:mm[:ss[.fff]]`.
+fn parse_timestamp(
+    date: &str,
+    time: Option<&str>,
+    zone: SourceZone,
+) -> Result<DateTime<Utc>, DataError> {
+    let invalid = || {
+        DataError::ParseError(format!(
+            "Unable to parse MT5 timestamp: '{} {}'",
+            date,
+            time.unwrap_or_default()
+        ))
+    };
+    let date = NaiveDate::parse_from_str(date, "%Y.%m.%d").map_err(|_| invalid())?;
+    let time = match time.filter(|t| !t.is_empty()) {
+        Some(t) => NaiveTime::parse_from_str(t, "%H:%M:%S%.f")
+            .or_else(|_| NaiveTime::parse_from_str(t, "%H:%M"))
+            .map_err(|_| invalid())?,
+        None => NaiveTime::MIN,
+    };
+    zone.localize(NaiveDateTime::new(date, time))
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::write_temp;
+    use chrono::FixedOffset;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_bars_use_tick_volume_when_real_volume_is_zero() {
+        let path = write_temp(
+            "mt5_bars.csv",
+            b"<DATE>\t<TIME>\t<OPEN>\t<HIGH>\t<LOW>\t<CLOSE>\t<TICKVOL>\t<VOL>\t<SPREAD>\n\
+              2024.03.05\t16:30:00\t1.08500\t1.08550\t1.08480\t1.08520\t412\t0\t2\n",
+        );
+        let server = SourceZone::Offset(FixedOffset::east_opt(2 * 3600).unwrap());
+        let bars = load_bars(&path, "EURUSD", server).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            bars[0].timestamp,
+            "2024-03-05T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(bars[0].close, dec!(1.08520));
+        assert_eq!(bars[0].volume, dec!(412));
+    }
+
+    #[test]
+    fn test_bars_follow_server_daylight_saving() {
+        // EU clocks go forward at 03:00 on 2024-03-31
+        let path = write_temp(
+            "mt5_dst.csv",
+            b"<DATE>\t<TIME>\t<OPEN>\t<HIGH>\t<LOW>\t<CLOSE>\t<TICKVOL>\t<VOL>\t<SPREAD>\n\
+              2024.03.29\t16:30:00\t1.08500\t1.08550\t1.08480\t1.08520\t412\t0\t2\n\
+              2024.04.01\t16:30:00\t1.07900\t1.07950\t1.07880\t1.07920\t388\t0\t2\n",
+        );
+        let server = SourceZone::Named(chrono_tz::Europe::Athens);
+        let bars = load_bars(&path, "EURUSD", server).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            bars[0].timestamp,
+            "2024-03-29T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(
+            bars[1].timestamp,
+            "2024-04-01T13:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_ticks_carry_forward_blank_prices_from_utf16() {
+        let text = "<DATE>\t<TIME>\t<BID>\t<ASK>\t<LAST>\t<VOLUME>\t<FLAGS>\n\
+                    2024.03.05\t14:30:00.100\t1.08500\t1.08510\t\t\t6\n\
+                    2024.03.05\t14:30:00.250\t\t1.08520\t\t\t4\n";
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        let path = write_temp("mt5_ticks.csv", &bytes);
+        let ticks = load_ticks(&path, "EURUSD", SourceZone::default()).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(ticks.len(), 2);
+        assert_eq!(ticks[1].bid, dec!(1.08500));
+        assert_eq!(ticks[1].ask, dec!(1.08520));
+        assert_eq!(ticks[1].last, dec!(1.08510));
+        assert_eq!(
+            ticks[1].timestamp,
+            "2024-03-05T14:30:00.250Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Parsers for NinjaTrader's historical data export (`Tools > Historical Data > Export`).
+//!
+//! Files are semicolon-separated with no header:
+//!
+//! - Minute/second bars: `yyyyMMdd HHmmss;open;high;low;close;volume`
+//! - Daily bars: `yyyyMMdd;open;high;low;close;volume`
+//! - NT8 ticks: `yyyyMMdd HHmmss fffffff;last;bid;ask;volume`
+//! - NT7 ticks: `yyyyMMdd HHmmss;last;volume`
+//!
+//! Timestamps are in the exporting machine's local time, daylight saving
+//! included, so callers pass its time zone. Intraday bars are stamped at the
+//! bar's close; they are shifted back by the bar interval so that
+//! `Bar::timestamp` is the open as elsewhere.
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use propbot_core::{Bar, DataError, Tick, Timeframe};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::aggregation::infer_timeframe;
+use crate::csv_loader::{parse_decimal, SourceZone};
+
+/// Load bars from a NinjaTrader export, sorted by timestamp.
+///
+/// `timeframe` is the bar interval used to move intraday close times to the
+/// open. Without it the interval is inferred from the bars, and a file it
+/// can't be inferred from (e.g. a single bar) is an error rather than being
+/// left stamped at the close.
+pub fn load_bars(
+    path: &Path,
+    instrument: &str,
+    zone: SourceZone,
+    timeframe: Option<Timeframe>,
+) -> Result<Vec<Bar>, DataError> {
+    let mut bars = Vec::new();
+    let mut intraday = false;
+    for (line_no, fields) in records(path)? {
+        let fields = fields?;
+        let [timestamp, open, high, low, close, volume] = fields.as_slice() else {
+            return Err(line_error(line_no, "expected 6 fields"));
+        };
+        intraday |= timestamp.contains(' ');
+        bars.push(Bar {
+            instrument: instrument.to_string(),
+            timestamp: parse_timestamp(timestamp, zone).map_err(|e| line_error(line_no, &e))?,
+            open: parse_decimal(open, "open")?,
+            high: parse_decimal(high, "high")?,
+            low: parse_decimal(low, "low")?,
+            close: parse_decimal(close, "close")?,
+            volume: parse_decimal(volume, "volume")?,
+        });
+    }
+    bars.sort_by_key(|b| b.timestamp);
+
+    if intraday {
+        let interval = match timeframe.or_else(|| infer_timeframe(&bars)) {
+            Some(tf @ (Timeframe::Second(_) | Timeframe::Minute(_) | Timeframe::Hour(_))) => {
+                tf.duration()
+            }
+            _ => None,
+        };
+        let interval = interval.ok_or_else(|| {
+            DataError::ParseError(format!(
+                "Cannot tell the intraday bar interval of {} to move close times to the open; \
+                 pass the timeframe",
+                path.display()
+            ))
+        })?;
+        for bar in &mut bars {
+            bar.timestamp -= interval;
+        }
+    }
+    Ok(bars)
+}
+
+/// Load ticks from a NinjaTrader tick export, sorted by timestamp.
+///
+/// NT7 files carry no bid/ask; both are set to the last price.
+pub fn load_ticks(path: &Path, instrument: &str, zone: SourceZone) -> Result<Vec<Tick>, DataError> {
+    let mut ticks = Vec::new();
+    for (line_no, fields) in records(path)? {
+        let fields = fields?;
+        let (timestamp, last, bid, ask, volume) = match fields.as_slice() {
+            [timestamp, last, bid, ask, volume] => {
+                let last = parse_decimal(last, "last")?;
+                (
+                    timestamp,
+                    last,
+                    parse_decimal(bid, "bid")?,
+                    parse_decimal(ask, "ask")?,
+                    volume,
+                )
+            }
+            [timestamp, last, volume] => {
+                let last = parse_decimal(last, "last")?;
+                (timestamp, last, last, last, volume)
+            }
+            _ => return Err(line_error(line_no, "expected 3 or 5 fields")),
+        };
+        ticks.push(Tick {
+            instrument: instrument.to_string(),
+            timestamp: parse_timestamp(timestamp, zone).map_err(|e| line_error(line_no, &e))?,
+            bid,
+            ask,
+            last,
+            volume: parse_decimal(volume, "volume")?,
+        });
+    }
+    ticks.sort_by_key(|t| t.timestamp);
+    Ok(ticks)
+}
+
+/// Non-empty lines split on `;`, with their 1-based line numbers.
+fn records(
+    path: &Path,
+) -> Result<impl Iterator<Item = (usize, Result<Vec<String>, DataError>)>, DataError> {
+    let file = std::fs::File::open(path)?;
+    Ok(BufReader::new(file)
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let fields = line
+                .map(|l| {
+                    l.split(';')
+                        .map(|f| f.trim().to_string())
+                        .collect::<Vec<_>>()
+                })
+                .map_err(DataError::IoError);
+            (i + 1, fields)
+        })
+        .filter(|(_, fields)| !matches!(fields, Ok(f) if f.iter().all(|s| s.is_empty()))))
+}
+
+/// Parse `yyyyMMdd`, `yyyyMMdd HHmmss` or `yyyyMMdd HHmmss fffffff`.
+fn parse_timestamp(s: &str, zone: SourceZone) -> Result<DateTime<Utc>, String> {
+    let mut parts = s.split_whitespace();
+    let date = parts
+        .next()
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y%m%d").ok())
+        .ok_or_else(|| format!("invalid date in '{}'", s))?;
+    let time = match parts.next() {
+        Some(t) => NaiveTime::parse_from_str(t, "%H%M%S")
+            .map_err(|_| format!("invalid time in '{}'", s))?,
+        None => NaiveTime::MIN,
+    };
+    // Fractional seconds in 100ns units
+    let fraction = match parts.next() {
+        Some(f) => {
+            let ticks: i64 = f
+                .parse()
+                .map_err(|_| format!("invalid fraction in '{}'", s))?;
+            Duration::nanoseconds(ticks * 100)
+        }
+        None => Duration::zero(),
+    };
+    zone.localize(NaiveDateTime::new(date, time) + fraction)
+        .ok_or_else(|| format!("invalid timestamp '{}'", s))
+}
+
+fn line_error(line: usize, message: &str) -> DataError {
+    DataError::ParseError(format!("NinjaTrader export line {}: {}", line, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::write_temp;
+    use chrono::FixedOffset;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_minute_bars_are_stamped_at_open() {
+        let path = write_temp(
+            "nt_bars.txt",
+            "20240305 093100;4700.25;4701.00;4699.75;4700.50;120\n\
+             20240305 093200;4700.50;4702.00;4700.00;4701.75;95\n",
+        );
+        let est = SourceZone::Offset(FixedOffset::west_opt(5 * 3600).unwrap());
+        let bars = load_bars(&path, "ES", est, None).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(bars.len(), 2);
+        assert_eq!(
+            bars[0].timestamp,
+            "2024-03-05T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(bars[1].close, dec!(4701.75));
+        assert_eq!(bars[1].volume, dec!(95));
+    }
+
+    #[test]
+    fn test_bars_follow_daylight_saving() {
+        // US clocks go forward at 02:00 on 2024-03-10
+        let path = write_temp(
+            "nt_dst.txt",
+            "20240308 093100;4700.25;4701.00;4699.75;4700.50;120\n\
+             20240311 093100;4710.25;4711.00;4709.75;4710.50;95\n",
+        );
+        let zone = SourceZone::Named(chrono_tz::America::New_York);
+        let bars = load_bars(&path, "ES", zone, Some(Timeframe::Minute(1))).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(
+            bars[0].timestamp,
+            "2024-03-08T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(
+            bars[1].timestamp,
+            "2024-03-11T13:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_single_intraday_bar_needs_timeframe() {
+        let path = write_temp(
+            "nt_single.txt",
+            "20240305 100000;4700.25;4701.00;4699.75;4700.50;120\n",
+        );
+        let err = load_bars(&path, "ES", SourceZone::default(), None);
+        let bars = load_bars(
+            &path,
+            "ES",
+            SourceZone::default(),
+            Some(Timeframe::Minute(5)),
+        );
+        std::fs::remove_file(&path).ok();
+
+        assert!(err.is_err());
+        assert_eq!(
+            bars.unwrap()[0].timestamp,
+            "2024-03-05T09:55:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_nt8_and_nt7_ticks() {
+        let utc = SourceZone::default();
+        let nt8 = write_temp(
+            "nt8.txt",
+            "20240305 143000 0500000;4700.25;4700.00;4700.25;3\n",
+        );
+        let nt7 = write_temp("nt7.txt", "20240305 143000;4700.25;3\n");
+        let nt8_ticks = load_ticks(&nt8, "ES", utc).unwrap();
+        let nt7_ticks = load_ticks(&nt7, "ES", utc).unwrap();
+        std::fs::remove_file(&nt8).ok();
+        std::fs::remove_file(&nt7).ok();
+
+        assert_eq!(
+            nt8_ticks[0].timestamp,
+            "2024-03-05T14:30:00.05Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert_eq!(nt8_ticks[0].bid, dec!(4700.00));
+        assert_eq!(nt8_ticks[0].ask, dec!(4700.25));
+        assert_eq!(nt7_ticks[0].bid, dec!(4700.25));
+        assert_eq!(nt7_ticks[0].volume, dec!(3));
+    }
+}
@@ -0,0 +1,245 @@
+//! Adapters for streaming bars and ticks without materializing them.
+
+use chrono::{DateTime, Utc};
+use futures_util::future;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use propbot_core::{Bar, BarStream, DataError, Tick, TickStream, Timeframe};
+
+use crate::aggregation::{infer_timeframe, BarBuilder, BarSpec};
+
+/// Bars read ahead to work out a stream's timeframe before resampling.
+const TIMEFRAME_LOOKAHEAD: usize = 64;
+
+/// Fail the stream if an item is earlier than the one before it.
+///
+/// Streams can't be sorted, so sources that may be out of order (CSV files)
+/// are checked instead of silently producing a broken series.
+pub fn chronological<'a, T: Send + 'a>(
+    input: BoxStream<'a, Result<T, DataError>>,
+    timestamp: fn(&T) -> DateTime<Utc>,
+) -> BoxStream<'a, Result<T, DataError>> {
+    input
+        .scan(Some(DateTime::<Utc>::MIN_UTC), move |last, item| {
+            let Some(previous) = *last else {
+                return future::ready(None);
+            };
+            let item = item.and_then(|value| {
+                let ts = timestamp(&value);
+                if ts < previous {
+                    return Err(DataError::ParseError(format!(
+                        "Data is not in time order: {} follows {}",
+                        ts, previous
+                    )));
+                }
+                Ok(value)
+            });
+            // Stop after the first error
+            *last = item.as_ref().ok().map(timestamp);
+            future::ready(Some(item))
+        })
+        .boxed()
+}
+
+/// Keep the items within `[start, end]` of a time-ordered stream, stopping
+/// once past `end`.
+pub fn within<'a, T: Send + 'a>(
+    input: BoxStream<'a, Result<T, DataError>>,
+    timestamp: fn(&T) -> DateTime<Utc>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> BoxStream<'a, Result<T, DataError>> {
+    input
+        .take_while(move |item| future::ready(item.as_ref().map_or(true, |v| timestamp(v) <= end)))
+        .filter(move |item| future::ready(item.as_ref().map_or(true, |v| timestamp(v) >= start)))
+        .boxed()
+}
+
+/// Convert a bar stream to `timeframe`, like [`crate::aggregation::resample`].
+///
+/// The source timeframe is inferred from the first bars; the stream fails if
+/// `timeframe` can't be built from it.
+pub fn resample_stream<'a>(
+    mut bars: BarStream<'a>,
+    instrument: &'a str,
+    timeframe: Timeframe,
+) -> BarStream<'a> {
+    stream::once(async move {
+        let mut head = Vec::new();
+        while head.len() < TIMEFRAME_LOOKAHEAD {
+            match bars.next().await {
+                Some(Ok(bar)) => head.push(bar),
+                Some(Err(e)) => return failed(e),
+                None => break,
+            }
+        }
+
+        let source = infer_timeframe(&head);
+        let bars = stream::iter(head.into_iter().map(Ok)).chain(bars).boxed();
+        match source {
+            None => bars,
+            Some(source) if source == timeframe => bars,
+            Some(source) if timeframe.builds_from(source) => {
+                build_bars(bars, BarSpec::Time(timeframe), BarBuilder::push_bar)
+            }
+            Some(source) => failed(DataError::NotFound(format!(
+                "Cannot build {} bars for {} from {} data",
+                timeframe, instrument, source
+            ))),
+        }
+    })
+    .flatten()
+    .boxed()
+}
+
+/// Aggregate a bar stream into coarser bars.
+pub fn aggregate_bar_stream<'a>(bars: BarStream<'a>, spec: BarSpec) -> BarStream<'a> {
+    build_bars(bars, spec, BarBuilder::push_bar)
+}
+
+/// Build bars from a tick stream.
+pub fn aggregate_tick_stream<'a>(ticks: TickStream<'a>, spec: BarSpec) -> BarStream<'a> {
+    build_bars(ticks, spec, BarBuilder::push_tick)
+}
+
+/// Feed a stream through a [`BarBuilder`], flushing the last bar at the end.
+fn build_bars<'a, T: Send + 'a>(
+    input: BoxStream<'a, Result<T, DataError>>,
+    spec: BarSpec,
+    push: fn(&mut BarBuilder, &T) -> Vec<Bar>,
+) -> BarStream<'a> {
+    stream::unfold(
+        Some((input, BarBuilder::new(spec))),
+        move |state| async move {
+            let (mut input, mut builder) = state?;
+            loop {
+                match input.next().await {
+                    Some(Ok(item)) => {
+                        let closed = push(&mut builder, &item);
+                        if !closed.is_empty() {
+                            let closed: Vec<_> = closed.into_iter().map(Ok).collect();
+                            return Some((closed, Some((input, builder))));
+                        }
+                    }
+                    Some(Err(e)) => return Some((vec![Err(e)], None)),
+                    None => {
+                        let last: Vec<_> = builder.flush().into_iter().map(Ok).collect();
+                        return Some((last, None));
+                    }
+                }
+            }
+        },
+    )
+    .flat_map(stream::iter)
+    .boxed()
+}
+
+/// A stream that yields only `error`.
+pub fn failed<'a, T: Send + 'a>(error: DataError) -> BoxStream<'a, Result<T, DataError>> {
+    stream::iter(vec![Err(error)]).boxed()
+}
+
+/// Flatten a stream of chunks into a stream of items.
+pub fn flatten_chunks<'a, T: Send + 'a, E: Send + 'a>(
+    chunks: BoxStream<'a, Result<Vec<T>, E>>,
+    map_err: fn(E) -> DataError,
+) -> BoxStream<'a, Result<T, DataError>> {
+    chunks
+        .flat_map(move |chunk| {
+            let items: Vec<_> = match chunk {
+                Ok(items) => items.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(map_err(e))],
+            };
+            stream::iter(items)
+        })
+        .boxed()
+}
+
+pub(crate) fn bar_time(bar: &Bar) -> DateTime<Utc> {
+    bar.timestamp
+}
+
+pub(crate) fn tick_time(tick: &Tick) -> DateTime<Utc> {
+    tick.timestamp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use rust_decimal::Decimal;
+    use rust_decimal_macros::dec;
+
+    fn minute_bars(count: i64) -> Vec<Bar> {
+        let start: DateTime<Utc> = "2024-01-02T14:00:00Z".parse().unwrap();
+        (0..count)
+            .map(|i| Bar {
+                instrument: "ES".to_string(),
+                timestamp: start + Duration::minutes(i),
+                open: dec!(4700) + Decimal::from(i),
+                high: dec!(4701) + Decimal::from(i),
+                low: dec!(4699) + Decimal::from(i),
+                close: dec!(4700.5) + Decimal::from(i),
+                volume: dec!(1),
+            })
+            .collect()
+    }
+
+    fn to_stream(bars: Vec<Bar>) -> BarStream<'static> {
+        stream::iter(bars.into_iter().map(Ok)).boxed()
+    }
+
+    #[tokio::test]
+    async fn test_resample_stream_matches_batch_resample() {
+        let bars = minute_bars(150);
+        let batch = crate::aggregation::resample(bars.clone(), Timeframe::Minute(5)).unwrap();
+        let streamed: Vec<Bar> = resample_stream(to_stream(bars), "ES", Timeframe::Minute(5))
+            .map(Result::unwrap)
+            .collect()
+            .await;
+
+        assert_eq!(streamed.len(), batch.len());
+        assert!(streamed.iter().zip(&batch).all(|(s, b)| (
+            s.timestamp,
+            s.open,
+            s.high,
+            s.low,
+            s.close,
+            s.volume
+        ) == (
+            b.timestamp,
+            b.open,
+            b.high,
+            b.low,
+            b.close,
+            b.volume
+        )));
+
+        let finer: Vec<_> = resample_stream(to_stream(batch), "ES", Timeframe::Minute(1))
+            .collect()
+            .await;
+        assert!(matches!(finer.as_slice(), [Err(DataError::NotFound(_))]));
+    }
+
+    #[tokio::test]
+    async fn test_chronological_stops_at_first_out_of_order_item() {
+        let mut bars = minute_bars(5);
+        bars.swap(2, 3);
+        let items: Vec<_> = chronological(to_stream(bars.clone()), bar_time)
+            .collect()
+            .await;
+
+        assert_eq!(items.len(), 4);
+        assert!(items[..3].iter().all(Result::is_ok));
+        assert!(items[3].is_err());
+
+        let ranged: Vec<_> = within(
+            to_stream(minute_bars(10)),
+            bar_time,
+            bars[1].timestamp,
+            bars[4].timestamp,
+        )
+        .collect()
+        .await;
+        assert_eq!(ranged.len(), 4);
+    }
+}
@@ -0,0 +1,387 @@
+//! Reproducible synthetic bar data for exercising strategies and risk rules.
+//!
+//! Prices follow a stochastic model on log price; each bar is built from a few
+//! sub-steps so highs and lows behave like real bars. The same configuration
+//! and seed always produce the same bars.
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use propbot_core::{Bar, DataError, Timeframe};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, StandardNormal};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+
+/// Sub-steps simulated within each bar.
+const STEPS_PER_BAR: usize = 8;
+
+/// Seconds in a year, the unit of drift and volatility.
+const YEAR_SECONDS: f64 = 365.25 * 86_400.0;
+
+/// How prices evolve from bar to bar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceModel {
+    /// Geometric Brownian motion.
+    Gbm,
+    /// GBM switching between a calm regime and a turbulent one with higher
+    /// volatility and reversed drift.
+    RegimeSwitching,
+    /// Ornstein-Uhlenbeck process on log price, pulled towards `mean_price`.
+    MeanReverting,
+}
+
+impl std::fmt::Display for PriceModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PriceModel::Gbm => write!(f, "gbm"),
+            PriceModel::RegimeSwitching => write!(f, "regime"),
+            PriceModel::MeanReverting => write!(f, "mean_reverting"),
+        }
+    }
+}
+
+impl std::str::FromStr for PriceModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "gbm" => Ok(PriceModel::Gbm),
+            "regime" | "regime_switching" => Ok(PriceModel::RegimeSwitching),
+            "mean_reverting" | "ou" => Ok(PriceModel::MeanReverting),
+            other => Err(format!(
+                "Unknown price model: {} (expected gbm, regime or mean_reverting)",
+                other
+            )),
+        }
+    }
+}
+
+/// Settings for [`generate_bars`].
+#[derive(Debug, Clone)]
+pub struct SyntheticConfig {
+    pub instrument: String,
+    pub model: PriceModel,
+    pub timeframe: Timeframe,
+    /// Time of the first bar (moved forward to the first session if needed).
+    pub start: DateTime<Utc>,
+    /// Number of bars to generate.
+    pub bars: usize,
+    pub initial_price: Decimal,
+    /// Annualized drift of log price.
+    pub drift: f64,
+    /// Annualized volatility of log price.
+    pub volatility: f64,
+    /// Prices are rounded to a multiple of this.
+    pub tick_size: Decimal,
+    /// Average volume per bar.
+    pub volume: f64,
+    /// Trading session in UTC as `(open, close)`; may wrap past midnight.
+    /// Bars are generated around the clock when `None`.
+    pub session: Option<(NaiveTime, NaiveTime)>,
+    /// Skip Saturdays and Sundays.
+    pub skip_weekends: bool,
+    /// Standard deviation of the log price jump when a session opens.
+    pub gap_volatility: f64,
+    /// Chance of leaving a bar out, to mimic gaps in recorded data.
+    pub missing_bar_probability: f64,
+    /// Chance per bar of switching regime ([`PriceModel::RegimeSwitching`]).
+    pub regime_switch_probability: f64,
+    /// Volatility multiplier in the turbulent regime.
+    pub turbulent_volatility_multiplier: f64,
+    /// Annualized speed of mean reversion ([`PriceModel::MeanReverting`]).
+    pub mean_reversion: f64,
+    /// Price the mean-reverting model is pulled towards; defaults to `initial_price`.
+    pub mean_price: Option<Decimal>,
+    pub seed: u64,
+}
+
+impl Default for SyntheticConfig {
+    fn default() -> Self {
+        Self {
+            instrument: "SYN".to_string(),
+            model: PriceModel::Gbm,
+            timeframe: Timeframe::Minute(1),
+            start: DateTime::<Utc>::from_timestamp(1_704_067_200, 0).expect("valid timestamp"),
+            bars: 10_000,
+            initial_price: Decimal::from(100),
+            drift: 0.0,
+            volatility: 0.2,
+            tick_size: Decimal::new(1, 2),
+            volume: 1_000.0,
+            session: None,
+            skip_weekends: false,
+            gap_volatility: 0.0,
+            missing_bar_probability: 0.0,
+            regime_switch_probability: 0.001,
+            turbulent_volatility_multiplier: 3.0,
+            mean_reversion: 5.0,
+            mean_price: None,
+            seed: 0,
+        }
+    }
+}
+
+/// Generate bars as described by `config`.
+pub fn generate_bars(config: &SyntheticConfig) -> Result<Vec<Bar>, DataError> {
+    Ok(SyntheticBars::new(config.clone())?.collect())
+}
+
+/// An iterator producing the bars of a [`SyntheticConfig`] one at a time.
+pub struct SyntheticBars {
+    config: SyntheticConfig,
+    rng: ChaCha8Rng,
+    interval: Duration,
+    /// Bar length in years.
+    dt: f64,
+    mean_log_price: f64,
+    log_price: f64,
+    next_time: DateTime<Utc>,
+    previous_time: Option<DateTime<Utc>>,
+    turbulent: bool,
+    produced: usize,
+}
+
+impl SyntheticBars {
+    pub fn new(config: SyntheticConfig) -> Result<Self, DataError> {
+        let invalid = |message: &str| Err(DataError::ParseError(message.to_string()));
+        let Some(interval) = config.timeframe.duration() else {
+            return invalid("Synthetic data needs a fixed-length timeframe");
+        };
+        match config.session {
+            Some(_) if interval >= Duration::days(1) => {
+                return invalid("Sessions need an intraday timeframe")
+            }
+            Some((open, close)) if open == close => return invalid("The session is empty"),
+            _ => {}
+        }
+        if config.tick_size <= Decimal::ZERO || config.initial_price <= Decimal::ZERO {
+            return invalid("Tick size and initial price must be positive");
+        }
+        if config.volatility < 0.0 || config.gap_volatility < 0.0 || config.volume < 0.0 {
+            return invalid("Volatility and volume can't be negative");
+        }
+        for p in [
+            config.missing_bar_probability,
+            config.regime_switch_probability,
+        ] {
+            if !(0.0..1.0).contains(&p) {
+                return invalid("Probabilities must be in [0, 1)");
+            }
+        }
+
+        let log = |price: Decimal| price.to_f64().unwrap_or(1.0).ln();
+        let mut bars = Self {
+            rng: ChaCha8Rng::seed_from_u64(config.seed),
+            interval,
+            dt: interval.num_seconds() as f64 / YEAR_SECONDS,
+            mean_log_price: log(config.mean_price.unwrap_or(config.initial_price)),
+            log_price: log(config.initial_price),
+            next_time: config.start,
+            previous_time: None,
+            turbulent: false,
+            produced: 0,
+            config,
+        };
+        bars.next_time = bars.next_trading_time(bars.config.start);
+        Ok(bars)
+    }
+
+    fn in_session(&self, time: DateTime<Utc>) -> bool {
+        if self.config.skip_weekends && matches!(time.weekday(), Weekday::Sat | Weekday::Sun) {
+            return false;
+        }
+        match self.config.session {
+            None => true,
+            Some((open, close)) if open <= close => time.time() >= open && time.time() < close,
+            Some((open, close)) => time.time() >= open || time.time() < close,
+        }
+    }
+
+    /// The first bar time at or after `time` that falls in a session.
+    fn next_trading_time(&self, mut time: DateTime<Utc>) -> DateTime<Utc> {
+        // At most a week of bars to skip, since every week has a session day
+        for _ in 0..(7 * 86_400 / self.interval.num_seconds().max(1)) {
+            if self.in_session(time) {
+                break;
+            }
+            time += self.interval;
+        }
+        time
+    }
+
+    fn normal(&mut self) -> f64 {
+        StandardNormal.sample(&mut self.rng)
+    }
+
+    /// Move log price forward by `dt` years.
+    fn step(&mut self, dt: f64) {
+        let (drift, volatility) = match self.config.model {
+            PriceModel::Gbm => (self.config.drift, self.config.volatility),
+            PriceModel::RegimeSwitching if self.turbulent => (
+                -self.config.drift,
+                self.config.volatility * self.config.turbulent_volatility_multiplier,
+            ),
+            PriceModel::RegimeSwitching => (self.config.drift, self.config.volatility),
+            PriceModel::MeanReverting => (
+                self.config.mean_reversion * (self.mean_log_price - self.log_price),
+                self.config.volatility,
+            ),
+        };
+        let shock = self.normal();
+        self.log_price +=
+            (drift - volatility * volatility / 2.0) * dt + volatility * dt.sqrt() * shock;
+    }
+
+    fn price(&self, log_price: f64) -> Decimal {
+        let tick = self.config.tick_size;
+        let price = Decimal::from_f64(log_price.exp()).unwrap_or(tick);
+        ((price / tick).round() * tick).max(tick)
+    }
+}
+
+impl Iterator for SyntheticBars {
+    type Item = Bar;
+
+    fn next(&mut self) -> Option<Bar> {
+        while self.produced < self.config.bars {
+            let timestamp = self.next_time;
+            self.next_time = self.next_trading_time(timestamp + self.interval);
+
+            let session_open = self
+                .previous_time
+                .is_some_and(|previous| timestamp - previous > self.interval);
+            self.previous_time = Some(timestamp);
+            if session_open && self.config.gap_volatility > 0.0 {
+                self.log_price += self.config.gap_volatility * self.normal();
+            }
+            if self.config.model == PriceModel::RegimeSwitching
+                && self.rng.gen_bool(self.config.regime_switch_probability)
+            {
+                self.turbulent = !self.turbulent;
+            }
+
+            let open = self.log_price;
+            let (mut high, mut low) = (open, open);
+            for _ in 0..STEPS_PER_BAR {
+                self.step(self.dt / STEPS_PER_BAR as f64);
+                high = high.max(self.log_price);
+                low = low.min(self.log_price);
+            }
+
+            // Busier bars when prices move more
+            let range = (high - low) / (self.config.volatility * self.dt.sqrt()).max(f64::EPSILON);
+            let volume = self.config.volume * self.rng.gen_range(0.5..1.5) * (0.5 + range / 4.0);
+
+            if self.config.missing_bar_probability > 0.0
+                && self.rng.gen_bool(self.config.missing_bar_probability)
+            {
+                continue;
+            }
+            self.produced += 1;
+            return Some(Bar {
+                instrument: self.config.instrument.clone(),
+                timestamp,
+                open: self.price(open),
+                high: self.price(high),
+                low: self.price(low),
+                close: self.price(self.log_price),
+                volume: Decimal::from_f64(volume.round()).unwrap_or_default(),
+            });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn key(bar: &Bar) -> (DateTime<Utc>, Decimal, Decimal, Decimal, Decimal, Decimal) {
+        (
+            bar.timestamp,
+            bar.open,
+            bar.high,
+            bar.low,
+            bar.close,
+            bar.volume,
+        )
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_bars() {
+        for model in [
+            PriceModel::Gbm,
+            PriceModel::RegimeSwitching,
+            PriceModel::MeanReverting,
+        ] {
+            let config = SyntheticConfig {
+                model,
+                bars: 2_000,
+                tick_size: dec!(0.25),
+                initial_price: dec!(4700),
+                seed: 7,
+                ..Default::default()
+            };
+            let a = generate_bars(&config).unwrap();
+            let b = generate_bars(&config).unwrap();
+            let c = generate_bars(&SyntheticConfig { seed: 8, ..config }).unwrap();
+
+            assert_eq!(a.len(), 2_000);
+            assert!(a.iter().map(key).eq(b.iter().map(key)));
+            assert!(!a.iter().map(key).eq(c.iter().map(key)));
+            assert!(a.iter().all(|bar| bar.low <= bar.open.min(bar.close)
+                && bar.high >= bar.open.max(bar.close)
+                && (bar.close / dec!(0.25)).fract().is_zero()));
+        }
+    }
+
+    #[test]
+    fn test_sessions_and_missing_bars() {
+        let config = SyntheticConfig {
+            timeframe: Timeframe::Minute(5),
+            // Friday 2024-01-05
+            start: "2024-01-05T00:00:00Z".parse().unwrap(),
+            bars: 200,
+            session: Some((
+                NaiveTime::from_hms_opt(14, 30, 0).unwrap(),
+                NaiveTime::from_hms_opt(21, 0, 0).unwrap(),
+            )),
+            skip_weekends: true,
+            gap_volatility: 0.01,
+            missing_bar_probability: 0.1,
+            ..Default::default()
+        };
+        let bars = generate_bars(&config).unwrap();
+
+        assert_eq!(bars.len(), 200);
+        assert_eq!(bars[0].timestamp.to_rfc3339(), "2024-01-05T14:30:00+00:00");
+        assert!(bars.iter().all(|b| {
+            let t = b.timestamp.time();
+            t >= NaiveTime::from_hms_opt(14, 30, 0).unwrap()
+                && t < NaiveTime::from_hms_opt(21, 0, 0).unwrap()
+                && !matches!(b.timestamp.weekday(), Weekday::Sat | Weekday::Sun)
+        }));
+        // Friday's session holds 78 bars, so the rest fall on Monday and Tuesday
+        assert!(bars.iter().any(|b| b.timestamp.weekday() == Weekday::Mon));
+        assert!(bars
+            .windows(2)
+            .any(|w| w[1].timestamp - w[0].timestamp == Duration::minutes(10)));
+    }
+
+    #[test]
+    fn test_mean_reverting_stays_near_mean() {
+        let config = SyntheticConfig {
+            model: PriceModel::MeanReverting,
+            timeframe: Timeframe::Hour(1),
+            bars: 20_000,
+            volatility: 0.3,
+            mean_reversion: 20.0,
+            seed: 3,
+            ..Default::default()
+        };
+        let bars = generate_bars(&config).unwrap();
+        let average = bars.iter().map(|b| b.close).sum::<Decimal>() / Decimal::from(bars.len());
+        assert!((average - dec!(100)).abs() < dec!(5), "average {}", average);
+    }
+}
@@ -0,0 +1,594 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc, Weekday};
+use propbot_core::{Bar, TradingCalendar};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::aggregation::infer_timeframe;
+
+/// Most bar times checked against the calendar for one gap; longer gaps are
+/// reported without a count. A million one-minute bars is about two years.
+const MAX_GAP_WALK: i64 = 1_000_000;
+
+/// Most bars added to fill one gap; longer gaps are left as they are.
+const MAX_FILL_BARS: usize = 100_000;
+
+/// The kind of problem found in a bar series.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// Same timestamp as an earlier bar.
+    Duplicate,
+    /// Earlier timestamp than the bar before it.
+    OutOfOrder,
+    /// High below open/close/low, or low above open/close.
+    InvalidOhlc,
+    ZeroVolume,
+    /// Missing bars during trading hours.
+    Gap,
+    /// A one-bar move far larger than the series' typical bar-to-bar change.
+    Spike,
+}
+
+/// A single problem found by [`validate_bars`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataIssue {
+    /// Position of the offending bar in the input.
+    pub index: usize,
+    pub timestamp: DateTime<Utc>,
+    pub kind: IssueKind,
+    pub message: String,
+}
+
+/// Result of validating a bar series.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub bars_checked: usize,
+    pub issues: Vec<DataIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Number of issues of a given kind.
+    pub fn count(&self, kind: IssueKind) -> usize {
+        self.issues.iter().filter(|i| i.kind == kind).count()
+    }
+}
+
+/// Validation settings.
+#[derive(Debug, Clone)]
+pub struct ValidationConfig {
+    /// Expected spacing between bars (inferred from the data when `None`).
+    pub bar_interval: Option<Duration>,
+    /// Daily trading window in UTC on Monday–Friday; missing bars outside it
+    /// aren't gaps.
+    pub session: Option<(NaiveTime, NaiveTime)>,
+    /// Exchange calendar; missing bars while it says the market is closed
+    /// (weekends, holidays, after early closes) aren't gaps. Replaces the
+    /// Monday–Friday rule and combines with `session` if both are set.
+    ///
+    /// With neither a session nor a calendar there is no telling overnight
+    /// breaks from missing data, so gaps aren't checked or filled.
+    pub calendar: Option<TradingCalendar>,
+    /// A bar is a spike if it moves more than this many times the median
+    /// absolute close-to-close change away from both the previous and the
+    /// next close.
+    pub spike_multiple: Decimal,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            bar_interval: None,
+            session: None,
+            calendar: None,
+            spike_multiple: Decimal::from(10),
+        }
+    }
+}
+
+impl ValidationConfig {
+    /// Whether gaps can be checked: a session or calendar is set.
+    pub fn checks_gaps(&self) -> bool {
+        self.session.is_some() || self.calendar.is_some()
+    }
+
+    /// Whether a bar would be expected at `timestamp`.
+    fn in_session(&self, timestamp: DateTime<Utc>) -> bool {
+        let open = match &self.calendar {
+            Some(calendar) => calendar.is_open(timestamp),
+            None if self.session.is_some() => {
+                !matches!(timestamp.weekday(), Weekday::Sat | Weekday::Sun)
+            }
+            None => false,
+        };
+        if !open {
+            return false;
+        }
+        match self.session {
+            Some((start, end)) if start <= end => {
+                timestamp.time() >= start && timestamp.time() < end
+            }
+            // Window wraps past midnight (e.g. 22:00-21:00)
+            Some((start, end)) => timestamp.time() >= start || timestamp.time() < end,
+            None => true,
+        }
+    }
+}
+
+/// Check a bar series, in the order given, for data quality problems.
+pub fn validate_bars(bars: &[Bar], config: &ValidationConfig) -> ValidationReport {
+    let mut issues = Vec::new();
+    let mut seen = HashSet::new();
+    let interval = config
+        .bar_interval
+        .or_else(|| infer_timeframe(bars).and_then(|t| t.duration()));
+    let median_move = median_abs_change(bars);
+
+    for (index, bar) in bars.iter().enumerate() {
+        let mut issue = |kind, message: String| {
+            issues.push(DataIssue {
+                index,
+                timestamp: bar.timestamp,
+                kind,
+                message,
+            })
+        };
+
+        if !seen.insert(bar.timestamp) {
+            issue(IssueKind::Duplicate, "Duplicate timestamp".to_string());
+        }
+
+        let valid_high = bar.high >= bar.open.max(bar.close).max(bar.low);
+        let valid_low = bar.low <= bar.open.min(bar.close);
+        if !valid_high || !valid_low {
+            issue(
+                IssueKind::InvalidOhlc,
+                format!(
+                    "Inconsistent OHLC: O={} H={} L={} C={}",
+                    bar.open, bar.high, bar.low, bar.close
+                ),
+            );
+        }
+
+        if bar.volume.is_zero() {
+            issue(IssueKind::ZeroVolume, "Zero volume".to_string());
+        }
+
+        let Some(prev) = index.checked_sub(1).map(|i| &bars[i]) else {
+            continue;
+        };
+
+        if bar.timestamp < prev.timestamp {
+            issue(
+                IssueKind::OutOfOrder,
+                format!("Earlier than previous bar at {}", prev.timestamp),
+            );
+        }
+
+        if let Some(interval) = interval {
+            match missing_in_session(prev.timestamp, bar.timestamp, interval, config) {
+                Some(0) => {}
+                Some(missing) => issue(
+                    IssueKind::Gap,
+                    format!("{} missing bar(s) since {}", missing, prev.timestamp),
+                ),
+                None => issue(
+                    IssueKind::Gap,
+                    format!(
+                        "{} day gap since {}, too long to count missing bars",
+                        (bar.timestamp - prev.timestamp).num_days(),
+                        prev.timestamp
+                    ),
+                ),
+            }
+        }
+
+        // A spike jumps away from the previous close and doesn't stay there
+        if let Some(median) = median_move.filter(|m| !m.is_zero()) {
+            let threshold = median * config.spike_multiple;
+            let excursion = |close: Decimal| (bar.high - close).abs().max((bar.low - close).abs());
+            let reverts = bars
+                .get(index + 1)
+                .is_none_or(|next| excursion(next.close) > threshold);
+            let excursion = excursion(prev.close);
+            if excursion > threshold && reverts {
+                issue(
+                    IssueKind::Spike,
+                    format!(
+                        "Moved {} from previous close {} (typical move {})",
+                        excursion, prev.close, median
+                    ),
+                );
+            }
+        }
+    }
+
+    ValidationReport {
+        bars_checked: bars.len(),
+        issues,
+    }
+}
+
+/// Which repairs to apply.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RepairOptions {
+    /// Keep only the first bar for each timestamp.
+    pub dedupe: bool,
+    /// Drop bars with inconsistent OHLC.
+    pub drop_invalid: bool,
+    pub drop_zero_volume: bool,
+    pub drop_spikes: bool,
+    /// Fill in-session gaps with flat bars at the previous close and zero volume.
+    pub forward_fill: bool,
+}
+
+/// Repair a bar series. The result is always sorted by timestamp.
+pub fn repair_bars(bars: Vec<Bar>, config: &ValidationConfig, options: RepairOptions) -> Vec<Bar> {
+    let mut bars = bars;
+    bars.sort_by_key(|b| b.timestamp);
+
+    let report = validate_bars(&bars, config);
+    let mut drop: HashSet<usize> = HashSet::new();
+    for issue in &report.issues {
+        let remove = match issue.kind {
+            IssueKind::Duplicate => options.dedupe,
+            IssueKind::InvalidOhlc => options.drop_invalid,
+            IssueKind::ZeroVolume => options.drop_zero_volume,
+            IssueKind::Spike => options.drop_spikes,
+            IssueKind::OutOfOrder | IssueKind::Gap => false,
+        };
+        if remove {
+            drop.insert(issue.index);
+        }
+    }
+    let bars: Vec<Bar> = bars
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !drop.contains(i))
+        .map(|(_, b)| b)
+        .collect();
+
+    let interval = config
+        .bar_interval
+        .or_else(|| infer_timeframe(&bars).and_then(|t| t.duration()));
+    match interval {
+        Some(interval) if options.forward_fill => forward_fill(bars, interval, config),
+        _ => bars,
+    }
+}
+
+fn forward_fill(bars: Vec<Bar>, interval: Duration, config: &ValidationConfig) -> Vec<Bar> {
+    let mut filled: Vec<Bar> = Vec::with_capacity(bars.len());
+    for bar in bars {
+        if let Some(prev) = filled.last().cloned() {
+            match missing_in_session(prev.timestamp, bar.timestamp, interval, config) {
+                Some(missing) if missing <= MAX_FILL_BARS => {}
+                _ => {
+                    tracing::warn!(
+                        from = %prev.timestamp,
+                        to = %bar.timestamp,
+                        "Gap too long to fill; leaving it"
+                    );
+                    filled.push(bar);
+                    continue;
+                }
+            }
+            let mut t = prev.timestamp + interval;
+            while t < bar.timestamp {
+                if config.in_session(t) {
+                    filled.push(Bar {
+                        timestamp: t,
+                        open: prev.close,
+                        high: prev.close,
+                        low: prev.close,
+                        volume: Decimal::ZERO,
+                        ..prev.clone()
+                    });
+                }
+                t += interval;
+            }
+        }
+        filled.push(bar);
+    }
+    filled
+}
+
+/// Number of expected in-session bar times strictly between two bars, or
+/// `None` if the gap is too long to check against the calendar.
+///
+/// Without a calendar the session is the same every weekday, so the bar times
+/// are counted a day at a time rather than walked one by one.
+fn missing_in_session(
+    prev: DateTime<Utc>,
+    next: DateTime<Utc>,
+    interval: Duration,
+    config: &ValidationConfig,
+) -> Option<usize> {
+    if interval <= Duration::zero() || next <= prev || !config.checks_gaps() {
+        return Some(0);
+    }
+    if config.calendar.is_none() {
+        return config
+            .session
+            .map(|session| missing_in_daily_session(prev, next, interval, session));
+    }
+    let step = interval.num_milliseconds().max(1);
+    if (next - prev).num_milliseconds() / step > MAX_GAP_WALK {
+        return None;
+    }
+    let mut missing = 0;
+    let mut t = prev + interval;
+    while t < next {
+        if config.in_session(t) {
+            missing += 1;
+        }
+        t += interval;
+    }
+    Some(missing)
+}
+
+/// [`missing_in_session`] for a fixed daily window on Monday–Friday.
+fn missing_in_daily_session(
+    prev: DateTime<Utc>,
+    next: DateTime<Utc>,
+    interval: Duration,
+    (start, end): (NaiveTime, NaiveTime),
+) -> usize {
+    let start = start.signed_duration_since(NaiveTime::MIN);
+    let end = end.signed_duration_since(NaiveTime::MIN);
+    let windows = if start <= end {
+        vec![(start, end)]
+    } else {
+        // Window wraps past midnight: the early morning and the evening
+        vec![(Duration::zero(), end), (start, Duration::days(1))]
+    };
+
+    let on_day = |day: NaiveDate| -> usize {
+        if matches!(day.weekday(), Weekday::Sat | Weekday::Sun) {
+            return 0;
+        }
+        let midnight = day.and_time(NaiveTime::MIN).and_utc();
+        windows
+            .iter()
+            .map(|(from, to)| {
+                bar_times_within(prev, next, interval, midnight + *from, midnight + *to)
+            })
+            .sum()
+    };
+    // Bar times fall at the same points every week if a week is whole bars
+    let weekly = Duration::weeks(1).num_milliseconds() % interval.num_milliseconds().max(1) == 0;
+
+    let mut missing = 0;
+    let mut day = prev.date_naive();
+    while day <= next.date_naive() {
+        let midnight = day.and_time(NaiveTime::MIN).and_utc();
+        let weeks = (next - midnight).num_days() / 7;
+        if weekly && midnight >= prev + interval && weeks > 1 {
+            // Whole weeks inside the gap all count the same
+            let week: usize = (0..7).map(|d| on_day(day + Duration::days(d))).sum();
+            missing += week * weeks as usize;
+            day += Duration::days(7 * weeks);
+            continue;
+        }
+        missing += on_day(day);
+        let Some(next_day) = day.succ_opt() else {
+            break;
+        };
+        day = next_day;
+    }
+    missing
+}
+
+/// Number of bar times `prev + k * interval` (k ≥ 1, before `next`) in `from..to`.
+fn bar_times_within(
+    prev: DateTime<Utc>,
+    next: DateTime<Utc>,
+    interval: Duration,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> usize {
+    let from = from.max(prev + interval);
+    let to = to.min(next);
+    if to <= from {
+        return 0;
+    }
+    let step = interval.num_milliseconds().max(1);
+    // Bar times at or after a point: ceil((point - prev) / step)
+    let first = |point: DateTime<Utc>| ((point - prev).num_milliseconds() + step - 1) / step;
+    (first(to) - first(from)) as usize
+}
+
+/// Median absolute close-to-close change, ignoring unchanged closes.
+fn median_abs_change(bars: &[Bar]) -> Option<Decimal> {
+    let mut moves: Vec<Decimal> = bars
+        .windows(2)
+        .map(|w| (w[1].close - w[0].close).abs())
+        .filter(|m| !m.is_zero())
+        .collect();
+    if moves.is_empty() {
+        return None;
+    }
+    moves.sort();
+    Some(moves[moves.len() / 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn bar(minute: i64, close: Decimal) -> Bar {
+        Bar {
+            instrument: "ES".to_string(),
+            timestamp: "2024-01-02T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+                + Duration::minutes(minute),
+            open: close,
+            high: close + dec!(0.5),
+            low: close - dec!(0.5),
+            close,
+            volume: dec!(100),
+        }
+    }
+
+    fn series() -> Vec<Bar> {
+        (0..10)
+            .map(|m| bar(m, dec!(4700) + Decimal::from(m % 2)))
+            .collect()
+    }
+
+    /// 14:30-21:00 UTC on weekdays.
+    fn day_session() -> ValidationConfig {
+        ValidationConfig {
+            session: Some((
+                NaiveTime::from_hms_opt(14, 30, 0).unwrap(),
+                NaiveTime::from_hms_opt(21, 0, 0).unwrap(),
+            )),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_clean_series_passes() {
+        let report = validate_bars(&series(), &ValidationConfig::default());
+        assert!(report.is_clean(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn test_reports_each_kind_of_issue() {
+        let mut bars = series();
+        bars[2].high = bars[2].close - dec!(1);
+        bars[3].volume = Decimal::ZERO;
+        bars[5].close = dec!(4800);
+        bars[5].high = dec!(4800);
+        bars.remove(7);
+        bars.push(bar(1, dec!(4701)));
+
+        let report = validate_bars(&bars, &day_session());
+        assert_eq!(report.count(IssueKind::InvalidOhlc), 1);
+        assert_eq!(report.count(IssueKind::ZeroVolume), 1);
+        assert_eq!(report.count(IssueKind::Gap), 1);
+        assert_eq!(report.count(IssueKind::OutOfOrder), 1);
+        assert_eq!(report.count(IssueKind::Duplicate), 1);
+        // Only the bar at 4800, not the one that drops back
+        assert_eq!(report.count(IssueKind::Spike), 1);
+    }
+
+    #[test]
+    fn test_gaps_outside_session_are_ignored() {
+        let bars = vec![bar(0, dec!(4700)), bar(120, dec!(4701))];
+        let config = ValidationConfig {
+            bar_interval: Some(Duration::minutes(1)),
+            session: Some((
+                NaiveTime::from_hms_opt(14, 30, 0).unwrap(),
+                NaiveTime::from_hms_opt(14, 31, 0).unwrap(),
+            )),
+            ..Default::default()
+        };
+        assert!(validate_bars(&bars, &config).is_clean());
+    }
+
+    #[test]
+    fn test_gaps_need_a_session_or_calendar() {
+        // Overnight from one close to the next open
+        let bars = vec![bar(390, dec!(4700)), bar(1_440, dec!(4701))];
+        let config = ValidationConfig {
+            bar_interval: Some(Duration::minutes(1)),
+            ..Default::default()
+        };
+        assert!(!config.checks_gaps());
+        assert!(validate_bars(&bars, &config).is_clean());
+
+        let repaired = repair_bars(
+            bars,
+            &config,
+            RepairOptions {
+                forward_fill: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(repaired.len(), 2);
+    }
+
+    #[test]
+    fn test_far_future_timestamp_is_one_gap() {
+        let mut far = bar(1, dec!(4701));
+        far.timestamp = "9999-01-04T14:30:00Z".parse().unwrap();
+        let bars = vec![bar(0, dec!(4700)), far];
+
+        // Counted a day at a time with a fixed session
+        let session = ValidationConfig {
+            bar_interval: Some(Duration::minutes(1)),
+            ..day_session()
+        };
+        let report = validate_bars(&bars, &session);
+        assert_eq!(report.count(IssueKind::Gap), 1);
+
+        // Not walked through the calendar, and not filled
+        let config = ValidationConfig {
+            bar_interval: Some(Duration::minutes(1)),
+            calendar: Some(TradingCalendar::always_open()),
+            ..Default::default()
+        };
+        let report = validate_bars(&bars, &config);
+        assert_eq!(report.count(IssueKind::Gap), 1);
+        assert!(report.issues[0].message.contains("too long"));
+
+        let options = RepairOptions {
+            forward_fill: true,
+            ..Default::default()
+        };
+        assert_eq!(repair_bars(bars.clone(), &config, options).len(), 2);
+        assert_eq!(repair_bars(bars, &session, options).len(), 2);
+    }
+
+    #[test]
+    fn test_daily_session_count_matches_walk() {
+        // Friday 20:00 to a Tuesday 15:00 weeks later, over a session that
+        // wraps midnight
+        let config = ValidationConfig {
+            session: Some((
+                NaiveTime::from_hms_opt(23, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+            )),
+            ..Default::default()
+        };
+        let prev = "2024-01-05T20:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let next = "2024-02-06T15:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let interval = Duration::minutes(5);
+        let mut walked = 0;
+        let mut t = prev + interval;
+        while t < next {
+            walked += usize::from(config.in_session(t));
+            t += interval;
+        }
+        assert_eq!(
+            missing_in_session(prev, next, interval, &config),
+            Some(walked)
+        );
+    }
+
+    #[test]
+    fn test_repair_dedupes_sorts_and_fills() {
+        let mut bars = series();
+        bars.remove(4);
+        bars.swap(0, 1);
+        bars.push(bar(2, dec!(4750)));
+
+        let options = RepairOptions {
+            dedupe: true,
+            forward_fill: true,
+            ..Default::default()
+        };
+        let repaired = repair_bars(bars, &day_session(), options);
+
+        assert_eq!(repaired.len(), 10);
+        assert!(repaired.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+        assert_eq!(repaired[2].close, dec!(4700));
+        assert_eq!(repaired[4].close, repaired[3].close);
+        assert_eq!(repaired[4].volume, Decimal::ZERO);
+    }
+}
@@ -0,0 +1,333 @@
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
+use futures_util::TryStreamExt;
+use propbot_core::*;
+use propbot_indicators::atr::Atr;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::metrics::r_multiples;
+
+/// Upper bounds of the holding time buckets, in minutes, with their labels.
+const HOLDING_TIMES: &[(i64, &str)] = &[
+    (5, "< 5m"),
+    (15, "5-15m"),
+    (60, "15m-1h"),
+    (240, "1-4h"),
+    (1_440, "4h-1d"),
+    (7_200, "1-5d"),
+    (i64::MAX, "> 5d"),
+];
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// When and in which conditions a strategy's trades make money. Each
+/// breakdown only lists buckets that have trades, in their natural order.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TradeAnalytics {
+    /// By entry hour (UTC).
+    pub by_hour: Vec<BucketStats>,
+    /// By entry weekday (UTC).
+    pub by_weekday: Vec<BucketStats>,
+    /// By entry month of the year.
+    pub by_month: Vec<BucketStats>,
+    pub by_holding_time: Vec<BucketStats>,
+    /// Long or short.
+    pub by_direction: Vec<BucketStats>,
+    /// By ATR quartile at entry, relative to the ATR over the whole series.
+    /// Empty without bar data.
+    pub by_volatility: Vec<BucketStats>,
+}
+
+/// Performance of the trades in one bucket.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BucketStats {
+    pub label: String,
+    pub trades: usize,
+    pub net_pnl: Decimal,
+    pub avg_pnl: Decimal,
+    pub win_rate: Decimal,
+    /// Average R-multiple, with R sized from all the trades; `None` when
+    /// none of them lost.
+    pub expectancy: Option<Decimal>,
+}
+
+/// ATR after each bar, built up as bars go by so it can follow a streamed
+/// backtest.
+#[derive(Debug, Clone)]
+pub struct AtrSeries {
+    atr: Atr,
+    timeframe: Timeframe,
+    /// ATR as of each bar's close.
+    values: Vec<(DateTime<Utc>, Decimal)>,
+}
+
+impl AtrSeries {
+    /// ATR over `period` bars of `timeframe`.
+    pub fn new(period: usize, timeframe: Timeframe) -> Self {
+        Self {
+            atr: Atr::new(period),
+            timeframe,
+            values: Vec::new(),
+        }
+    }
+
+    /// ATR over a stream of bars, read a bar at a time.
+    pub async fn from_stream(
+        period: usize,
+        timeframe: Timeframe,
+        mut bars: BarStream<'_>,
+    ) -> Result<Self, DataError> {
+        let mut series = Self::new(period, timeframe);
+        while let Some(bar) = bars.try_next().await? {
+            series.observe(&bar);
+        }
+        Ok(series)
+    }
+
+    /// Feed the next bar, in time order.
+    pub fn observe(&mut self, bar: &Bar) {
+        if let Some(atr) = self.atr.next_hlc(bar.high, bar.low, bar.close) {
+            let close = self.timeframe.period_end(bar.timestamp);
+            self.values.push((close, atr));
+        }
+    }
+
+    /// Whether no bar has produced an ATR yet.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// ATR as of the last bar that closed at or before `time`, so a trade
+    /// entered mid-bar doesn't see the rest of that bar.
+    pub fn at(&self, time: DateTime<Utc>) -> Option<Decimal> {
+        let idx = self.values.partition_point(|(t, _)| *t <= time);
+        Some(self.values.get(idx.checked_sub(1)?)?.1)
+    }
+
+    /// The 25th, 50th and 75th percentile ATR.
+    fn quartiles(&self) -> Option<[Decimal; 3]> {
+        if self.values.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<Decimal> = self.values.iter().map(|(_, v)| *v).collect();
+        sorted.sort();
+        let pick = |q: usize| sorted[(sorted.len() - 1) * q / 4];
+        Some([pick(1), pick(2), pick(3)])
+    }
+}
+
+/// Break trades down by entry time, holding time, direction and, given an
+/// ATR series over the same period, volatility regime at entry.
+pub fn analyze_trades(trades: &[Trade], atr: Option<&AtrSeries>) -> TradeAnalytics {
+    let r = r_multiples(trades);
+    let stats = |key: &dyn Fn(&Trade) -> Option<(usize, String)>| breakdown(trades, &r, key);
+
+    let by_volatility = match atr.and_then(|a| a.quartiles().map(|q| (a, q))) {
+        Some((atr, quartiles)) => stats(&|t| {
+            let value = atr.at(t.entry_time)?;
+            let quartile = quartiles.iter().filter(|q| value > **q).count();
+            let label = match quartile {
+                0 => "Q1 (low ATR)".to_string(),
+                3 => "Q4 (high ATR)".to_string(),
+                n => format!("Q{}", n + 1),
+            };
+            Some((quartile, label))
+        }),
+        None => Vec::new(),
+    };
+
+    TradeAnalytics {
+        by_hour: stats(&|t| {
+            let hour = t.entry_time.hour() as usize;
+            Some((hour, format!("{:02}:00", hour)))
+        }),
+        by_weekday: stats(&|t| {
+            let day = t.entry_time.weekday().num_days_from_monday() as usize;
+            Some((day, WEEKDAYS[day].to_string()))
+        }),
+        by_month: stats(&|t| {
+            let month = t.entry_time.month0() as usize;
+            Some((month, MONTHS[month].to_string()))
+        }),
+        by_holding_time: stats(&|t| {
+            let minutes = (t.exit_time - t.entry_time)
+                .max(Duration::zero())
+                .num_minutes();
+            HOLDING_TIMES
+                .iter()
+                .position(|(limit, _)| minutes < *limit)
+                .map(|i| (i, HOLDING_TIMES[i].1.to_string()))
+        }),
+        by_direction: stats(&|t| {
+            Some(match t.side {
+                Side::Buy => (0, "Long".to_string()),
+                Side::Sell => (1, "Short".to_string()),
+            })
+        }),
+        by_volatility,
+    }
+}
+
+/// Group trades by `key` (sort order, label), skipping trades it returns
+/// `None` for. `r` holds each trade's R-multiple, or is empty.
+fn breakdown(
+    trades: &[Trade],
+    r: &[Decimal],
+    key: &dyn Fn(&Trade) -> Option<(usize, String)>,
+) -> Vec<BucketStats> {
+    let mut buckets: BTreeMap<usize, (String, Vec<usize>)> = BTreeMap::new();
+    for (i, trade) in trades.iter().enumerate() {
+        if let Some((order, label)) = key(trade) {
+            buckets
+                .entry(order)
+                .or_insert((label, Vec::new()))
+                .1
+                .push(i);
+        }
+    }
+
+    buckets
+        .into_values()
+        .map(|(label, indices)| {
+            let n = Decimal::from(indices.len());
+            let net_pnl: Decimal = indices.iter().map(|&i| trades[i].net_pnl()).sum();
+            let winners = indices
+                .iter()
+                .filter(|&&i| trades[i].net_pnl() > Decimal::ZERO)
+                .count();
+            let expectancy =
+                (!r.is_empty()).then(|| indices.iter().map(|&i| r[i]).sum::<Decimal>() / n);
+            BucketStats {
+                label,
+                trades: indices.len(),
+                net_pnl,
+                avg_pnl: net_pnl / n,
+                win_rate: Decimal::from(winners) / n * dec!(100),
+                expectancy,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn trade(entry: DateTime<Utc>, minutes: i64, side: Side, pnl: Decimal) -> Trade {
+        Trade {
+            id: uuid::Uuid::new_v4(),
+            instrument: "ES".to_string(),
+            side,
+            quantity: dec!(1),
+            entry_price: dec!(4700),
+            exit_price: dec!(4700),
+            pnl,
+            commission: Decimal::ZERO,
+            entry_time: entry,
+            exit_time: entry + Duration::minutes(minutes),
+            strategy_id: None,
+            mae: Decimal::ZERO,
+            mfe: Decimal::ZERO,
+        }
+    }
+
+    fn bar(timestamp: DateTime<Utc>, range: Decimal) -> Bar {
+        Bar {
+            instrument: "ES".to_string(),
+            timestamp,
+            open: dec!(4700),
+            high: dec!(4700) + range,
+            low: dec!(4700),
+            close: dec!(4700),
+            volume: dec!(100),
+        }
+    }
+
+    #[test]
+    fn test_breakdowns_group_trades_in_order() {
+        // Tuesday 2024-01-02
+        let day = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let trades = vec![
+            trade(day + Duration::hours(15), 10, Side::Buy, dec!(200)),
+            trade(day + Duration::hours(9), 90, Side::Sell, dec!(-100)),
+            trade(day + Duration::hours(15), 3, Side::Buy, dec!(-100)),
+            trade(
+                day + Duration::days(1) + Duration::hours(9),
+                10,
+                Side::Buy,
+                dec!(300),
+            ),
+        ];
+        // Hourly ranges of 1 to 24, so quartiles split at 6, 12 and 18
+        let mut atr = AtrSeries::new(1, Timeframe::Hour(1));
+        for hour in 0..24 {
+            atr.observe(&bar(day + Duration::hours(hour), Decimal::from(hour + 1)));
+        }
+
+        let analytics = analyze_trades(&trades, Some(&atr));
+
+        let summary = |buckets: &[BucketStats]| {
+            buckets
+                .iter()
+                .map(|b| (b.label.clone(), b.trades, b.net_pnl))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summary(&analytics.by_hour),
+            vec![
+                ("09:00".to_string(), 2, dec!(200)),
+                ("15:00".to_string(), 2, dec!(100)),
+            ]
+        );
+        assert_eq!(
+            summary(&analytics.by_weekday),
+            vec![
+                ("Tue".to_string(), 3, dec!(0)),
+                ("Wed".to_string(), 1, dec!(300)),
+            ]
+        );
+        assert_eq!(
+            summary(&analytics.by_holding_time),
+            vec![
+                ("< 5m".to_string(), 1, dec!(-100)),
+                ("5-15m".to_string(), 2, dec!(500)),
+                ("1-4h".to_string(), 1, dec!(-100)),
+            ]
+        );
+        let long = &analytics.by_direction[0];
+        assert_eq!((long.label.as_str(), long.trades), ("Long", 3));
+        assert_eq!(long.win_rate.round_dp(2), dec!(66.67));
+        // R is the average loss of 100
+        assert_eq!(long.expectancy.map(|e| e.round_dp(4)), Some(dec!(1.3333)));
+        // ATR 9 at 09:00, 15 at 15:00 and 24 from the last bar the next day
+        assert_eq!(
+            summary(&analytics.by_volatility),
+            vec![
+                ("Q2".to_string(), 1, dec!(-100)),
+                ("Q3".to_string(), 2, dec!(100)),
+                ("Q4 (high ATR)".to_string(), 1, dec!(300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_atr_at_entry_uses_only_closed_bars() {
+        let day = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let mut atr = AtrSeries::new(1, Timeframe::Hour(1));
+        atr.observe(&bar(day + Duration::hours(9), dec!(5)));
+        atr.observe(&bar(day + Duration::hours(10), dec!(50)));
+
+        // The 09:00 bar closes at 10:00
+        assert_eq!(atr.at(day + Duration::minutes(9 * 60 + 30)), None);
+        assert_eq!(atr.at(day + Duration::hours(10)), Some(dec!(5)));
+        // The 10:00 bar is still forming at 10:30
+        assert_eq!(atr.at(day + Duration::minutes(10 * 60 + 30)), Some(dec!(5)));
+        assert_eq!(atr.at(day + Duration::hours(11)), Some(dec!(50)));
+    }
+}
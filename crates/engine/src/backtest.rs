@@ -1,8 +1,13 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::stream::{self, Stream, StreamExt};
 use propbot_core::*;
 use propbot_brokers_common::simulated::{SimulatedBroker, SimulatedBrokerConfig};
+use propbot_data::aggregation::{BarBuilder, BarSpec};
 use propbot_risk::PropFirmRiskManager;
 use rust_decimal::Decimal;
+use std::collections::VecDeque;
 use tracing::{info, warn};
+use uuid::Uuid;
 
 use crate::metrics;
 
@@ -10,100 +15,189 @@ use crate::metrics;
 #[derive(Debug, Clone)]
 pub struct BacktestConfig {
     pub instrument: Instrument,
+    /// Timeframe of the bars being fed in.
+    pub timeframe: Timeframe,
+    /// Contract rolls in a continuous futures series; open positions are
+    /// rolled into the new contract at each one.
+    pub rolls: Vec<ContractRoll>,
     pub broker_config: SimulatedBrokerConfig,
+    /// Exchange calendar. Sessions and `Day` orders end at its closes and
+    /// bars while the market is closed are skipped; without one, both use the
+    /// broker's fixed `session_close_utc` every day.
+    pub calendar: Option<TradingCalendar>,
+    /// Annual risk-free rate as a fraction (0.04 = 4%) for the Sharpe and
+    /// Sortino ratios.
+    pub risk_free_rate: Decimal,
 }
 
 /// Run a backtest: feed bars through the strategy and simulated broker.
 pub async fn run_backtest(
     bars: Vec<Bar>,
     strategy: &mut dyn Strategy,
-    mut risk_manager: Option<&mut PropFirmRiskManager>,
+    risk_manager: Option<&mut PropFirmRiskManager>,
     config: BacktestConfig,
 ) -> BacktestResult {
+    let bars = stream::iter(bars.into_iter().map(Ok));
+    run_backtest_stream(bars, strategy, risk_manager, config)
+        .await
+        .expect("in-memory bars cannot fail to load")
+}
+
+/// Run a backtest over a stream of bars (e.g. from [`DataProvider::stream_bars`]),
+/// so the history never has to be held in memory.
+///
+/// Stops with the stream's error if reading the data fails.
+pub async fn run_backtest_stream(
+    bars: impl Stream<Item = Result<Bar, DataError>>,
+    strategy: &mut dyn Strategy,
+    risk_manager: Option<&mut PropFirmRiskManager>,
+    config: BacktestConfig,
+) -> Result<BacktestResult, DataError> {
+    let mut bars = std::pin::pin!(bars);
     let mut broker = SimulatedBroker::new(config.broker_config.clone());
+    if let Some(calendar) = &config.calendar {
+        broker = broker.with_calendar(calendar.clone());
+    }
     broker.connect().await.expect("Simulated broker connect");
+    let mut run = BacktestRun {
+        broker,
+        risk_manager,
+        instrument: config.instrument.clone(),
+        pending: VecDeque::new(),
+        violations: Vec::new(),
+        risk_events: Vec::new(),
+        halted: false,
+    };
 
     strategy.on_start().await;
 
-    let mut equity_curve = Vec::with_capacity(bars.len());
-    let start_date = bars.first().map(|b| b.timestamp).unwrap_or_default();
-    let end_date = bars.last().map(|b| b.timestamp).unwrap_or_default();
+    let mut equity_curve = Vec::new();
+    let mut start_date = None;
+    let mut end_date = DateTime::<Utc>::default();
+    let mut bar_count = 0usize;
+    let mut skipped = 0usize;
+    let session_close = config.broker_config.session_close_utc;
+    let mut aggregators: Vec<(Timeframe, BarBuilder)> = Vec::new();
+    for timeframe in strategy.timeframes() {
+        if timeframe == config.timeframe {
+            continue;
+        }
+        if !timeframe.builds_from(config.timeframe) {
+            warn!(%timeframe, base = %config.timeframe, "Cannot build timeframe from base feed, skipping");
+            continue;
+        }
+        aggregators.push((timeframe, BarBuilder::new(BarSpec::Time(timeframe))));
+    }
+    let mut current_session: Option<(NaiveDate, Bar)> = None;
+
+    info!(instrument = %config.instrument.symbol, "Starting backtest");
 
-    info!(
-        instrument = %config.instrument.symbol,
-        bars = bars.len(),
-        "Starting backtest from {} to {}",
-        start_date, end_date
-    );
+    let mut rolls = config.rolls.iter().peekable();
+
+    while let Some(bar) = bars.next().await {
+        let bar = bar?;
+        let bar = &bar;
+        if config
+            .calendar
+            .as_ref()
+            .is_some_and(|c| !c.is_open(bar.timestamp))
+        {
+            skipped += 1;
+            continue;
+        }
+        start_date.get_or_insert(bar.timestamp);
+        end_date = bar.timestamp;
+        bar_count += 1;
+
+        // Close out the previous session before its orders see the new bar
+        let session = match &config.calendar {
+            Some(calendar) => calendar.session_date(bar.timestamp),
+            None => metrics::session_date(bar.timestamp, session_close),
+        };
+        let new_session = current_session
+            .as_ref()
+            .is_none_or(|(date, _)| *date != session);
+        if let Some((date, last_bar)) = current_session.as_ref().filter(|_| new_session) {
+            let mut ctx = run.context(strategy.id(), last_bar.timestamp);
+            strategy.on_session_end(*date, &mut ctx).await;
+            run.pending.extend(ctx.take_requests());
+            run.execute_requests(strategy, last_bar).await;
+
+            // Daily loss limits start over
+            run.broker.start_new_day();
+            if let Some(rm) = run.risk_manager.as_deref_mut() {
+                rm.reset_daily();
+            }
+        }
+        current_session = Some((session, bar.clone()));
+
+        // Roll positions while the old contract's last bar is still current
+        while let Some(roll) = rolls.next_if(|r| r.timestamp <= bar.timestamp) {
+            info!(from = %roll.from_contract, to = %roll.to_contract, "Rolling positions");
+            run.broker
+                .roll_positions(&roll.instrument, roll.exit_price, roll.entry_price);
+            run.dispatch_order_events(strategy, bar).await;
+        }
 
-    for bar in &bars {
         // Feed bar to the broker (updates positions, processes pending orders)
-        broker.set_current_bar(bar.clone());
+        run.broker.set_current_bar(bar.clone());
+        run.dispatch_order_events(strategy, bar).await;
 
         // Update risk manager with current account state
-        if let Some(ref mut rm) = risk_manager.as_deref_mut() {
-            rm.update_account(broker.account());
+        run.update_risk(strategy, bar).await;
+
+        if new_session {
+            let mut ctx = run.context(strategy.id(), bar.timestamp);
+            strategy.on_session_start(session, &mut ctx).await;
+            run.pending.extend(ctx.take_requests());
+        }
+
+        // Higher-timeframe bars closed by this bar go out first
+        let base_end = bar.timestamp + config.timeframe.duration().unwrap_or_default();
+        let mut signals = Vec::new();
+        for (timeframe, builder) in &mut aggregators {
+            // Bars closed by a gap, then the one this bar completes
+            let mut closed = builder.push_bar(bar);
+            closed.extend(builder.poll(base_end));
+            for htf_bar in closed {
+                let mut ctx = run.context(strategy.id(), bar.timestamp);
+                signals.extend(
+                    strategy
+                        .on_timeframe_bar(*timeframe, &htf_bar, &mut ctx)
+                        .await,
+                );
+                run.pending.extend(ctx.take_requests());
+            }
         }
 
         // Feed bar to the strategy
-        let signals = strategy.on_bar(bar).await;
+        let mut ctx = run.context(strategy.id(), bar.timestamp);
+        signals.extend(strategy.on_bar(bar, &mut ctx).await);
+        run.pending.extend(ctx.take_requests());
 
         // Process signals
         for signal in signals {
-            let order = signal_to_order(&signal);
-
-            // Risk check
-            let approved = if let Some(ref rm) = risk_manager.as_deref() {
-                match rm.evaluate_order(&order, broker.account()) {
-                    RiskDecision::Approved => true,
-                    RiskDecision::Rejected(reason) => {
-                        warn!(order_id = %order.id, %reason, "Order rejected by risk manager");
-                        false
-                    }
-                    RiskDecision::Modified(modified) => {
-                        // Submit modified order instead
-                        let _ = broker.submit_order(modified).await;
-                        false // original not submitted
-                    }
-                }
-            } else {
-                true
-            };
-
-            if approved {
-                match broker.submit_order(order).await {
-                    Ok(filled) => {
-                        if filled.status == OrderStatus::Filled {
-                            // Notify strategy of fill
-                            let fill = Fill {
-                                order_id: filled.id,
-                                instrument: filled.instrument.clone(),
-                                side: filled.side,
-                                quantity: filled.filled_quantity,
-                                price: filled.price.unwrap_or(bar.close),
-                                commission: Decimal::ZERO,
-                                timestamp: bar.timestamp,
-                            };
-                            strategy.on_fill(&fill).await;
-                        }
-                    }
-                    Err(e) => {
-                        warn!("Order submission failed: {}", e);
-                    }
-                }
+            if signal.action == SignalAction::ExitAll {
+                run.cancel_working_orders(&signal).await;
             }
 
-            // Check if risk manager wants to halt
-            if let Some(ref rm) = risk_manager.as_deref() {
-                if rm.should_halt() {
-                    info!("Risk manager halted trading — flattening all positions");
-                    let _ = broker.flatten_all().await;
-                }
-            }
+            let position = run
+                .broker
+                .strategy_position(&signal.strategy_id, &signal.instrument)
+                .cloned();
+            let Some(order) = signal_to_order(&signal, position.as_ref()) else {
+                continue;
+            };
+
+            run.submit(order, strategy, bar).await;
+            run.halt_if_breached(strategy, bar).await;
         }
 
+        // Orders placed, cancelled or modified through the context
+        run.execute_requests(strategy, bar).await;
+
         // Record equity point
-        let account = broker.account();
+        let account = run.broker.account();
         equity_curve.push(EquityPoint {
             timestamp: bar.timestamp,
             equity: account.equity,
@@ -111,63 +205,707 @@ pub async fn run_backtest(
         });
     }
 
+    if let Some((date, last_bar)) = current_session {
+        let mut ctx = run.context(strategy.id(), last_bar.timestamp);
+        strategy.on_session_end(date, &mut ctx).await;
+        run.pending.extend(ctx.take_requests());
+        run.execute_requests(strategy, &last_bar).await;
+    }
+
+    if skipped > 0 {
+        info!(bars = skipped, "Skipped bars while the market was closed");
+    }
+    info!(
+        instrument = %config.instrument.symbol,
+        bars = bar_count,
+        "Finished backtest from {} to {}",
+        start_date.unwrap_or_default(),
+        end_date
+    );
+
     strategy.on_stop().await;
 
     // Flatten any remaining positions at the last price
-    let _ = broker.flatten_all().await;
+    let _ = run.broker.flatten_all().await;
 
     // Compute results
-    let trades = broker.trade_log().to_vec();
-    let account = broker.account().clone();
+    let trades = run.broker.trade_log().to_vec();
+    let account = run.broker.account().clone();
 
-    metrics::compute_backtest_result(
+    let result = metrics::compute_backtest_result(
         strategy.id().to_string(),
         config.instrument.symbol.clone(),
         config.broker_config.initial_balance,
         account,
         trades,
         equity_curve,
-        start_date,
+        start_date.unwrap_or_default(),
         end_date,
-    )
+        &metrics::MetricsConfig {
+            risk_free_rate: config.risk_free_rate,
+            calendar: config.calendar.clone(),
+            session_close_utc: Some(session_close),
+        },
+    );
+    Ok(BacktestResult {
+        timeframe: Some(config.timeframe),
+        order_log: run.broker.order_log().to_vec(),
+        risk_events: run.risk_events,
+        ..result
+    })
 }
 
-/// Convert a signal into an order.
-fn signal_to_order(signal: &Signal) -> Order {
-    let qty = signal.quantity.unwrap_or(Decimal::ONE);
+/// State shared by the steps of a single backtest run.
+struct BacktestRun<'a> {
+    broker: SimulatedBroker,
+    risk_manager: Option<&'a mut PropFirmRiskManager>,
+    instrument: Instrument,
+    /// Order requests queued by strategy callbacks, executed in order.
+    pending: VecDeque<OrderRequest>,
+    /// Risk violations already reported to the strategy.
+    violations: Vec<RiskViolation>,
+    /// Every risk event delivered to the strategy.
+    risk_events: Vec<RiskEventRecord>,
+    /// Whether the strategy has been told trading is halted.
+    halted: bool,
+}
 
-    match signal.action {
-        SignalAction::BuyEntry => {
-            let mut order = match signal.price {
-                Some(price) => Order::limit(&signal.instrument, Side::Buy, qty, price),
-                None => Order::market(&signal.instrument, Side::Buy, qty),
+impl BacktestRun<'_> {
+    /// Snapshot the strategy's view of the account for a callback.
+    fn context(&self, strategy_id: &str, timestamp: DateTime<Utc>) -> StrategyContext {
+        let mut ctx = StrategyContext::new(strategy_id, timestamp, self.broker.account().clone());
+        ctx.positions = self
+            .broker
+            .strategy_positions(strategy_id)
+            .into_iter()
+            .cloned()
+            .collect();
+        ctx.working_orders = self
+            .broker
+            .working_orders()
+            .iter()
+            .filter(|o| o.strategy_id.as_deref() == Some(strategy_id))
+            .cloned()
+            .collect();
+        if let Some(rm) = self.risk_manager.as_deref() {
+            ctx.risk = RiskStatus {
+                halted: rm.should_halt(),
+                violations: rm.active_violations(),
             };
-            order.strategy_id = Some(signal.strategy_id.clone());
-            order
         }
-        SignalAction::SellEntry => {
-            let mut order = match signal.price {
-                Some(price) => Order::limit(&signal.instrument, Side::Sell, qty, price),
-                None => Order::market(&signal.instrument, Side::Sell, qty),
+        ctx.instruments
+            .insert(self.instrument.symbol.clone(), self.instrument.clone());
+        ctx
+    }
+
+    /// Run an order past the risk manager, submit it, and deliver the
+    /// resulting order events to the strategy.
+    async fn submit(&mut self, order: Order, strategy: &mut dyn Strategy, bar: &Bar) {
+        let order = match self.risk_manager.as_deref() {
+            Some(rm) => match rm.evaluate_order(&order, self.broker.account()) {
+                RiskDecision::Approved => order,
+                RiskDecision::Rejected(reason) => {
+                    warn!(order_id = %order.id, %reason, "Order rejected by risk manager");
+                    self.broker.log_rejection(&order, &reason);
+                    let event = RiskEvent::OrderBlocked {
+                        order_id: order.id,
+                        reason,
+                    };
+                    self.deliver_risk_event(&event, strategy, bar).await;
+                    return;
+                }
+                // Submit modified order instead
+                RiskDecision::Modified(modified) => modified,
+            },
+            None => order,
+        };
+
+        if let Err(e) = self.broker.submit_order(order).await {
+            warn!("Order submission failed: {}", e);
+        }
+        self.dispatch_order_events(strategy, bar).await;
+    }
+
+    /// Execute queued order requests, including any queued while executing.
+    async fn execute_requests(&mut self, strategy: &mut dyn Strategy, bar: &Bar) {
+        while let Some(request) = self.pending.pop_front() {
+            match request {
+                OrderRequest::Place(order) => {
+                    self.submit(order, strategy, bar).await;
+                }
+                OrderRequest::Cancel(order_id) => {
+                    if !self.owns_working_order(strategy.id(), order_id) {
+                        warn!(%order_id, "Ignoring cancel for an order the strategy doesn't own");
+                        continue;
+                    }
+                    if let Err(e) = self.broker.cancel_order(order_id).await {
+                        warn!(%order_id, "Cancel failed: {}", e);
+                    }
+                    self.dispatch_order_events(strategy, bar).await;
+                }
+                OrderRequest::Modify(order) => {
+                    if !self.owns_working_order(strategy.id(), order.id) {
+                        warn!(order_id = %order.id, "Ignoring modify for an order the strategy doesn't own");
+                        continue;
+                    }
+                    if let Some(rm) = self.risk_manager.as_deref() {
+                        if let RiskDecision::Rejected(reason) =
+                            rm.evaluate_order(&order, self.broker.account())
+                        {
+                            warn!(order_id = %order.id, %reason, "Modify rejected by risk manager");
+                            let event = RiskEvent::OrderBlocked {
+                                order_id: order.id,
+                                reason,
+                            };
+                            self.deliver_risk_event(&event, strategy, bar).await;
+                            continue;
+                        }
+                    }
+                    if let Err(e) = self.broker.modify_order(order).await {
+                        warn!("Modify failed: {}", e);
+                    }
+                }
+            }
+            self.halt_if_breached(strategy, bar).await;
+        }
+    }
+
+    fn owns_working_order(&self, strategy_id: &str, order_id: Uuid) -> bool {
+        self.broker
+            .working_orders()
+            .iter()
+            .any(|o| o.id == order_id && o.strategy_id.as_deref() == Some(strategy_id))
+    }
+
+    /// Feed the account to the risk manager and report new violations.
+    async fn update_risk(&mut self, strategy: &mut dyn Strategy, bar: &Bar) {
+        let Some(rm) = self.risk_manager.as_deref_mut() else {
+            return;
+        };
+        rm.set_time(bar.timestamp);
+        rm.update_account(self.broker.account());
+
+        // Only report a violation once per rule and severity
+        let active = rm.active_violations();
+        let new: Vec<RiskViolation> = active
+            .iter()
+            .filter(|v| {
+                !self
+                    .violations
+                    .iter()
+                    .any(|seen| seen.rule == v.rule && seen.severity == v.severity)
+            })
+            .cloned()
+            .collect();
+        self.violations = active;
+        for violation in new {
+            self.deliver_risk_event(&RiskEvent::Violation(violation), strategy, bar)
+                .await;
+        }
+
+        self.halt_if_breached(strategy, bar).await;
+    }
+
+    /// Flatten everything if the risk manager wants to halt, telling the
+    /// strategy the first time it happens.
+    async fn halt_if_breached(&mut self, strategy: &mut dyn Strategy, bar: &Bar) {
+        let Some(rm) = self.risk_manager.as_deref() else {
+            return;
+        };
+        let halted = rm.should_halt();
+        let reason = rm
+            .active_violations()
+            .into_iter()
+            .find(|v| v.severity == RiskSeverity::Breach)
+            .map(|v| v.message)
+            .unwrap_or_else(|| "Trading halted by risk manager".to_string());
+
+        if halted && !self.halted {
+            info!("Risk manager halted trading — cancelling orders and flattening all positions");
+            let working: Vec<Uuid> = self.broker.working_orders().iter().map(|o| o.id).collect();
+            for order_id in working {
+                let _ = self.broker.cancel_order(order_id).await;
+            }
+        }
+        if halted {
+            let _ = self.broker.flatten_all().await;
+            self.dispatch_order_events(strategy, bar).await;
+        }
+        if halted && !self.halted {
+            self.halted = true;
+            let event = RiskEvent::AutoFlatten {
+                reason: reason.clone(),
             };
-            order.strategy_id = Some(signal.strategy_id.clone());
-            order
+            self.deliver_risk_event(&event, strategy, bar).await;
+            let mut ctx = self.context(strategy.id(), bar.timestamp);
+            strategy.on_risk_halt(&reason, &mut ctx).await;
+            self.pending.extend(ctx.take_requests());
+        }
+        self.halted = halted;
+    }
+
+    async fn deliver_risk_event(
+        &mut self,
+        event: &RiskEvent,
+        strategy: &mut dyn Strategy,
+        bar: &Bar,
+    ) {
+        self.risk_events.push(RiskEventRecord {
+            timestamp: bar.timestamp,
+            event: event.clone(),
+        });
+        let mut ctx = self.context(strategy.id(), bar.timestamp);
+        strategy.on_risk_event(event, &mut ctx).await;
+        self.pending.extend(ctx.take_requests());
+    }
+
+    /// Deliver order events raised by the simulated broker to the strategy
+    /// that owns each order.
+    ///
+    /// Fills are followed by the strategy's updated position; a position with
+    /// zero quantity tells the strategy it is flat.
+    async fn dispatch_order_events(&mut self, strategy: &mut dyn Strategy, bar: &Bar) {
+        for event in self.broker.drain_events() {
+            let order_id = match &event {
+                OrderEvent::Submitted(order) => order.id,
+                OrderEvent::Filled(fill) | OrderEvent::PartiallyFilled(fill) => fill.order_id,
+                OrderEvent::Cancelled { order_id, .. } | OrderEvent::Rejected { order_id, .. } => {
+                    *order_id
+                }
+            };
+            if let (OrderEvent::Filled(fill) | OrderEvent::PartiallyFilled(fill), Some(rm)) =
+                (&event, self.risk_manager.as_deref_mut())
+            {
+                rm.record_fill(fill.timestamp);
+            }
+            if self.broker.order_owner(order_id) != Some(strategy.id()) {
+                continue;
+            }
+
+            let mut ctx = self.context(strategy.id(), bar.timestamp);
+            match event {
+                OrderEvent::Submitted(_) => {}
+                OrderEvent::Filled(fill) | OrderEvent::PartiallyFilled(fill) => {
+                    strategy.on_fill(&fill, &mut ctx).await;
+                    let position = self
+                        .broker
+                        .strategy_position(strategy.id(), &fill.instrument)
+                        .cloned()
+                        .unwrap_or_else(|| Position {
+                            instrument: fill.instrument.clone(),
+                            side: fill.side,
+                            quantity: Decimal::ZERO,
+                            avg_entry_price: Decimal::ZERO,
+                            unrealized_pnl: Decimal::ZERO,
+                            realized_pnl: Decimal::ZERO,
+                            opened_at: fill.timestamp,
+                            strategy_id: Some(strategy.id().to_string()),
+                        });
+                    strategy.on_position_update(&position, &mut ctx).await;
+                }
+                OrderEvent::Cancelled { order_id, reason } => {
+                    info!(%order_id, %reason, "Order cancelled");
+                    strategy
+                        .on_order_cancelled(order_id, &reason, &mut ctx)
+                        .await;
+                }
+                OrderEvent::Rejected { order_id, reason } => {
+                    strategy
+                        .on_order_rejected(order_id, &reason, &mut ctx)
+                        .await;
+                }
+            }
+            self.pending.extend(ctx.take_requests());
+        }
+    }
+
+    /// Cancel the working orders a strategy has resting on the signal's instrument.
+    async fn cancel_working_orders(&mut self, signal: &Signal) {
+        let working: Vec<Uuid> = self
+            .broker
+            .working_orders()
+            .iter()
+            .filter(|o| {
+                o.instrument == signal.instrument
+                    && o.strategy_id.as_deref() == Some(&signal.strategy_id)
+            })
+            .map(|o| o.id)
+            .collect();
+        for order_id in working {
+            if let Err(e) = self.broker.cancel_order(order_id).await {
+                warn!(%order_id, "Failed to cancel working order on exit: {}", e);
+            }
+        }
+    }
+}
+
+/// Convert a signal into an order, sizing exits against the open position.
+///
+/// Exits always close exactly the open quantity so they can never reverse a
+/// position. Exits with nothing to close are dropped (and logged).
+fn signal_to_order(signal: &Signal, position: Option<&Position>) -> Option<Order> {
+    let qty = signal.quantity.unwrap_or(Decimal::ONE);
+
+    let mut order = match signal.action {
+        SignalAction::BuyEntry => match signal.price {
+            Some(price) => Order::limit(&signal.instrument, Side::Buy, qty, price),
+            None => Order::market(&signal.instrument, Side::Buy, qty),
+        },
+        SignalAction::SellEntry => match signal.price {
+            Some(price) => Order::limit(&signal.instrument, Side::Sell, qty, price),
+            None => Order::market(&signal.instrument, Side::Sell, qty),
+        },
+        SignalAction::ExitLong => match position {
+            Some(pos) if pos.side == Side::Buy => {
+                Order::market(&signal.instrument, Side::Sell, pos.quantity)
+            }
+            _ => {
+                drop_signal(signal, "no open long position");
+                return None;
+            }
+        },
+        SignalAction::ExitShort => match position {
+            Some(pos) if pos.side == Side::Sell => {
+                Order::market(&signal.instrument, Side::Buy, pos.quantity)
+            }
+            _ => {
+                drop_signal(signal, "no open short position");
+                return None;
+            }
+        },
+        SignalAction::ExitAll => match position {
+            Some(pos) => Order::market(&signal.instrument, pos.side.opposite(), pos.quantity),
+            None => {
+                drop_signal(signal, "no open position to flatten");
+                return None;
+            }
+        },
+    };
+
+    order.strategy_id = Some(signal.strategy_id.clone());
+    Some(order)
+}
+
+fn drop_signal(signal: &Signal, reason: &str) {
+    info!(
+        signal_id = %signal.id,
+        strategy = %signal.strategy_id,
+        instrument = %signal.instrument,
+        action = ?signal.action,
+        %reason,
+        "Dropping exit signal"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn signal(action: SignalAction, quantity: Option<Decimal>) -> Signal {
+        Signal {
+            id: Uuid::new_v4(),
+            instrument: "ES".to_string(),
+            action,
+            quantity,
+            price: None,
+            strategy_id: "test".to_string(),
+            timestamp: Utc::now(),
+            metadata: None,
+        }
+    }
+
+    fn position(side: Side, quantity: Decimal) -> Position {
+        Position {
+            instrument: "ES".to_string(),
+            side,
+            quantity,
+            avg_entry_price: dec!(4750),
+            unrealized_pnl: Decimal::ZERO,
+            realized_pnl: Decimal::ZERO,
+            opened_at: Utc::now(),
+            strategy_id: None,
+        }
+    }
+
+    /// An ES backtest on `timeframe` bars with default settings.
+    fn config(timeframe: Timeframe) -> BacktestConfig {
+        BacktestConfig {
+            instrument: Instrument {
+                symbol: "ES".to_string(),
+                asset_class: AssetClass::Futures,
+                tick_size: dec!(0.25),
+                tick_value: dec!(12.50),
+                contract_size: dec!(50),
+                currency: "USD".to_string(),
+                exchange: None,
+            },
+            timeframe,
+            rolls: Vec::new(),
+            broker_config: SimulatedBrokerConfig::default(),
+            calendar: None,
+            risk_free_rate: Decimal::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_exit_closes_open_quantity() {
+        let pos = position(Side::Buy, dec!(3));
+        let order = signal_to_order(&signal(SignalAction::ExitLong, Some(dec!(1))), Some(&pos))
+            .expect("exit order");
+        assert_eq!(order.side, Side::Sell);
+        assert_eq!(order.quantity, dec!(3));
+        assert_eq!(order.strategy_id.as_deref(), Some("test"));
+    }
+
+    #[test]
+    fn test_exit_all_flattens_short() {
+        let pos = position(Side::Sell, dec!(2));
+        let order = signal_to_order(&signal(SignalAction::ExitAll, None), Some(&pos))
+            .expect("flatten order");
+        assert_eq!(order.side, Side::Buy);
+        assert_eq!(order.quantity, dec!(2));
+    }
+
+    #[test]
+    fn test_exit_without_matching_position_is_dropped() {
+        let short = position(Side::Sell, dec!(1));
+        assert!(signal_to_order(&signal(SignalAction::ExitLong, None), None).is_none());
+        assert!(signal_to_order(&signal(SignalAction::ExitLong, None), Some(&short)).is_none());
+        assert!(signal_to_order(&signal(SignalAction::ExitAll, None), None).is_none());
+    }
+
+    /// Places a handful of orders on the first bar and records every callback.
+    #[derive(Default)]
+    struct RecordingStrategy {
+        log: Vec<String>,
+    }
+
+    #[async_trait::async_trait]
+    impl Strategy for RecordingStrategy {
+        fn id(&self) -> &str {
+            "recorder"
+        }
+
+        fn name(&self) -> &str {
+            "Recorder"
+        }
+
+        async fn on_bar(&mut self, _bar: &Bar, ctx: &mut StrategyContext) -> Vec<Signal> {
+            if self.log.iter().any(|e| e.starts_with("bar")) {
+                self.log.push("bar".to_string());
+                return Vec::new();
+            }
+            self.log.push("bar".to_string());
+            // Rests, then fills on the next bar
+            ctx.place_order(Order::limit("ES", Side::Buy, dec!(1), dec!(4695)));
+            // Expires at the session close
+            ctx.place_order(
+                Order::limit("ES", Side::Buy, dec!(1), dec!(4600))
+                    .with_time_in_force(TimeInForce::Day),
+            );
+            // Over the profile's position size limit
+            ctx.place_order(Order::market("ES", Side::Buy, dec!(10)));
+            // Trailing stop without a trail is rejected by the broker
+            let mut trailing = Order::market("ES", Side::Sell, dec!(1));
+            trailing.order_type = OrderType::TrailingStop;
+            ctx.place_order(trailing);
+            Vec::new()
+        }
+
+        async fn on_fill(&mut self, fill: &Fill, _ctx: &mut StrategyContext) {
+            self.log.push(format!("fill {}", fill.quantity));
+        }
+
+        async fn on_order_rejected(
+            &mut self,
+            _id: Uuid,
+            _reason: &str,
+            _ctx: &mut StrategyContext,
+        ) {
+            self.log.push("rejected".to_string());
+        }
+
+        async fn on_order_cancelled(
+            &mut self,
+            _id: Uuid,
+            reason: &str,
+            _ctx: &mut StrategyContext,
+        ) {
+            self.log.push(format!("cancelled: {}", reason));
+        }
+
+        async fn on_risk_event(&mut self, event: &RiskEvent, _ctx: &mut StrategyContext) {
+            if let RiskEvent::OrderBlocked { .. } = event {
+                self.log.push("blocked".to_string());
+            }
+        }
+
+        async fn on_session_start(&mut self, session: NaiveDate, _ctx: &mut StrategyContext) {
+            self.log.push(format!("start {}", session));
         }
-        SignalAction::ExitLong => {
-            let mut order = Order::market(&signal.instrument, Side::Sell, qty);
-            order.strategy_id = Some(signal.strategy_id.clone());
-            order
+
+        async fn on_session_end(&mut self, session: NaiveDate, _ctx: &mut StrategyContext) {
+            self.log.push(format!("end {}", session));
         }
-        SignalAction::ExitShort => {
-            let mut order = Order::market(&signal.instrument, Side::Buy, qty);
-            order.strategy_id = Some(signal.strategy_id.clone());
-            order
+
+        fn reset(&mut self) {
+            self.log.clear();
         }
-        SignalAction::ExitAll => {
-            // Will be handled separately (flatten_all)
-            let mut order = Order::market(&signal.instrument, Side::Sell, qty);
-            order.strategy_id = Some(signal.strategy_id.clone());
-            order
+    }
+
+    fn bar(timestamp: &str, low: Decimal, close: Decimal) -> Bar {
+        Bar {
+            instrument: "ES".to_string(),
+            timestamp: timestamp.parse().unwrap(),
+            open: close,
+            high: close + dec!(5),
+            low,
+            close,
+            volume: dec!(100),
         }
     }
+
+    #[tokio::test]
+    async fn test_strategy_is_notified_of_order_and_session_events() {
+        let bars = vec![
+            bar("2024-01-02T14:30:00Z", dec!(4698), dec!(4700)),
+            bar("2024-01-02T15:30:00Z", dec!(4690), dec!(4700)),
+            bar("2024-01-02T21:30:00Z", dec!(4698), dec!(4700)),
+        ];
+        let config = config(Timeframe::Hour(1));
+        let mut risk = PropFirmRiskManager::new(propbot_risk::PropFirmProfile::topstep_50k());
+        let mut strategy = RecordingStrategy::default();
+
+        run_backtest(bars, &mut strategy, Some(&mut risk), config).await;
+
+        assert_eq!(
+            strategy.log,
+            vec![
+                "start 2024-01-02",
+                "bar",
+                "blocked",
+                "rejected",
+                "fill 1",
+                "bar",
+                "end 2024-01-02",
+                "cancelled: Time in force expired",
+                "start 2024-01-03",
+                "bar",
+                "end 2024-01-03",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_calendar_sessions_skip_holidays() {
+        let bars = vec![
+            bar("2024-03-28T15:00:00Z", dec!(4698), dec!(4700)),
+            // Good Friday
+            bar("2024-03-29T15:00:00Z", dec!(4698), dec!(4700)),
+            // Sunday evening opens Monday's session
+            bar("2024-03-31T23:00:00Z", dec!(4698), dec!(4700)),
+        ];
+        let config = BacktestConfig {
+            calendar: Some(TradingCalendar::cme_equity()),
+            ..config(Timeframe::Hour(1))
+        };
+        let mut strategy = RecordingStrategy::default();
+
+        let result = run_backtest(bars, &mut strategy, None, config).await;
+
+        let sessions: Vec<&str> = strategy
+            .log
+            .iter()
+            .map(String::as_str)
+            .filter(|e| e.starts_with("start") || e.starts_with("end"))
+            .collect();
+        assert_eq!(
+            sessions,
+            vec![
+                "start 2024-03-28",
+                "end 2024-03-28",
+                "start 2024-04-01",
+                "end 2024-04-01",
+            ]
+        );
+        assert_eq!(result.equity_curve.len(), 2);
+    }
+
+    /// Subscribes to hourly bars on a 30-minute feed and records what it sees.
+    #[derive(Default)]
+    struct HourlyStrategy {
+        log: Vec<String>,
+    }
+
+    #[async_trait::async_trait]
+    impl Strategy for HourlyStrategy {
+        fn id(&self) -> &str {
+            "hourly"
+        }
+
+        fn name(&self) -> &str {
+            "Hourly"
+        }
+
+        fn timeframes(&self) -> Vec<Timeframe> {
+            vec![Timeframe::Minute(30), Timeframe::Hour(1)]
+        }
+
+        async fn on_timeframe_bar(
+            &mut self,
+            timeframe: Timeframe,
+            bar: &Bar,
+            _ctx: &mut StrategyContext,
+        ) -> Vec<Signal> {
+            self.log
+                .push(format!("{} {}", timeframe, bar.timestamp.format("%H:%M")));
+            Vec::new()
+        }
+
+        async fn on_bar(&mut self, bar: &Bar, _ctx: &mut StrategyContext) -> Vec<Signal> {
+            self.log
+                .push(format!("bar {}", bar.timestamp.format("%H:%M")));
+            Vec::new()
+        }
+
+        fn reset(&mut self) {
+            self.log.clear();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_higher_timeframe_bar_delivered_once_closed() {
+        let bars = vec![
+            bar("2024-01-02T14:00:00Z", dec!(4698), dec!(4700)),
+            bar("2024-01-02T14:30:00Z", dec!(4698), dec!(4700)),
+            bar("2024-01-02T15:00:00Z", dec!(4698), dec!(4700)),
+        ];
+        let config = config(Timeframe::Minute(30));
+        let mut strategy = HourlyStrategy::default();
+
+        run_backtest(bars, &mut strategy, None, config).await;
+
+        // The base timeframe isn't duplicated and the 15:00 hour never closes
+        assert_eq!(
+            strategy.log,
+            vec!["bar 14:00", "1h 14:00", "bar 14:30", "bar 15:00"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_error_stops_backtest() {
+        let bars = stream::iter(vec![
+            Ok(bar("2024-01-02T14:00:00Z", dec!(4698), dec!(4700))),
+            Err(DataError::ParseError("bad row".to_string())),
+            Ok(bar("2024-01-02T15:00:00Z", dec!(4698), dec!(4700))),
+        ]);
+        let config = config(Timeframe::Minute(30));
+        let mut strategy = HourlyStrategy::default();
+
+        let result = run_backtest_stream(bars, &mut strategy, None, config).await;
+
+        assert!(matches!(result, Err(DataError::ParseError(_))));
+        assert_eq!(strategy.log, vec!["bar 14:00"]);
+    }
 }
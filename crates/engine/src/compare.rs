@@ -0,0 +1,206 @@
+use chrono::{DateTime, Utc};
+use propbot_core::*;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Several backtest results side by side.
+#[derive(Debug, Clone, Serialize)]
+pub struct BacktestComparison {
+    pub runs: Vec<ComparedRun>,
+    /// Every summary metric, with each run's value and its difference from
+    /// the first run.
+    pub metrics: Vec<MetricComparison>,
+    pub equity: EquityOverlay,
+}
+
+/// Which backtest a comparison column belongs to.
+#[derive(Debug, Clone, Serialize)]
+pub struct ComparedRun {
+    pub id: Uuid,
+    pub strategy_id: String,
+    pub instrument: String,
+    pub start_date: DateTime<Utc>,
+    pub end_date: DateTime<Utc>,
+}
+
+/// One metric across the compared runs, in run order.
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricComparison {
+    pub metric: String,
+    /// `None` where the metric is undefined for a run.
+    pub values: Vec<Option<Decimal>>,
+    /// `value - first run's value`; zero for the first run, `None` when
+    /// either value is undefined.
+    pub deltas: Vec<Option<Decimal>>,
+}
+
+/// Equity curves sampled on one time axis so they can be drawn together.
+#[derive(Debug, Clone, Serialize)]
+pub struct EquityOverlay {
+    pub timestamps: Vec<DateTime<Utc>>,
+    /// Per run, the percentage return on the initial balance at each
+    /// timestamp; `None` outside the run's test period.
+    pub returns: Vec<Vec<Option<Decimal>>>,
+}
+
+/// Compare results metric by metric against the first, with their equity
+/// curves sampled at `points` evenly spaced times across all of them.
+pub fn compare_results(results: &[BacktestResult], points: usize) -> BacktestComparison {
+    let runs = results
+        .iter()
+        .map(|r| ComparedRun {
+            id: r.id,
+            strategy_id: r.strategy_id.clone(),
+            instrument: r.instrument.clone(),
+            start_date: r.start_date,
+            end_date: r.end_date,
+        })
+        .collect();
+
+    let metrics = BacktestResult::METRICS
+        .iter()
+        .map(|(name, value)| {
+            let values: Vec<Option<Decimal>> = results.iter().map(value).collect();
+            let base = values.first().copied().flatten();
+            MetricComparison {
+                metric: name.to_string(),
+                deltas: values.iter().map(|v| Some(v.as_ref()? - base?)).collect(),
+                values,
+            }
+        })
+        .collect();
+
+    BacktestComparison {
+        runs,
+        metrics,
+        equity: overlay_equity(results, points),
+    }
+}
+
+fn overlay_equity(results: &[BacktestResult], points: usize) -> EquityOverlay {
+    let first = results
+        .iter()
+        .filter_map(|r| r.equity_curve.first())
+        .map(|p| p.timestamp)
+        .min();
+    let last = results
+        .iter()
+        .filter_map(|r| r.equity_curve.last())
+        .map(|p| p.timestamp)
+        .max();
+    let timestamps: Vec<DateTime<Utc>> = match (first, last) {
+        (Some(first), Some(last)) if points >= 2 && last > first => {
+            let step = (last - first) / (points as i32 - 1);
+            (0..points - 1)
+                .map(|i| first + step * i as i32)
+                .chain(std::iter::once(last))
+                .collect()
+        }
+        (Some(first), _) => vec![first],
+        _ => Vec::new(),
+    };
+
+    let returns = results
+        .iter()
+        .map(|r| {
+            let curve = &r.equity_curve;
+            timestamps
+                .iter()
+                .map(|t| {
+                    if curve.last().is_none_or(|p| p.timestamp < *t) {
+                        return None;
+                    }
+                    // Equity as of the last point at or before `t`
+                    let idx = curve.partition_point(|p| p.timestamp <= *t);
+                    let point = curve.get(idx.checked_sub(1)?)?;
+                    if r.initial_balance.is_zero() {
+                        return Some(Decimal::ZERO);
+                    }
+                    let pct = (point.equity - r.initial_balance) / r.initial_balance
+                        * Decimal::ONE_HUNDRED;
+                    Some(pct.round_dp(2))
+                })
+                .collect()
+        })
+        .collect();
+
+    EquityOverlay {
+        timestamps,
+        returns,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{compute_backtest_result, MetricsConfig};
+    use chrono::Duration;
+    use rust_decimal_macros::dec;
+
+    fn result(start: &str, equity: &[Decimal]) -> BacktestResult {
+        let start: DateTime<Utc> = start.parse().unwrap();
+        let curve: Vec<EquityPoint> = equity
+            .iter()
+            .enumerate()
+            .map(|(i, e)| EquityPoint {
+                timestamp: start + Duration::hours(i as i64),
+                equity: *e,
+                drawdown: Decimal::ZERO,
+            })
+            .collect();
+        let mut account = AccountState::new(dec!(10000));
+        account.equity = *equity.last().unwrap();
+        compute_backtest_result(
+            "test".to_string(),
+            "ES".to_string(),
+            dec!(10000),
+            account,
+            Vec::new(),
+            curve.clone(),
+            curve[0].timestamp,
+            curve[curve.len() - 1].timestamp,
+            &MetricsConfig::default(),
+        )
+    }
+
+    #[test]
+    fn test_compare_deltas_and_overlaid_equity() {
+        let a = result(
+            "2024-01-02T00:00:00Z",
+            &[dec!(10000), dec!(10100), dec!(10200)],
+        );
+        let b = result(
+            "2024-01-02T02:00:00Z",
+            &[dec!(10000), dec!(9900), dec!(9950)],
+        );
+
+        let comparison = compare_results(&[a.clone(), b.clone()], 5);
+
+        let net = comparison
+            .metrics
+            .iter()
+            .find(|m| m.metric == "net_profit")
+            .unwrap();
+        assert_eq!(net.values, vec![Some(a.net_profit), Some(b.net_profit)]);
+        assert_eq!(
+            net.deltas,
+            vec![Some(dec!(0)), Some(b.net_profit - a.net_profit)]
+        );
+
+        // 00:00 to 04:00 in five samples, an hour apart
+        assert_eq!(comparison.equity.timestamps.len(), 5);
+        assert_eq!(
+            comparison.equity.timestamps[4] - comparison.equity.timestamps[0],
+            Duration::hours(4)
+        );
+        assert_eq!(
+            comparison.equity.returns[0],
+            vec![Some(dec!(0)), Some(dec!(1)), Some(dec!(2)), None, None]
+        );
+        assert_eq!(
+            comparison.equity.returns[1],
+            vec![None, None, Some(dec!(0)), Some(dec!(-1)), Some(dec!(-0.5))]
+        );
+    }
+}
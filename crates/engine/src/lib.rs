@@ -1,5 +1,13 @@
+pub mod analytics;
 pub mod backtest;
+pub mod compare;
 pub mod metrics;
+pub mod recorder;
+pub mod report;
 
+pub use analytics::*;
 pub use backtest::*;
+pub use compare::*;
 pub use metrics::*;
+pub use recorder::*;
+pub use report::*;
@@ -1,11 +1,60 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, Utc, Weekday};
 use propbot_core::*;
+use rust_decimal::prelude::{FromPrimitive, MathematicalOps, ToPrimitive};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
 use uuid::Uuid;
 
+/// How returns are grouped into days and annualized.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsConfig {
+    /// Annual risk-free rate as a fraction (0.04 = 4%), taken off returns
+    /// for the Sharpe and Sortino ratios.
+    pub risk_free_rate: Decimal,
+    /// Days follow its sessions.
+    pub calendar: Option<TradingCalendar>,
+    /// Without a calendar, days end at this time every day, as in the
+    /// backtest engine; without either they are UTC dates.
+    pub session_close_utc: Option<NaiveTime>,
+}
+
+impl MetricsConfig {
+    /// The trading day `timestamp` counts towards.
+    fn session_date(&self, timestamp: DateTime<Utc>) -> NaiveDate {
+        match (&self.calendar, self.session_close_utc) {
+            (Some(calendar), _) => calendar.session_date(timestamp),
+            (None, Some(close)) => session_date(timestamp, close),
+            (None, None) => timestamp.date_naive(),
+        }
+    }
+
+    /// Trading days per year: 365 for markets open every day, 252 otherwise.
+    ///
+    /// Without a calendar, a market open every day has two of every seven
+    /// sessions on a weekend; one counts as such when more than one in seven
+    /// of its session days are Saturdays or Sundays, so a few stray weekend
+    /// bars don't make a weekday market look open every day.
+    fn days_per_year(&self, daily_returns: &[PeriodReturn]) -> Decimal {
+        let every_day = match &self.calendar {
+            Some(calendar) => calendar.trading_days.len() == 7,
+            None => {
+                let weekend = daily_returns
+                    .iter()
+                    .filter(|d| matches!(d.start.weekday(), Weekday::Sat | Weekday::Sun))
+                    .count();
+                weekend * 7 > daily_returns.len()
+            }
+        };
+        if every_day {
+            dec!(365)
+        } else {
+            dec!(252)
+        }
+    }
+}
 
 /// Compute aggregate backtest results from trade log and equity curve.
+#[allow(clippy::too_many_arguments)]
 pub fn compute_backtest_result(
     strategy_id: String,
     instrument: String,
@@ -15,10 +64,17 @@ pub fn compute_backtest_result(
     equity_curve: Vec<EquityPoint>,
     start_date: DateTime<Utc>,
     end_date: DateTime<Utc>,
+    config: &MetricsConfig,
 ) -> BacktestResult {
     let total_trades = trades.len();
-    let winning_trades = trades.iter().filter(|t| t.net_pnl() > Decimal::ZERO).count();
-    let losing_trades = trades.iter().filter(|t| t.net_pnl() < Decimal::ZERO).count();
+    let winning_trades = trades
+        .iter()
+        .filter(|t| t.net_pnl() > Decimal::ZERO)
+        .count();
+    let losing_trades = trades
+        .iter()
+        .filter(|t| t.net_pnl() < Decimal::ZERO)
+        .count();
 
     let gross_profit: Decimal = trades
         .iter()
@@ -82,13 +138,85 @@ pub fn compute_backtest_result(
         gross_loss / Decimal::from(losing_trades)
     };
 
-    let sharpe_ratio = compute_sharpe(&equity_curve);
-    let sortino_ratio = compute_sortino(&equity_curve);
+    let daily_returns = daily_returns(&equity_curve, initial_balance, config);
+    let (sharpe_ratio, sortino_ratio, volatility) = compute_ratios(
+        &daily_returns,
+        config.days_per_year(&daily_returns),
+        config.risk_free_rate,
+    );
+
+    let mar_ratio = compute_return_over_drawdown(&equity_curve, initial_balance);
+    // Calmar only looks at the last 36 months
+    let calmar_ratio = match equity_curve.last() {
+        Some(last) => {
+            let window_start = last.timestamp - Months::new(36);
+            let from = equity_curve.partition_point(|p| p.timestamp < window_start);
+            if from == 0 {
+                mar_ratio
+            } else {
+                // Start from the equity just before the window
+                compute_return_over_drawdown(&equity_curve[from..], equity_curve[from - 1].equity)
+            }
+        }
+        None => Decimal::ZERO,
+    };
+
+    let r_multiples = r_multiples(&trades);
+    let expectancy = (!r_multiples.is_empty())
+        .then(|| r_multiples.iter().sum::<Decimal>() / Decimal::from(r_multiples.len()));
+
+    let largest_winner = trades
+        .iter()
+        .map(|t| t.net_pnl())
+        .max()
+        .unwrap_or_default()
+        .max(Decimal::ZERO);
+    let largest_loser = trades
+        .iter()
+        .map(|t| t.net_pnl())
+        .min()
+        .unwrap_or_default()
+        .min(Decimal::ZERO)
+        .abs();
+    let (max_consecutive_wins, max_consecutive_losses) = compute_streaks(&trades);
+
+    let (drawdown_duration, drawdown_recovery) = compute_drawdown_durations(&equity_curve);
+
+    let avg_trade_duration = if total_trades == 0 {
+        Duration::zero()
+    } else {
+        trades
+            .iter()
+            .map(|t| t.exit_time - t.entry_time)
+            .sum::<Duration>()
+            / total_trades as i32
+    };
+    let test_length = end_date - start_date;
+    let time_in_market_percent = if test_length <= Duration::zero() {
+        Decimal::ZERO
+    } else {
+        Decimal::from(time_in_market(&trades).num_seconds())
+            / Decimal::from(test_length.num_seconds())
+            * dec!(100)
+    };
+
+    let (avg_mae, avg_mfe) = if total_trades == 0 {
+        (Decimal::ZERO, Decimal::ZERO)
+    } else {
+        let n = Decimal::from(total_trades);
+        (
+            trades.iter().map(|t| t.mae).sum::<Decimal>() / n,
+            trades.iter().map(|t| t.mfe).sum::<Decimal>() / n,
+        )
+    };
+
+    let monthly_returns = monthly_returns(&equity_curve, initial_balance);
 
     BacktestResult {
         id: Uuid::new_v4(),
         strategy_id,
         instrument,
+        timeframe: None,
         start_date,
         end_date,
         initial_balance,
@@ -105,88 +233,556 @@ pub fn compute_backtest_result(
         profit_factor,
         sharpe_ratio,
         sortino_ratio,
+        volatility,
         avg_trade_pnl,
         avg_winner,
         avg_loser,
         total_commission,
+        calmar_ratio,
+        mar_ratio,
+        expectancy,
+        largest_winner,
+        largest_loser,
+        max_consecutive_wins,
+        max_consecutive_losses,
+        max_drawdown_duration_hours: hours(drawdown_duration),
+        max_drawdown_recovery_hours: hours(drawdown_recovery),
+        avg_trade_duration_hours: hours(avg_trade_duration),
+        time_in_market_percent,
+        avg_mae,
+        avg_mfe,
+        monthly_returns,
+        daily_returns,
         equity_curve,
         trades,
+        order_log: Vec::new(),
+        risk_events: Vec::new(),
     }
 }
 
-/// Compute annualized Sharpe ratio from equity curve.
-fn compute_sharpe(equity_curve: &[EquityPoint]) -> Decimal {
-    if equity_curve.len() < 2 {
-        return Decimal::ZERO;
-    }
+/// Return over each calendar month (UTC) covered by the equity curve,
+/// measured from the previous month's closing equity (the initial balance
+/// for the first month).
+pub fn monthly_returns(
+    equity_curve: &[EquityPoint],
+    initial_balance: Decimal,
+) -> Vec<PeriodReturn> {
+    period_returns(equity_curve, initial_balance, |timestamp| {
+        timestamp
+            .date_naive()
+            .with_day(1)
+            .expect("first of the month")
+    })
+}
 
-    let returns: Vec<Decimal> = equity_curve
-        .windows(2)
-        .map(|w| {
-            if w[0].equity.is_zero() {
-                Decimal::ZERO
-            } else {
-                (w[1].equity - w[0].equity) / w[0].equity
-            }
-        })
-        .collect();
+/// Return over each trading day covered by the equity curve, measured from
+/// the previous day's closing equity. Days are split as `config` says.
+pub fn daily_returns(
+    equity_curve: &[EquityPoint],
+    initial_balance: Decimal,
+    config: &MetricsConfig,
+) -> Vec<PeriodReturn> {
+    period_returns(equity_curve, initial_balance, |timestamp| {
+        config.session_date(timestamp)
+    })
+}
 
-    let n = Decimal::from(returns.len());
-    let mean: Decimal = returns.iter().sum::<Decimal>() / n;
+/// The trading session a time belongs to: times at or after the session
+/// close count towards the next day's session.
+pub(crate) fn session_date(timestamp: DateTime<Utc>, session_close: NaiveTime) -> NaiveDate {
+    let until_midnight = Duration::days(1) - session_close.signed_duration_since(NaiveTime::MIN);
+    (timestamp + until_midnight).date_naive()
+}
+
+/// Returns per period, where `period_start` maps a time to the first day
+/// of its period.
+fn period_returns(
+    equity_curve: &[EquityPoint],
+    initial_balance: Decimal,
+    period_start: impl Fn(DateTime<Utc>) -> NaiveDate,
+) -> Vec<PeriodReturn> {
+    let mut periods: Vec<PeriodReturn> = Vec::new();
+    let mut opening = initial_balance;
+    let mut closing = initial_balance;
+    let mut current: Option<NaiveDate> = None;
+    for point in equity_curve {
+        let start = period_start(point.timestamp);
+        if let Some(period) = current.filter(|p| *p != start) {
+            periods.push(period_return(period, opening, closing));
+            opening = closing;
+        }
+        current = Some(start);
+        closing = point.equity;
+    }
+    if let Some(period) = current {
+        periods.push(period_return(period, opening, closing));
+    }
+    periods
+}
 
-    let variance: Decimal = returns
+fn period_return(start: NaiveDate, opening: Decimal, closing: Decimal) -> PeriodReturn {
+    let pnl = closing - opening;
+    PeriodReturn {
+        start,
+        pnl,
+        return_percent: if opening.is_zero() {
+            Decimal::ZERO
+        } else {
+            pnl / opening * dec!(100)
+        },
+    }
+}
+
+/// Each trade's net PnL in units of R, the average net loss of the losing
+/// trades. Empty when there are no losing trades to size R from.
+///
+/// R depends on every trade in the set, so it isn't stored on [`Trade`]: a
+/// trade's R-multiple changes with the trades it is measured against. The
+/// report lists them per trade.
+pub fn r_multiples(trades: &[Trade]) -> Vec<Decimal> {
+    let losses: Vec<Decimal> = trades
         .iter()
-        .map(|r| {
-            let diff = *r - mean;
-            diff * diff
-        })
-        .sum::<Decimal>()
-        / n;
+        .map(|t| t.net_pnl())
+        .filter(|pnl| *pnl < Decimal::ZERO)
+        .collect();
+    if losses.is_empty() {
+        return Vec::new();
+    }
+    let r = losses.iter().sum::<Decimal>().abs() / Decimal::from(losses.len());
+    trades.iter().map(|t| t.net_pnl() / r).collect()
+}
 
-    let std_dev = propbot_indicators::bollinger::decimal_sqrt(variance);
+/// Compound annual return over a stretch of the equity curve divided by
+/// its max drawdown, both as percentages of `opening_equity`.
+fn compute_return_over_drawdown(equity_curve: &[EquityPoint], opening_equity: Decimal) -> Decimal {
+    let (Some(first), Some(last)) = (equity_curve.first(), equity_curve.last()) else {
+        return Decimal::ZERO;
+    };
+    if opening_equity <= Decimal::ZERO {
+        return Decimal::ZERO;
+    }
+    let years = (last.timestamp - first.timestamp).num_seconds() as f64 / (365.25 * 86_400.0);
+    let growth = (last.equity / opening_equity).to_f64().unwrap_or_default();
+    if years <= 0.0 || growth <= 0.0 {
+        return Decimal::ZERO;
+    }
 
-    if std_dev.is_zero() {
+    let mut peak = opening_equity;
+    let mut max_drawdown = Decimal::ZERO;
+    for point in equity_curve {
+        peak = peak.max(point.equity);
+        max_drawdown = max_drawdown.max(peak - point.equity);
+    }
+    if max_drawdown.is_zero() {
         return Decimal::ZERO;
     }
 
-    // Annualize (assuming daily bars, ~252 trading days)
-    let annualization = propbot_indicators::bollinger::decimal_sqrt(dec!(252));
-    (mean / std_dev) * annualization
+    let annual_return = (growth.powf(1.0 / years) - 1.0) * 100.0;
+    let drawdown_percent = (max_drawdown / opening_equity * dec!(100))
+        .to_f64()
+        .unwrap_or_default();
+    // Very short tests annualize to absurd numbers; cap like the profit factor
+    Decimal::from_f64(annual_return / drawdown_percent)
+        .unwrap_or(dec!(999.99))
+        .clamp(dec!(-999.99), dec!(999.99))
+        .round_dp(4)
 }
 
-/// Compute annualized Sortino ratio (only downside deviation).
-fn compute_sortino(equity_curve: &[EquityPoint]) -> Decimal {
-    if equity_curve.len() < 2 {
-        return Decimal::ZERO;
+/// Longest winning and losing runs of consecutive trades, by net PnL.
+fn compute_streaks(trades: &[Trade]) -> (usize, usize) {
+    let (mut wins, mut losses) = (0, 0);
+    let (mut max_wins, mut max_losses) = (0, 0);
+    for trade in trades {
+        let pnl = trade.net_pnl();
+        wins = if pnl > Decimal::ZERO { wins + 1 } else { 0 };
+        losses = if pnl < Decimal::ZERO { losses + 1 } else { 0 };
+        max_wins = max_wins.max(wins);
+        max_losses = max_losses.max(losses);
     }
+    (max_wins, max_losses)
+}
 
-    let returns: Vec<Decimal> = equity_curve
-        .windows(2)
-        .map(|w| {
-            if w[0].equity.is_zero() {
-                Decimal::ZERO
-            } else {
-                (w[1].equity - w[0].equity) / w[0].equity
+/// The longest peak-to-new-high span, and the trough-to-recovery time of the
+/// deepest drawdown. Drawdowns still open at the end run to the last point.
+fn compute_drawdown_durations(equity_curve: &[EquityPoint]) -> (Duration, Duration) {
+    let (Some(first), Some(last)) = (equity_curve.first(), equity_curve.last()) else {
+        return (Duration::zero(), Duration::zero());
+    };
+    let mut longest = Duration::zero();
+    let (mut deepest, mut recovery) = (Decimal::ZERO, Duration::zero());
+    let (mut peak, mut peak_time) = (first.equity, first.timestamp);
+    let (mut trough, mut trough_time) = (first.equity, first.timestamp);
+
+    let mut close = |peak: Decimal,
+                     peak_time: DateTime<Utc>,
+                     trough: Decimal,
+                     trough_time: DateTime<Utc>,
+                     end: DateTime<Utc>| {
+        if trough < peak {
+            longest = longest.max(end - peak_time);
+            if peak - trough > deepest {
+                deepest = peak - trough;
+                recovery = end - trough_time;
             }
-        })
-        .collect();
+        }
+    };
+    for point in equity_curve {
+        if point.equity >= peak {
+            close(peak, peak_time, trough, trough_time, point.timestamp);
+            (peak, peak_time) = (point.equity, point.timestamp);
+            (trough, trough_time) = (point.equity, point.timestamp);
+        } else if point.equity < trough {
+            (trough, trough_time) = (point.equity, point.timestamp);
+        }
+    }
+    close(peak, peak_time, trough, trough_time, last.timestamp);
+    (longest, recovery)
+}
 
-    let n = Decimal::from(returns.len());
-    let mean: Decimal = returns.iter().sum::<Decimal>() / n;
+/// Total time with at least one trade open, counting overlaps once.
+fn time_in_market(trades: &[Trade]) -> Duration {
+    let mut spans: Vec<(DateTime<Utc>, DateTime<Utc>)> =
+        trades.iter().map(|t| (t.entry_time, t.exit_time)).collect();
+    spans.sort();
+    let mut total = Duration::zero();
+    let mut current: Option<(DateTime<Utc>, DateTime<Utc>)> = None;
+    for (start, end) in spans {
+        match current {
+            Some((_, current_end)) if start <= current_end => {
+                current = current.map(|(s, e)| (s, e.max(end)));
+            }
+            _ => {
+                if let Some((s, e)) = current {
+                    total += e - s;
+                }
+                current = Some((start, end));
+            }
+        }
+    }
+    if let Some((s, e)) = current {
+        total += e - s;
+    }
+    total
+}
+
+fn hours(duration: Duration) -> Decimal {
+    (Decimal::from(duration.num_seconds()) / dec!(3600)).round_dp(2)
+}
+
+/// Sharpe and Sortino ratios and volatility from per-day returns, all
+/// annualized. Returns `(sharpe, sortino, volatility percent)`.
+fn compute_ratios(
+    daily_returns: &[PeriodReturn],
+    days_per_year: Decimal,
+    risk_free_rate: Decimal,
+) -> (Decimal, Decimal, Decimal) {
+    if daily_returns.len() < 2 {
+        return (Decimal::ZERO, Decimal::ZERO, Decimal::ZERO);
+    }
+
+    let daily_risk_free = risk_free_rate / days_per_year;
+    let excess: Vec<Decimal> = daily_returns
+        .iter()
+        .map(|d| d.return_percent / dec!(100) - daily_risk_free)
+        .collect();
+    let n = Decimal::from(excess.len());
+    let mean = excess.iter().sum::<Decimal>() / n;
 
-    let downside_variance: Decimal = returns
+    // Sample standard deviation
+    let variance = excess
+        .iter()
+        .map(|r| (*r - mean) * (*r - mean))
+        .sum::<Decimal>()
+        / (n - Decimal::ONE);
+    let std_dev = variance.sqrt().unwrap_or_default();
+    // Downside deviation against a zero excess return
+    let downside_variance = excess
         .iter()
         .filter(|r| **r < Decimal::ZERO)
         .map(|r| r * r)
         .sum::<Decimal>()
         / n;
+    let downside_dev = downside_variance.sqrt().unwrap_or_default();
+
+    let annualization = days_per_year.sqrt().unwrap_or_default();
+    let ratio = |deviation: Decimal| {
+        if deviation.is_zero() {
+            Decimal::ZERO
+        } else {
+            (mean / deviation * annualization).round_dp(4)
+        }
+    };
+    (
+        ratio(std_dev),
+        ratio(downside_dev),
+        (std_dev * annualization * dec!(100)).round_dp(4),
+    )
+}
 
-    let downside_dev = propbot_indicators::bollinger::decimal_sqrt(downside_variance);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
 
-    if downside_dev.is_zero() {
-        return Decimal::ZERO;
+    fn at(hour: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap() + Duration::hours(hour)
+    }
+
+    fn trade(entry: i64, exit: i64, pnl: Decimal) -> Trade {
+        Trade {
+            id: Uuid::new_v4(),
+            instrument: "ES".to_string(),
+            side: Side::Buy,
+            quantity: dec!(1),
+            entry_price: dec!(4700),
+            exit_price: dec!(4700),
+            pnl,
+            commission: Decimal::ZERO,
+            entry_time: at(entry),
+            exit_time: at(exit),
+            strategy_id: None,
+            mae: dec!(50),
+            mfe: pnl.max(Decimal::ZERO),
+        }
+    }
+
+    #[test]
+    fn test_trade_and_drawdown_metrics() {
+        let trades = vec![
+            trade(0, 2, dec!(300)),
+            trade(1, 4, dec!(-100)),
+            trade(6, 8, dec!(-200)),
+            trade(30, 36, dec!(400)),
+        ];
+        // Peak 10300 at 2h, trough 10000 at 8h, new high at 36h
+        let curve: Vec<EquityPoint> = [
+            (0, dec!(10000)),
+            (2, dec!(10300)),
+            (4, dec!(10200)),
+            (8, dec!(10000)),
+            (36, dec!(10400)),
+            (48, dec!(10400)),
+        ]
+        .into_iter()
+        .map(|(hour, equity)| EquityPoint {
+            timestamp: at(hour),
+            equity,
+            drawdown: Decimal::ZERO,
+        })
+        .collect();
+        let mut account = AccountState::new(dec!(10000));
+        account.equity = dec!(10400);
+
+        let result = compute_backtest_result(
+            "test".to_string(),
+            "ES".to_string(),
+            dec!(10000),
+            account,
+            trades,
+            curve,
+            at(0),
+            at(48),
+            &MetricsConfig::default(),
+        );
+
+        // R is the average loss of 150
+        assert_eq!(result.expectancy, Some(dec!(400) / dec!(4) / dec!(150)));
+        assert_eq!(result.largest_winner, dec!(400));
+        assert_eq!(result.largest_loser, dec!(200));
+        assert_eq!(result.max_consecutive_wins, 1);
+        assert_eq!(result.max_consecutive_losses, 2);
+        assert_eq!(result.max_drawdown_duration_hours, dec!(34));
+        assert_eq!(result.max_drawdown_recovery_hours, dec!(28));
+        assert_eq!(result.avg_trade_duration_hours, dec!(3.25));
+        // 0-4h, 6-8h and 30-36h of 48h
+        assert_eq!(
+            result.time_in_market_percent,
+            dec!(12) / dec!(48) * dec!(100)
+        );
+        assert_eq!(result.avg_mae, dec!(50));
+        assert_eq!(result.avg_mfe, dec!(175));
+        assert!(result.mar_ratio > Decimal::ZERO);
+        assert_eq!(result.calmar_ratio, result.mar_ratio);
+        assert_eq!(
+            result
+                .daily_returns
+                .iter()
+                .map(|d| (d.start.day(), d.pnl))
+                .collect::<Vec<_>>(),
+            vec![(2, dec!(0)), (3, dec!(400)), (4, dec!(0))]
+        );
+    }
+
+    fn curve_result(curve: Vec<EquityPoint>, config: &MetricsConfig) -> BacktestResult {
+        let mut account = AccountState::new(dec!(10000));
+        account.equity = curve.last().unwrap().equity;
+        let (start, end) = (curve[0].timestamp, curve[curve.len() - 1].timestamp);
+        compute_backtest_result(
+            "test".to_string(),
+            "ES".to_string(),
+            dec!(10000),
+            account,
+            Vec::new(),
+            curve,
+            start,
+            end,
+            config,
+        )
+    }
+
+    #[test]
+    fn test_expectancy_undefined_without_losers() {
+        let winners = [trade(0, 2, dec!(300)), trade(4, 6, dec!(100))];
+        let curve = vec![
+            EquityPoint {
+                timestamp: at(0),
+                equity: dec!(10000),
+                drawdown: Decimal::ZERO,
+            },
+            EquityPoint {
+                timestamp: at(6),
+                equity: dec!(10400),
+                drawdown: Decimal::ZERO,
+            },
+        ];
+
+        let result = compute_backtest_result(
+            "test".to_string(),
+            "ES".to_string(),
+            dec!(10000),
+            AccountState::new(dec!(10400)),
+            winners.to_vec(),
+            curve,
+            at(0),
+            at(6),
+            &MetricsConfig::default(),
+        );
+
+        assert!(r_multiples(&winners).is_empty());
+        assert_eq!(result.expectancy, None);
+        assert_eq!(result.metric("expectancy"), None);
     }
 
-    let annualization = propbot_indicators::bollinger::decimal_sqrt(dec!(252));
-    (mean / downside_dev) * annualization
+    #[test]
+    fn test_ratios_use_daily_returns_whatever_the_bar_size() {
+        // Monday to Friday, closing up 1%, down 0.5%, up 2%, flat, down 1%
+        let closes = [
+            dec!(10100),
+            dec!(10049.5),
+            dec!(10250.49),
+            dec!(10250.49),
+            dec!(10147.9851),
+        ];
+        let point = |timestamp, equity| EquityPoint {
+            timestamp,
+            equity,
+            drawdown: Decimal::ZERO,
+        };
+        let day_closes: Vec<EquityPoint> = closes
+            .iter()
+            .enumerate()
+            .map(|(day, equity)| point(at(24 * day as i64 - 4), *equity))
+            .collect();
+        // The same days as minute bars drifting towards each close
+        let mut minutes = Vec::new();
+        let mut previous = dec!(10000);
+        for (day, close) in closes.iter().enumerate() {
+            for minute in 0..=390 {
+                let equity = previous + (*close - previous) * Decimal::from(minute) / dec!(390);
+                minutes.push(point(
+                    at(24 * day as i64 - 10) + Duration::minutes(minute),
+                    equity,
+                ));
+            }
+            previous = *close;
+        }
+
+        let daily = curve_result(day_closes, &MetricsConfig::default());
+        let intraday = curve_result(minutes, &MetricsConfig::default());
+        assert_eq!(intraday.sharpe_ratio, daily.sharpe_ratio);
+        assert_eq!(intraday.sortino_ratio, daily.sortino_ratio);
+        assert_eq!(intraday.volatility, daily.volatility);
+
+        // Mean 0.3%, sample standard deviation 1.2042%, over 252 days a year
+        assert_eq!(daily.sharpe_ratio.round_dp(2), dec!(3.95));
+        assert_eq!(daily.volatility.round_dp(2), dec!(19.12));
+
+        let with_rate = curve_result(
+            daily.equity_curve.clone(),
+            &MetricsConfig {
+                risk_free_rate: dec!(0.05),
+                ..MetricsConfig::default()
+            },
+        );
+        assert!(with_rate.sharpe_ratio < daily.sharpe_ratio);
+    }
+
+    fn point(timestamp: DateTime<Utc>, equity: Decimal) -> EquityPoint {
+        EquityPoint {
+            timestamp,
+            equity,
+            drawdown: Decimal::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_days_split_at_session_close_without_calendar() {
+        // Futures week: the Sunday evening open belongs to Monday's session
+        let mut curve = Vec::new();
+        for day in 0..5 {
+            let open = at(24 * day - 25); // 23:00 UTC the evening before
+            curve.push(point(open, dec!(10000) + Decimal::from(day)));
+            curve.push(point(
+                open + Duration::hours(20),
+                dec!(10000) + Decimal::from(day),
+            ));
+        }
+        let config = MetricsConfig {
+            session_close_utc: NaiveTime::from_hms_opt(21, 0, 0),
+            ..MetricsConfig::default()
+        };
+        let daily = daily_returns(&curve, dec!(10000), &config);
+        let days: Vec<Weekday> = daily.iter().map(|d| d.start.weekday()).collect();
+        assert_eq!(
+            days,
+            [
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri
+            ]
+        );
+        assert_eq!(config.days_per_year(&daily), dec!(252));
+
+        // Split at UTC midnight, the same week has a Sunday
+        let utc = daily_returns(&curve, dec!(10000), &MetricsConfig::default());
+        assert_eq!(utc[0].start.weekday(), Weekday::Sun);
+    }
+
+    #[test]
+    fn test_days_per_year_counts_weekend_session_days() {
+        let config = MetricsConfig::default();
+        let days = |count: i64, step: i64| -> Vec<PeriodReturn> {
+            let curve: Vec<EquityPoint> = (0..count)
+                .map(|day| point(at(24 * day * step + 12), dec!(10000)))
+                .collect();
+            daily_returns(&curve, dec!(10000), &config)
+        };
+        // Four weeks of a market open every day
+        assert_eq!(config.days_per_year(&days(28, 1)), dec!(365));
+
+        // Four weeks of weekdays with a single stray Saturday
+        let mut weekdays: Vec<PeriodReturn> = days(28, 1)
+            .into_iter()
+            .filter(|d| !matches!(d.start.weekday(), Weekday::Sat | Weekday::Sun))
+            .collect();
+        assert_eq!(config.days_per_year(&weekdays), dec!(252));
+        weekdays.extend(
+            days(28, 1)
+                .into_iter()
+                .find(|d| d.start.weekday() == Weekday::Sat),
+        );
+        assert_eq!(config.days_per_year(&weekdays), dec!(252));
+    }
 }
@@ -0,0 +1,208 @@
+use propbot_core::*;
+use rust_decimal::Decimal;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Records a live session's order log and closed trades under one run ID,
+/// so they can be queried like a saved backtest's.
+///
+/// Live brokers report fills but not round trips, so trades are built here by
+/// netting fills into a position per strategy and instrument, the same way
+/// the simulated broker does. Without intratrade prices their MAE and MFE
+/// are left at zero.
+pub struct SessionRecorder {
+    pool: PgPool,
+    run_id: Uuid,
+    instrument: Instrument,
+    positions: HashMap<(Option<String>, String), Position>,
+}
+
+impl SessionRecorder {
+    /// Start recording a session trading `instrument`. `open_positions` are
+    /// the broker's positions at the start, so fills that close them make
+    /// trades rather than opening new positions.
+    pub fn new(pool: PgPool, instrument: Instrument, open_positions: Vec<Position>) -> Self {
+        let positions = open_positions
+            .into_iter()
+            .map(|p| ((p.strategy_id.clone(), p.instrument.clone()), p))
+            .collect();
+        Self {
+            pool,
+            run_id: Uuid::new_v4(),
+            instrument,
+            positions,
+        }
+    }
+
+    /// The session's run ID, for looking up its orders and trades.
+    pub fn run_id(&self) -> Uuid {
+        self.run_id
+    }
+
+    /// Record the order updates `broker` has produced since the last call and
+    /// the trades their fills closed. Call it after each message the session
+    /// handles, so updates are written in the order they happened.
+    ///
+    /// Returns the number of order updates and trades written.
+    pub async fn record(&mut self, broker: &mut dyn Broker) -> Result<(u64, u64), sqlx::Error> {
+        let updates = broker.take_order_updates();
+        let trades: Vec<Trade> = updates
+            .iter()
+            .filter_map(|u| self.apply_fill(u.fill.as_ref()?, u.order.strategy_id.clone()))
+            .collect();
+        let orders =
+            propbot_data::db::record_order_updates(&self.pool, self.run_id, &updates).await?;
+        let trades = propbot_data::db::record_trades(&self.pool, self.run_id, &trades).await?;
+        Ok((orders, trades))
+    }
+
+    /// Net a fill into its strategy's position, returning the trade it
+    /// closed, if any. Fills in other instruments are ignored.
+    fn apply_fill(&mut self, fill: &Fill, strategy_id: Option<String>) -> Option<Trade> {
+        if fill.instrument != self.instrument.symbol {
+            return None;
+        }
+        let key = (strategy_id.clone(), fill.instrument.clone());
+        let open = |quantity| Position {
+            instrument: fill.instrument.clone(),
+            side: fill.side,
+            quantity,
+            avg_entry_price: fill.price,
+            unrealized_pnl: Decimal::ZERO,
+            realized_pnl: Decimal::ZERO,
+            opened_at: fill.timestamp,
+            strategy_id: strategy_id.clone(),
+        };
+
+        let Some(pos) = self.positions.get_mut(&key) else {
+            self.positions.insert(key, open(fill.quantity));
+            return None;
+        };
+        if pos.side == fill.side {
+            let cost = pos.avg_entry_price * pos.quantity + fill.price * fill.quantity;
+            pos.quantity += fill.quantity;
+            pos.avg_entry_price = cost / pos.quantity;
+            return None;
+        }
+
+        // Closing or reversing
+        let close_qty = fill.quantity.min(pos.quantity);
+        let price_diff = match pos.side {
+            Side::Buy => fill.price - pos.avg_entry_price,
+            Side::Sell => pos.avg_entry_price - fill.price,
+        };
+        let trade = Trade {
+            id: Uuid::new_v4(),
+            instrument: fill.instrument.clone(),
+            side: pos.side,
+            quantity: close_qty,
+            entry_price: pos.avg_entry_price,
+            exit_price: fill.price,
+            pnl: price_diff / self.instrument.tick_size * self.instrument.tick_value * close_qty,
+            commission: fill.commission,
+            entry_time: pos.opened_at,
+            exit_time: fill.timestamp,
+            strategy_id: pos.strategy_id.clone(),
+            mae: Decimal::ZERO,
+            mfe: Decimal::ZERO,
+        };
+        pos.quantity -= close_qty;
+        if pos.quantity.is_zero() {
+            self.positions.remove(&key);
+        }
+        if fill.quantity > close_qty {
+            self.positions.insert(key, open(fill.quantity - close_qty));
+        }
+        Some(trade)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Duration, Utc};
+    use rust_decimal_macros::dec;
+
+    fn fill(minute: i64, side: Side, quantity: Decimal, price: Decimal) -> Fill {
+        let start: DateTime<Utc> = "2024-03-05T14:30:00Z".parse().unwrap();
+        Fill {
+            order_id: Uuid::new_v4(),
+            instrument: "ES".to_string(),
+            side,
+            quantity,
+            price,
+            commission: dec!(2),
+            timestamp: start + Duration::minutes(minute),
+        }
+    }
+
+    fn recorder(open_positions: Vec<Position>) -> SessionRecorder {
+        // Never connects: only the trade building is exercised
+        let pool = PgPool::connect_lazy("postgres://localhost/unused").unwrap();
+        let es = Instrument {
+            symbol: "ES".to_string(),
+            asset_class: AssetClass::Futures,
+            tick_size: dec!(0.25),
+            tick_value: dec!(12.50),
+            contract_size: dec!(50),
+            currency: "USD".to_string(),
+            exchange: None,
+        };
+        SessionRecorder::new(pool, es, open_positions)
+    }
+
+    #[tokio::test]
+    async fn test_fills_build_trades_per_strategy() {
+        let mut recorder = recorder(Vec::new());
+        let a = Some("a".to_string());
+        let b = Some("b".to_string());
+
+        assert!(recorder
+            .apply_fill(&fill(0, Side::Buy, dec!(1), dec!(4700)), a.clone())
+            .is_none());
+        assert!(recorder
+            .apply_fill(&fill(1, Side::Buy, dec!(1), dec!(4702)), a.clone())
+            .is_none());
+        assert!(recorder
+            .apply_fill(&fill(2, Side::Sell, dec!(1), dec!(4705)), b.clone())
+            .is_none());
+        // Sells 3 against a's long of 2 at 4701: closes it and goes short 1
+        let closed = recorder
+            .apply_fill(&fill(3, Side::Sell, dec!(3), dec!(4703)), a.clone())
+            .unwrap();
+        let covered = recorder
+            .apply_fill(&fill(4, Side::Buy, dec!(1), dec!(4700)), b.clone())
+            .unwrap();
+
+        assert_eq!((closed.side, closed.quantity), (Side::Buy, dec!(2)));
+        assert_eq!(closed.entry_price, dec!(4701));
+        // 2 points is 8 ticks of $12.50, on 2 contracts
+        assert_eq!(closed.pnl, dec!(200));
+        assert_eq!(closed.strategy_id, a);
+        assert_eq!((covered.side, covered.pnl), (Side::Sell, dec!(250)));
+        assert_eq!(recorder.positions[&(a, "ES".to_string())].side, Side::Sell);
+    }
+
+    #[tokio::test]
+    async fn test_fill_closes_position_open_at_start() {
+        let long = Position {
+            instrument: "ES".to_string(),
+            side: Side::Buy,
+            quantity: dec!(1),
+            avg_entry_price: dec!(4690),
+            unrealized_pnl: Decimal::ZERO,
+            realized_pnl: Decimal::ZERO,
+            opened_at: "2024-03-05T13:00:00Z".parse().unwrap(),
+            strategy_id: None,
+        };
+        let mut recorder = recorder(vec![long]);
+
+        let trade = recorder
+            .apply_fill(&fill(0, Side::Sell, dec!(1), dec!(4700)), None)
+            .unwrap();
+
+        assert_eq!(trade.pnl, dec!(500));
+        assert!(recorder.positions.is_empty());
+    }
+}
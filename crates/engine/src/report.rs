@@ -0,0 +1,465 @@
+use chrono::{DateTime, Datelike, Utc};
+use propbot_core::*;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::analytics::{BucketStats, TradeAnalytics};
+use crate::metrics::{monthly_returns, r_multiples};
+
+/// Most points drawn per chart; longer curves are thinned out.
+const MAX_CHART_POINTS: usize = 1_000;
+
+const CHART_WIDTH: f64 = 900.0;
+const CHART_HEIGHT: f64 = 240.0;
+
+/// Write a backtest report into `dir`, creating it if needed:
+///
+/// - `report.html`: self-contained report with charts and tables
+/// - `result.json`: the full [`BacktestResult`]
+/// - `trades.csv` and `equity.csv`
+pub fn write_report(
+    result: &BacktestResult,
+    analytics: &TradeAnalytics,
+    dir: &Path,
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join("report.html"), render_html(result, analytics))?;
+    std::fs::write(
+        dir.join("result.json"),
+        serde_json::to_string_pretty(result)?,
+    )?;
+
+    let mut trades = csv::Writer::from_path(dir.join("trades.csv"))?;
+    for trade in &result.trades {
+        trades.serialize(trade)?;
+    }
+    trades.flush()?;
+
+    let mut equity = csv::Writer::from_path(dir.join("equity.csv"))?;
+    for point in &result.equity_curve {
+        equity.serialize(point)?;
+    }
+    equity.flush()?;
+    Ok(())
+}
+
+/// Render a backtest as a single HTML page with no external resources.
+pub fn render_html(result: &BacktestResult, analytics: &TradeAnalytics) -> String {
+    let mut html = String::new();
+    let title = format!("{} on {}", result.strategy_id, result.instrument);
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Backtest: {title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <h1>{title}</h1>\n<p class=\"muted\">{} &rarr; {} &middot; backtest {}</p>\n",
+        result.start_date.format("%Y-%m-%d %H:%M"),
+        result.end_date.format("%Y-%m-%d %H:%M"),
+        result.id,
+        title = escape(&title),
+    );
+
+    html.push_str("<h2>Summary</h2>\n<table class=\"summary\">\n");
+    let _ = writeln!(
+        html,
+        "<tr><th>initial_balance</th><td>{:.2}</td></tr>",
+        result.initial_balance
+    );
+    for (name, value) in BacktestResult::METRICS {
+        let _ = writeln!(
+            html,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            name,
+            number(value(result))
+        );
+    }
+    html.push_str("</table>\n");
+
+    let equity: Vec<(DateTime<Utc>, Decimal)> = result
+        .equity_curve
+        .iter()
+        .map(|p| (p.timestamp, p.equity))
+        .collect();
+    let drawdown: Vec<(DateTime<Utc>, Decimal)> = result
+        .equity_curve
+        .iter()
+        .map(|p| (p.timestamp, -p.drawdown))
+        .collect();
+    html.push_str("<h2>Equity</h2>\n");
+    html.push_str(&chart(&equity, "#2563eb", false));
+    html.push_str("<h2>Drawdown</h2>\n");
+    html.push_str(&chart(&drawdown, "#dc2626", true));
+
+    html.push_str("<h2>Monthly returns (%)</h2>\n");
+    // Results saved before monthly returns were recorded
+    let months = if result.monthly_returns.is_empty() {
+        monthly_returns(&result.equity_curve, result.initial_balance)
+    } else {
+        result.monthly_returns.clone()
+    };
+    html.push_str(&monthly_table(&months));
+
+    html.push_str("<h2>Trade analytics</h2>\n");
+    for (title, buckets) in [
+        ("Entry hour (UTC)", &analytics.by_hour),
+        ("Weekday", &analytics.by_weekday),
+        ("Month", &analytics.by_month),
+        ("Holding time", &analytics.by_holding_time),
+        ("Direction", &analytics.by_direction),
+        ("ATR quartile at entry", &analytics.by_volatility),
+    ] {
+        let _ = writeln!(html, "<h3>{}</h3>", title);
+        html.push_str(&bucket_table(buckets));
+    }
+
+    let _ = writeln!(html, "<h2>Risk events ({})</h2>", result.risk_events.len());
+    if result.risk_events.is_empty() {
+        html.push_str("<p class=\"muted\">None</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>Time</th><th>Event</th><th>Detail</th></tr>\n");
+        for record in &result.risk_events {
+            let (event, detail) = describe_risk_event(&record.event);
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td class=\"text\">{}</td></tr>",
+                record.timestamp.format("%Y-%m-%d %H:%M"),
+                escape(&event),
+                escape(&detail)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    let _ = writeln!(html, "<h2>Trades ({})</h2>", result.trades.len());
+    html.push_str(
+        "<table>\n<tr><th>Entry</th><th>Exit</th><th>Side</th><th>Qty</th>\
+         <th>Entry price</th><th>Exit price</th><th>PnL</th><th>Commission</th>\
+         <th>Net PnL</th><th>R</th><th>MAE</th><th>MFE</th></tr>\n",
+    );
+    let r = r_multiples(&result.trades);
+    for (i, trade) in result.trades.iter().enumerate() {
+        let net = trade.net_pnl();
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{:?}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td>{:.2}</td><td>{:.2}</td><td class=\"{}\">{:.2}</td><td>{}</td>\
+             <td>{:.2}</td><td>{:.2}</td></tr>",
+            trade.entry_time.format("%Y-%m-%d %H:%M"),
+            trade.exit_time.format("%Y-%m-%d %H:%M"),
+            trade.side,
+            trade.quantity,
+            trade.entry_price,
+            trade.exit_price,
+            trade.pnl,
+            trade.commission,
+            sign_class(net),
+            net,
+            number(r.get(i).copied()),
+            trade.mae,
+            trade.mfe
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+/// An SVG line chart of a time series, thinned to [`MAX_CHART_POINTS`].
+fn chart(series: &[(DateTime<Utc>, Decimal)], color: &str, fill: bool) -> String {
+    if series.len() < 2 {
+        return "<p class=\"muted\">Not enough data</p>\n".to_string();
+    }
+    let step = series.len().div_ceil(MAX_CHART_POINTS);
+    let mut points: Vec<(f64, f64)> = series
+        .iter()
+        .step_by(step)
+        .chain(series.last())
+        .map(|(t, v)| (t.timestamp() as f64, v.to_f64().unwrap_or_default()))
+        .collect();
+    points.dedup_by(|a, b| a.0 == b.0);
+
+    let (x_min, x_max) = (points[0].0, points[points.len() - 1].0);
+    let y_min = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+    let y_max = points.iter().map(|p| p.1).fold(f64::NEG_INFINITY, f64::max);
+    let x_span = (x_max - x_min).max(1.0);
+    let y_span = (y_max - y_min).max(1e-9);
+    let (left, bottom) = (70.0, 20.0);
+    let plot_width = CHART_WIDTH - left - 10.0;
+    let plot_height = CHART_HEIGHT - bottom - 10.0;
+    let x = |v: f64| left + (v - x_min) / x_span * plot_width;
+    let y = |v: f64| 10.0 + (y_max - v) / y_span * plot_height;
+
+    let mut path = points
+        .iter()
+        .map(|(px, py)| format!("{:.1},{:.1}", x(*px), y(*py)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let shape = if fill {
+        // Close the area against the top edge (zero drawdown)
+        path = format!(
+            "{:.1},{:.1} {} {:.1},{:.1}",
+            x(x_min),
+            y(y_max),
+            path,
+            x(x_max),
+            y(y_max)
+        );
+        format!(
+            "<polygon points=\"{path}\" fill=\"{color}\" fill-opacity=\"0.3\" stroke=\"{color}\"/>"
+        )
+    } else {
+        format!(
+            "<polyline points=\"{path}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"1.5\"/>"
+        )
+    };
+    let date = |v: f64| {
+        DateTime::from_timestamp(v as i64, 0)
+            .unwrap_or_default()
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+
+    format!(
+        "<svg viewBox=\"0 0 {w} {h}\" width=\"100%\" role=\"img\">\n\
+         <rect x=\"{left}\" y=\"10\" width=\"{pw}\" height=\"{ph}\" fill=\"#f8fafc\" stroke=\"#e2e8f0\"/>\n\
+         {shape}\n\
+         <text x=\"{lx}\" y=\"{ty}\" text-anchor=\"end\">{y_max:.0}</text>\n\
+         <text x=\"{lx}\" y=\"{by}\" text-anchor=\"end\">{y_min:.0}</text>\n\
+         <text x=\"{left}\" y=\"{h}\">{start}</text>\n\
+         <text x=\"{right}\" y=\"{h}\" text-anchor=\"end\">{end}</text>\n\
+         </svg>\n",
+        w = CHART_WIDTH,
+        h = CHART_HEIGHT,
+        pw = plot_width,
+        ph = plot_height,
+        lx = left - 6.0,
+        ty = 20.0,
+        by = 10.0 + plot_height,
+        right = CHART_WIDTH - 10.0,
+        start = date(x_min),
+        end = date(x_max),
+    )
+}
+
+/// A year-by-month table of returns, with each year's compounded total.
+fn monthly_table(months: &[PeriodReturn]) -> String {
+    if months.is_empty() {
+        return "<p class=\"muted\">No data</p>\n".to_string();
+    }
+    let mut html = String::from("<table>\n<tr><th>Year</th>");
+    for month in [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ] {
+        let _ = write!(html, "<th>{}</th>", month);
+    }
+    html.push_str("<th>Year</th></tr>\n");
+
+    let first_year = months[0].start.year();
+    let last_year = months[months.len() - 1].start.year();
+    for year in first_year..=last_year {
+        let _ = write!(html, "<tr><th>{}</th>", year);
+        let mut growth = Decimal::ONE;
+        for month in 1..=12 {
+            match months
+                .iter()
+                .find(|m| m.start.year() == year && m.start.month() == month)
+            {
+                Some(m) => {
+                    growth *= Decimal::ONE + m.return_percent / Decimal::ONE_HUNDRED;
+                    let _ = write!(
+                        html,
+                        "<td class=\"{}\">{:.2}</td>",
+                        sign_class(m.return_percent),
+                        m.return_percent
+                    );
+                }
+                None => html.push_str("<td></td>"),
+            }
+        }
+        let total = (growth - Decimal::ONE) * Decimal::ONE_HUNDRED;
+        let _ = writeln!(
+            html,
+            "<td class=\"{}\"><b>{:.2}</b></td></tr>",
+            sign_class(total),
+            total
+        );
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn bucket_table(buckets: &[BucketStats]) -> String {
+    if buckets.is_empty() {
+        return "<p class=\"muted\">No data</p>\n".to_string();
+    }
+    let mut html = String::from(
+        "<table>\n<tr><th></th><th>Trades</th><th>Net PnL</th><th>Avg PnL</th>\
+         <th>Win rate (%)</th><th>Expectancy (R)</th></tr>\n",
+    );
+    for bucket in buckets {
+        let _ = writeln!(
+            html,
+            "<tr><th>{}</th><td>{}</td><td class=\"{}\">{:.2}</td><td>{:.2}</td>\
+             <td>{:.1}</td><td>{}</td></tr>",
+            escape(&bucket.label),
+            bucket.trades,
+            sign_class(bucket.net_pnl),
+            bucket.net_pnl,
+            bucket.avg_pnl,
+            bucket.win_rate,
+            number(bucket.expectancy)
+        );
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+fn describe_risk_event(event: &RiskEvent) -> (String, String) {
+    match event {
+        RiskEvent::OrderBlocked { order_id, reason } => (
+            "Order blocked".to_string(),
+            format!("{}: {}", order_id, reason),
+        ),
+        RiskEvent::Violation(v) => (
+            format!("{:?}: {}", v.severity, v.rule),
+            format!(
+                "{} (value {}, limit {})",
+                v.message, v.current_value, v.threshold
+            ),
+        ),
+        RiskEvent::AutoFlatten { reason } => ("Auto flatten".to_string(), reason.clone()),
+    }
+}
+
+/// Two decimal places, or a dash for an undefined value.
+fn number(value: Option<Decimal>) -> String {
+    value.map_or_else(|| "-".to_string(), |v| format!("{:.2}", v))
+}
+
+fn sign_class(value: Decimal) -> &'static str {
+    if value.is_sign_negative() && !value.is_zero() {
+        "neg"
+    } else {
+        "pos"
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const STYLE: &str =
+    "body{font-family:system-ui,sans-serif;margin:2em auto;max-width:960px;color:#0f172a}\
+h2{margin-top:1.6em;border-bottom:1px solid #e2e8f0}\
+h3{font-size:14px;margin-bottom:.3em}\
+table{border-collapse:collapse;font-size:13px}\
+th,td{padding:3px 8px;border-bottom:1px solid #f1f5f9;text-align:right}\
+th{background:#f8fafc}\
+table.summary th{text-align:left}\
+td.text{text-align:left}\
+.pos{color:#15803d}.neg{color:#b91c1c}.muted{color:#64748b}\
+svg text{font-size:11px;fill:#475569}";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analytics::analyze_trades;
+    use crate::metrics::{compute_backtest_result, MetricsConfig};
+    use chrono::{Duration, TimeZone};
+    use rust_decimal_macros::dec;
+
+    fn result() -> BacktestResult {
+        let start = Utc.with_ymd_and_hms(2024, 1, 30, 0, 0, 0).unwrap();
+        // 10000 → 10100 by the end of January, then 9595 in February
+        let curve: Vec<EquityPoint> = [dec!(10000), dec!(10100), dec!(10000), dec!(9595)]
+            .into_iter()
+            .enumerate()
+            .map(|(i, equity)| EquityPoint {
+                timestamp: start + Duration::days(i as i64),
+                equity,
+                drawdown: dec!(10100) - equity.min(dec!(10100)),
+            })
+            .collect();
+        let trade = Trade {
+            id: uuid::Uuid::new_v4(),
+            instrument: "ES".to_string(),
+            side: Side::Buy,
+            quantity: dec!(1),
+            entry_price: dec!(4700),
+            exit_price: dec!(4690),
+            pnl: dec!(-500),
+            commission: dec!(5),
+            entry_time: start,
+            exit_time: start + Duration::days(3),
+            strategy_id: Some("test<1>".to_string()),
+            mae: dec!(750),
+            mfe: dec!(250),
+        };
+        let mut account = AccountState::new(dec!(10000));
+        account.equity = dec!(9595);
+        let mut result = compute_backtest_result(
+            "test<1>".to_string(),
+            "ES".to_string(),
+            dec!(10000),
+            account,
+            vec![trade],
+            curve,
+            start,
+            start + Duration::days(3),
+            &MetricsConfig::default(),
+        );
+        result.risk_events.push(RiskEventRecord {
+            timestamp: start + Duration::days(3),
+            event: RiskEvent::AutoFlatten {
+                reason: "Daily loss limit".to_string(),
+            },
+        });
+        result
+    }
+
+    #[test]
+    fn test_monthly_returns_chain_month_end_equity() {
+        let result = result();
+        let months = monthly_returns(&result.equity_curve, result.initial_balance);
+        assert_eq!(
+            months
+                .iter()
+                .map(|m| (m.start.to_string(), m.pnl, m.return_percent))
+                .collect::<Vec<_>>(),
+            vec![
+                ("2024-01-01".to_string(), dec!(100), dec!(1)),
+                ("2024-02-01".to_string(), dec!(-505), dec!(-5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_report_files() {
+        let result = result();
+        let dir = std::env::temp_dir().join(format!("propbot-report-{}", result.id));
+
+        write_report(&result, &analyze_trades(&result.trades, None), &dir).unwrap();
+
+        let html = std::fs::read_to_string(dir.join("report.html")).unwrap();
+        assert!(html.contains("<h1>test&lt;1&gt; on ES</h1>"));
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert!(html.contains("<td class=\"neg\">-5.00</td>"));
+        assert!(html.contains("Daily loss limit"));
+        assert!(html.contains("<tr><th>Long</th><td>1</td>"));
+
+        let json: BacktestResult =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("result.json")).unwrap())
+                .unwrap();
+        assert_eq!(json.risk_events.len(), 1);
+        let trades = std::fs::read_to_string(dir.join("trades.csv")).unwrap();
+        assert_eq!(trades.lines().count(), 2);
+        assert!(trades.starts_with("id,instrument,side,"));
+        let equity = std::fs::read_to_string(dir.join("equity.csv")).unwrap();
+        assert_eq!(equity.lines().count(), 5);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -61,13 +61,10 @@ impl Macd {
         let fast = self.fast_ema.next(value);
         let slow = self.slow_ema.next(value);
 
-        match (fast, slow) {
-            (Some(f), Some(s)) => {
-                let macd = f - s;
-                self.macd_line = Some(macd);
-                self.signal_line = self.signal_ema.next(macd);
-            }
-            _ => {}
+        if let (Some(f), Some(s)) = (fast, slow) {
+            let macd = f - s;
+            self.macd_line = Some(macd);
+            self.signal_line = self.signal_ema.next(macd);
         }
 
         self.output()
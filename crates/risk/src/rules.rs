@@ -1,7 +1,8 @@
 use crate::profiles::PropFirmProfile;
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Utc};
 use propbot_core::*;
 use rust_decimal::Decimal;
+use std::collections::BTreeSet;
 use tracing::{info, warn};
 
 /// Prop firm risk manager that enforces evaluation/funded account rules.
@@ -19,6 +20,12 @@ pub struct PropFirmRiskManager {
     initial_balance: Decimal,
     /// Total open position size across all instruments.
     total_position_size: Decimal,
+    /// Exchange calendar; orders are rejected while the market is closed.
+    calendar: Option<TradingCalendar>,
+    /// Simulated time in backtests; the wall clock is used when `None`.
+    clock: Option<DateTime<Utc>>,
+    /// Trading days on which orders were filled.
+    traded_days: BTreeSet<NaiveDate>,
 }
 
 impl PropFirmRiskManager {
@@ -33,13 +40,49 @@ impl PropFirmRiskManager {
             high_water_mark: initial,
             initial_balance: initial,
             total_position_size: Decimal::ZERO,
+            calendar: None,
+            clock: None,
+            traded_days: BTreeSet::new(),
         }
     }
 
+    /// Use an exchange calendar for market hours and trading days.
+    pub fn with_calendar(mut self, calendar: TradingCalendar) -> Self {
+        self.calendar = Some(calendar);
+        self
+    }
+
     pub fn profile(&self) -> &PropFirmProfile {
         &self.profile
     }
 
+    pub fn calendar(&self) -> Option<&TradingCalendar> {
+        self.calendar.as_ref()
+    }
+
+    /// Set the current time, for backtests.
+    pub fn set_time(&mut self, timestamp: DateTime<Utc>) {
+        self.clock = Some(timestamp);
+    }
+
+    /// Record a fill, counting the trading day it happened on.
+    pub fn record_fill(&mut self, timestamp: DateTime<Utc>) {
+        let day = match &self.calendar {
+            Some(calendar) => calendar.session_date(timestamp),
+            None => timestamp.date_naive(),
+        };
+        self.traded_days.insert(day);
+    }
+
+    /// Number of distinct trading days with at least one fill.
+    pub fn trading_days(&self) -> usize {
+        self.traded_days.len()
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.clock.unwrap_or_else(Utc::now)
+    }
+
     /// Check the daily loss limit.
     fn check_daily_loss(&self) -> Option<RiskViolation> {
         let daily_loss = -self.daily_pnl;
@@ -143,7 +186,7 @@ impl PropFirmRiskManager {
             self.profile.trading_start_utc,
             self.profile.trading_end_utc,
         ) {
-            let now = Utc::now().time();
+            let now = self.now().time();
             if now < start || now > end {
                 return Some(RiskViolation {
                     rule: "trading_hours".to_string(),
@@ -161,6 +204,26 @@ impl PropFirmRiskManager {
         }
         None
     }
+
+    /// Check that the exchange is open, if a calendar is set.
+    fn check_market_open(&self) -> Option<RiskViolation> {
+        let calendar = self.calendar.as_ref()?;
+        let now = self.now();
+        if calendar.is_open(now) {
+            return None;
+        }
+        Some(RiskViolation {
+            rule: "market_closed".to_string(),
+            message: format!(
+                "{} is closed at {}",
+                calendar.name,
+                now.format("%Y-%m-%d %H:%M")
+            ),
+            current_value: now.to_string(),
+            threshold: calendar.name.clone(),
+            severity: RiskSeverity::Critical,
+        })
+    }
 }
 
 impl RiskManager for PropFirmRiskManager {
@@ -170,6 +233,10 @@ impl RiskManager for PropFirmRiskManager {
             return RiskDecision::Rejected("Trading is halted due to risk breach".to_string());
         }
 
+        if let Some(violation) = self.check_market_open() {
+            return RiskDecision::Rejected(violation.message);
+        }
+
         // Check trading hours
         if let Some(violation) = self.check_trading_hours() {
             if violation.severity == RiskSeverity::Critical || violation.severity == RiskSeverity::Breach {
@@ -286,6 +353,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_calendar_blocks_orders_on_holidays_and_counts_trading_days() {
+        let mut risk = PropFirmRiskManager::new(PropFirmProfile::topstep_50k())
+            .with_calendar(TradingCalendar::cme_equity());
+        let order = Order::market("ES", Side::Buy, dec!(1));
+        let account = AccountState::new(dec!(50000));
+
+        // Good Friday
+        risk.set_time("2024-03-29T15:00:00Z".parse().unwrap());
+        match risk.evaluate_order(&order, &account) {
+            RiskDecision::Rejected(msg) => assert!(msg.contains("closed"), "{}", msg),
+            _ => panic!("Expected rejection"),
+        }
+        risk.set_time("2024-03-28T15:00:00Z".parse().unwrap());
+        assert!(matches!(
+            risk.evaluate_order(&order, &account),
+            RiskDecision::Approved
+        ));
+
+        // Thursday evening trades for Monday's session
+        risk.record_fill("2024-03-28T15:00:00Z".parse().unwrap());
+        risk.record_fill("2024-03-28T23:00:00Z".parse().unwrap());
+        risk.record_fill("2024-04-01T14:00:00Z".parse().unwrap());
+        assert_eq!(risk.trading_days(), 2);
+    }
+
     #[test]
     fn test_position_size_limit() {
         let profile = PropFirmProfile::topstep_50k(); // max 5 contracts
@@ -17,7 +17,6 @@ pub struct DonchianBreakoutStrategy {
     config: DonchianBreakoutConfig,
     channel: DonchianChannel,
     atr: Atr,
-    position: Option<Side>,
     stop_price: Option<Decimal>,
     instrument: String,
 }
@@ -48,7 +47,6 @@ impl std::fmt::Debug for DonchianBreakoutStrategy {
         f.debug_struct("DonchianBreakoutStrategy")
             .field("id", &self.id)
             .field("config", &self.config)
-            .field("position", &self.position)
             .field("stop_price", &self.stop_price)
             .field("instrument", &self.instrument)
             .finish()
@@ -65,7 +63,6 @@ impl DonchianBreakoutStrategy {
             config,
             channel,
             atr,
-            position: None,
             stop_price: None,
             instrument,
         }
@@ -82,13 +79,19 @@ impl Strategy for DonchianBreakoutStrategy {
         "Donchian Breakout"
     }
 
-    async fn on_bar(&mut self, bar: &Bar) -> Vec<Signal> {
+    async fn on_bar(&mut self, bar: &Bar, ctx: &mut StrategyContext) -> Vec<Signal> {
         let donchian = self.channel.next_hl(bar.high, bar.low);
         let atr = self.atr.next_hlc(bar.high, bar.low, bar.close);
         let mut signals = Vec::new();
 
+        // Forget the stop once flat (stopped out, flattened by risk, or entry rejected)
+        let position = ctx.position_side(&self.instrument);
+        if position.is_none() {
+            self.stop_price = None;
+        }
+
         // Check stop loss first
-        if let (Some(side), Some(stop)) = (self.position, self.stop_price) {
+        if let (Some(side), Some(stop)) = (position, self.stop_price) {
             let stopped = match side {
                 Side::Buy => bar.low <= stop,
                 Side::Sell => bar.high >= stop,
@@ -108,14 +111,13 @@ impl Strategy for DonchianBreakoutStrategy {
                     timestamp: bar.timestamp,
                     metadata: None,
                 });
-                self.position = None;
                 self.stop_price = None;
                 return signals;
             }
         }
 
         // Update trailing stop
-        if let (Some(side), Some(atr_val)) = (self.position, atr) {
+        if let (Some(side), Some(atr_val)) = (position, atr) {
             let trail = atr_val * self.config.atr_stop_multiplier;
             let new_stop = match side {
                 Side::Buy => bar.close - trail,
@@ -140,7 +142,7 @@ impl Strategy for DonchianBreakoutStrategy {
 
         // Entry signals
         if let (Some(donchian_out), Some(atr_val)) = (donchian, atr) {
-            if self.position.is_none() {
+            if position.is_none() {
                 // Breakout above upper band
                 if bar.close > donchian_out.upper {
                     let trail = atr_val * self.config.atr_stop_multiplier;
@@ -154,7 +156,6 @@ impl Strategy for DonchianBreakoutStrategy {
                         timestamp: bar.timestamp,
                         metadata: None,
                     });
-                    self.position = Some(Side::Buy);
                     self.stop_price = Some(bar.close - trail);
                 }
                 // Breakout below lower band
@@ -170,7 +171,6 @@ impl Strategy for DonchianBreakoutStrategy {
                         timestamp: bar.timestamp,
                         metadata: None,
                     });
-                    self.position = Some(Side::Sell);
                     self.stop_price = Some(bar.close + trail);
                 }
             }
@@ -179,12 +179,11 @@ impl Strategy for DonchianBreakoutStrategy {
         signals
     }
 
-    async fn on_fill(&mut self, _fill: &Fill) {}
+    async fn on_fill(&mut self, _fill: &Fill, _ctx: &mut StrategyContext) {}
 
     fn reset(&mut self) {
         self.channel.reset();
         self.atr.reset();
-        self.position = None;
         self.stop_price = None;
     }
 }
@@ -18,7 +18,6 @@ pub struct MaCrossoverStrategy {
     slow_ma: Box<dyn Indicator>,
     prev_fast: Option<Decimal>,
     prev_slow: Option<Decimal>,
-    position: Option<Side>,
     instrument: String,
 }
 
@@ -51,7 +50,6 @@ impl std::fmt::Debug for MaCrossoverStrategy {
             .field("config", &self.config)
             .field("prev_fast", &self.prev_fast)
             .field("prev_slow", &self.prev_slow)
-            .field("position", &self.position)
             .field("instrument", &self.instrument)
             .finish()
     }
@@ -75,7 +73,6 @@ impl MaCrossoverStrategy {
             slow_ma,
             prev_fast: None,
             prev_slow: None,
-            position: None,
             instrument,
         }
     }
@@ -91,7 +88,8 @@ impl Strategy for MaCrossoverStrategy {
         "MA Crossover"
     }
 
-    async fn on_bar(&mut self, bar: &Bar) -> Vec<Signal> {
+    async fn on_bar(&mut self, bar: &Bar, ctx: &mut StrategyContext) -> Vec<Signal> {
+        let position = ctx.position_side(&self.instrument);
         let fast = self.fast_ma.next(bar.close);
         let slow = self.slow_ma.next(bar.close);
 
@@ -102,7 +100,7 @@ impl Strategy for MaCrossoverStrategy {
                 // Bullish crossover: fast crosses above slow
                 if prev_f <= prev_s && fast_val > slow_val {
                     // Close any short position first
-                    if self.position == Some(Side::Sell) {
+                    if position == Some(Side::Sell) {
                         signals.push(Signal {
                             id: Uuid::new_v4(),
                             instrument: self.instrument.clone(),
@@ -125,12 +123,11 @@ impl Strategy for MaCrossoverStrategy {
                         timestamp: bar.timestamp,
                         metadata: None,
                     });
-                    self.position = Some(Side::Buy);
                 }
                 // Bearish crossover: fast crosses below slow
                 else if prev_f >= prev_s && fast_val < slow_val {
                     // Close any long position first
-                    if self.position == Some(Side::Buy) {
+                    if position == Some(Side::Buy) {
                         signals.push(Signal {
                             id: Uuid::new_v4(),
                             instrument: self.instrument.clone(),
@@ -153,7 +150,6 @@ impl Strategy for MaCrossoverStrategy {
                         timestamp: bar.timestamp,
                         metadata: None,
                     });
-                    self.position = Some(Side::Sell);
                 }
             }
 
@@ -164,13 +160,12 @@ impl Strategy for MaCrossoverStrategy {
         signals
     }
 
-    async fn on_fill(&mut self, _fill: &Fill) {}
+    async fn on_fill(&mut self, _fill: &Fill, _ctx: &mut StrategyContext) {}
 
     fn reset(&mut self) {
         self.fast_ma.reset();
         self.slow_ma.reset();
         self.prev_fast = None;
         self.prev_slow = None;
-        self.position = None;
     }
 }
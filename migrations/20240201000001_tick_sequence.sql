@@ -0,0 +1,14 @@
+-- Give ticks a natural key so re-importing a file upserts instead of
+-- duplicating rows. `seq` orders ticks that share a timestamp, in file order.
+ALTER TABLE ticks ADD COLUMN IF NOT EXISTS seq INTEGER NOT NULL DEFAULT 0;
+
+UPDATE ticks t
+SET seq = numbered.seq
+FROM (
+    SELECT id, (ROW_NUMBER() OVER (PARTITION BY instrument, timestamp ORDER BY id) - 1)::INTEGER AS seq
+    FROM ticks
+) numbered
+WHERE t.id = numbered.id AND t.seq <> numbered.seq;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_ticks_instrument_ts_seq ON ticks (instrument, timestamp, seq);
+DROP INDEX IF EXISTS idx_ticks_instrument_ts;
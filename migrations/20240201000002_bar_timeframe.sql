@@ -0,0 +1,37 @@
+-- Store bars of several timeframes per instrument
+
+ALTER TABLE bars ADD COLUMN IF NOT EXISTS timeframe TEXT NOT NULL DEFAULT '1m';
+
+-- Existing rows get the timeframe implied by the smallest gap between bars,
+-- as aggregation::infer_timeframe does
+WITH gaps AS (
+    SELECT instrument,
+           EXTRACT(EPOCH FROM timestamp - LAG(timestamp) OVER (
+               PARTITION BY instrument ORDER BY timestamp
+           ))::BIGINT AS gap
+    FROM bars
+),
+smallest AS (
+    SELECT instrument, MIN(gap) AS gap
+    FROM gaps
+    WHERE gap > 0
+    GROUP BY instrument
+)
+UPDATE bars
+SET timeframe = CASE
+    WHEN s.gap >= 28 * 86400 THEN '1mo'
+    WHEN s.gap % (7 * 86400) = 0 THEN '1w'
+    WHEN s.gap % 86400 = 0 THEN '1d'
+    WHEN s.gap % 3600 = 0 THEN (s.gap / 3600) || 'h'
+    WHEN s.gap % 60 = 0 THEN (s.gap / 60) || 'm'
+    ELSE s.gap || 's'
+END
+FROM smallest s
+WHERE bars.instrument = s.instrument;
+
+ALTER TABLE bars ALTER COLUMN timeframe DROP DEFAULT;
+
+ALTER TABLE bars DROP CONSTRAINT IF EXISTS bars_pkey;
+ALTER TABLE bars ADD PRIMARY KEY (instrument, timeframe, timestamp);
+
+DROP INDEX IF EXISTS idx_bars_instrument_ts;
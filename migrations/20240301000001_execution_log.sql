@@ -0,0 +1,33 @@
+-- Record every order state change and closed trade, per run. A run is a
+-- backtest (its backtest_results id) or a live trading session.
+
+ALTER TABLE trades RENAME COLUMN backtest_id TO run_id;
+
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS run_id UUID;
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS trail JSONB;
+ALTER TABLE orders ADD COLUMN IF NOT EXISTS time_in_force JSONB NOT NULL DEFAULT '"gtc"';
+
+-- One row per order state change; `orders` holds each order's latest state
+CREATE TABLE IF NOT EXISTS order_events (
+    id              BIGSERIAL PRIMARY KEY,
+    order_id        UUID NOT NULL REFERENCES orders (id) ON DELETE CASCADE,
+    kind            TEXT NOT NULL,
+    status          TEXT NOT NULL,
+    quantity        DECIMAL NOT NULL,
+    filled_quantity DECIMAL NOT NULL,
+    price           DECIMAL,
+    stop_price      DECIMAL,
+    fill_quantity   DECIMAL,
+    fill_price      DECIMAL,
+    commission      DECIMAL,
+    reason          TEXT,
+    timestamp       TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_events_order ON order_events (order_id, id);
+CREATE INDEX IF NOT EXISTS idx_order_events_ts ON order_events (timestamp);
+CREATE INDEX IF NOT EXISTS idx_orders_run ON orders (run_id);
+CREATE INDEX IF NOT EXISTS idx_orders_strategy ON orders (strategy_id);
+CREATE INDEX IF NOT EXISTS idx_orders_instrument_created ON orders (instrument, created_at);
+CREATE INDEX IF NOT EXISTS idx_trades_run ON trades (run_id);
+CREATE INDEX IF NOT EXISTS idx_trades_exit_time ON trades (exit_time);
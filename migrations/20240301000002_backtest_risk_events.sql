@@ -0,0 +1,3 @@
+-- Risk rule events raised during a backtest
+
+ALTER TABLE backtest_results ADD COLUMN IF NOT EXISTS risk_events JSONB;
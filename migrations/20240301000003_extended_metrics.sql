@@ -0,0 +1,22 @@
+-- Excursions per trade and the extended backtest metrics
+
+ALTER TABLE trades
+    ADD COLUMN IF NOT EXISTS mae DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS mfe DECIMAL NOT NULL DEFAULT 0;
+
+ALTER TABLE backtest_results
+    ADD COLUMN IF NOT EXISTS calmar_ratio                DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS mar_ratio                   DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS expectancy                  DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS largest_winner              DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS largest_loser               DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS max_consecutive_wins        BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS max_consecutive_losses      BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS max_drawdown_duration_hours DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS max_drawdown_recovery_hours DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS avg_trade_duration_hours    DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS time_in_market_percent      DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS avg_mae                     DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS avg_mfe                     DECIMAL NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS monthly_returns             JSONB,
+    ADD COLUMN IF NOT EXISTS daily_returns               JSONB;
@@ -0,0 +1,3 @@
+-- Annualized volatility of daily returns
+
+ALTER TABLE backtest_results ADD COLUMN IF NOT EXISTS volatility DECIMAL NOT NULL DEFAULT 0;
@@ -0,0 +1,18 @@
+-- 20240201000002_bar_timeframe infers each instrument's timeframe from the
+-- gaps between its bars. An instrument with a single bar has no gap, so it
+-- kept the '1m' default whatever its real timeframe. Flag those rows so they
+-- can be checked and corrected by hand.
+
+DO $$
+DECLARE
+    r RECORD;
+BEGIN
+    FOR r IN
+        SELECT instrument
+        FROM bars
+        GROUP BY instrument
+        HAVING COUNT(*) = 1 AND MIN(timeframe) = '1m'
+    LOOP
+        RAISE WARNING 'bars: % has a single bar stored as 1m; its timeframe may have been guessed', r.instrument;
+    END LOOP;
+END $$;
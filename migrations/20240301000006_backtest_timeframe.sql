@@ -0,0 +1,3 @@
+-- Timeframe of the bars a backtest ran on; unknown for results saved before
+
+ALTER TABLE backtest_results ADD COLUMN IF NOT EXISTS timeframe TEXT;
@@ -0,0 +1,8 @@
+-- Expectancy is in R, sized from the average losing trade, so it is undefined
+-- for runs without losing trades. Those were saved as 0 until now.
+
+ALTER TABLE backtest_results
+    ALTER COLUMN expectancy DROP NOT NULL,
+    ALTER COLUMN expectancy DROP DEFAULT;
+
+UPDATE backtest_results SET expectancy = NULL WHERE losing_trades = 0;